@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    errors::AppError,
+    logging::{LogLevelController, LogLevelStatus},
+    state::AppState,
+};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, ToSchema)]
+pub struct LogLevelResponse {
+    /// `EnvFilter` directives currently in effect
+    pub directives: String,
+    /// Directives that will be restored when the TTL expires
+    pub revert_to: Option<String>,
+    pub ttl_remaining_seconds: Option<u64>,
+}
+
+impl From<LogLevelStatus> for LogLevelResponse {
+    fn from(status: LogLevelStatus) -> Self {
+        Self {
+            directives: status.directives,
+            revert_to: status.revert_to,
+            ttl_remaining_seconds: status.ttl_remaining.map(|ttl| ttl.as_secs()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, ToSchema)]
+pub struct UpdateLogLevelRequest {
+    /// New `EnvFilter` directives, e.g. `axum_starter=trace,tower_http=debug`
+    pub directives: String,
+    /// Revert to the previous directives after this many seconds
+    pub ttl_seconds: Option<u64>,
+}
+
+fn log_levels(state: &AppState) -> Result<&LogLevelController, AppError> {
+    state
+        .log_levels
+        .as_ref()
+        .ok_or_else(|| AppError::service_unavailable("Runtime log level changes are not available"))
+}
+
+/// Get the current log level
+///
+/// Returns the log filter directives currently in effect.
+#[utoipa::path(
+    get,
+    path = "/api/admin/log-level",
+    tag = "admin",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Current log filter", body = LogLevelResponse),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 503, description = "Runtime log level changes are not available")
+    )
+)]
+pub async fn get_log_level(
+    State(state): State<AppState>,
+) -> Result<Json<LogLevelResponse>, AppError> {
+    let status = log_levels(&state)?
+        .status()
+        .map_err(|err| AppError::internal_error(err.to_string()))?;
+
+    Ok(Json(status.into()))
+}
+
+/// Change the log level
+///
+/// Replaces the log filter directives without a restart. When `ttl_seconds` is
+/// given the previous directives are restored automatically after that time,
+/// which may be at most `logging.max_level_ttl_seconds` (24 hours by default).
+#[utoipa::path(
+    put,
+    path = "/api/admin/log-level",
+    tag = "admin",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = UpdateLogLevelRequest,
+    responses(
+        (status = 200, description = "Log filter updated", body = LogLevelResponse),
+        (status = 400, description = "Invalid filter directives or a TTL above the configured maximum"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 503, description = "Runtime log level changes are not available")
+    )
+)]
+pub async fn put_log_level(
+    State(state): State<AppState>,
+    Json(request): Json<UpdateLogLevelRequest>,
+) -> Result<Json<LogLevelResponse>, AppError> {
+    let max_ttl_seconds = state.config.logging.max_level_ttl_seconds;
+    if let Some(ttl_seconds) = request.ttl_seconds
+        && ttl_seconds > max_ttl_seconds
+    {
+        return Err(AppError::bad_request(format!(
+            "ttl_seconds must be at most {}",
+            max_ttl_seconds
+        )));
+    }
+    let ttl = request.ttl_seconds.map(Duration::from_secs);
+    let status = log_levels(&state)?
+        .set(&request.directives, ttl)
+        .map_err(|err| AppError::bad_request(format!("Invalid log directives: {}", err)))?;
+
+    Ok(Json(status.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::AppConfig, logging::TestLogging};
+    use axum::http::StatusCode;
+
+    #[tokio::test]
+    async fn test_log_level_unavailable() {
+        let state = AppState::new(AppConfig::default());
+
+        let err = get_log_level(State(state)).await.unwrap_err();
+
+        assert_eq!(err.status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_put_and_get_log_level() {
+        let logging = TestLogging::new("info");
+        let state = AppState::new(AppConfig::default()).with_log_levels(logging.levels.clone());
+
+        let request = UpdateLogLevelRequest {
+            directives: "debug".to_string(),
+            ttl_seconds: Some(300),
+        };
+        let response = put_log_level(State(state.clone()), Json(request)).await.unwrap().0;
+        assert_eq!(response.directives, "debug");
+        assert_eq!(response.revert_to.as_deref(), Some("info"));
+
+        let response = get_log_level(State(state)).await.unwrap().0;
+        assert_eq!(response.directives, "debug");
+    }
+
+    #[tokio::test]
+    async fn test_put_invalid_log_level() {
+        let logging = TestLogging::new("info");
+        let state = AppState::new(AppConfig::default()).with_log_levels(logging.levels.clone());
+
+        let request = UpdateLogLevelRequest {
+            directives: "axum_starter=loud".to_string(),
+            ttl_seconds: None,
+        };
+        let err = put_log_level(State(state), Json(request)).await.unwrap_err();
+
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_put_log_level_limits_ttl() {
+        let logging = TestLogging::new("info");
+        let state = AppState::new(AppConfig::default()).with_log_levels(logging.levels.clone());
+
+        let request = UpdateLogLevelRequest {
+            directives: "debug".to_string(),
+            ttl_seconds: Some(u64::MAX),
+        };
+        let err = put_log_level(State(state.clone()), Json(request)).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+        assert!(err.message.contains("86400"));
+
+        // The filter is left alone
+        let response = get_log_level(State(state)).await.unwrap().0;
+        assert_eq!(response.directives, "info");
+    }
+}
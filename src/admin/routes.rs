@@ -0,0 +1,93 @@
+use axum::{
+    routing::get,
+    Router,
+};
+
+use super::handlers;
+use crate::state::AppState;
+
+pub fn api_routes(state: AppState) -> Router {
+    // This router doesn't include authentication -
+    // Authentication is added in main.rs
+    Router::new()
+        .route(
+            "/log-level",
+            get(handlers::get_log_level).put(handlers::put_log_level),
+        )
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::AppConfig, logging::TestLogging};
+    use axum::{
+        body::{Body, to_bytes},
+        http::{header, Request, StatusCode},
+    };
+    use tower::util::ServiceExt;
+
+    #[tokio::test]
+    async fn test_log_level_routes() {
+        let logging = TestLogging::new("info");
+        let state = AppState::new(AppConfig::default()).with_log_levels(logging.levels.clone());
+        let app = api_routes(state);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri("/log-level")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(r#"{"directives":"warn"}"#))
+                    .unwrap()
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(Request::builder().uri("/log-level").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: handlers::LogLevelResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.directives, "warn");
+        assert_eq!(body.revert_to, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_log_level_change_reaches_output() {
+        let logging = TestLogging::new("info");
+        let _default = tracing::dispatcher::set_default(&logging.dispatch);
+        let app = api_routes(AppState::new(AppConfig::default()).with_log_levels(logging.levels));
+
+        tracing::debug!("before the change");
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri("/log-level")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(r#"{"directives":"debug","ttl_seconds":60}"#))
+                    .unwrap()
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        tracing::debug!("during the change");
+
+        tokio::time::sleep(std::time::Duration::from_secs(61)).await;
+        tracing::debug!("after the ttl");
+
+        let logs = logging.logs.contents();
+        assert!(!logs.contains("before the change"));
+        assert!(logs.contains("during the change"));
+        assert!(logs.contains("Log level reverted"));
+        assert!(!logs.contains("after the ttl"));
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::BTreeSet;
+
+use serde_json::{Map, Value};
+
+use super::models::Change;
+
+/// List the values that differ between two JSON documents.
+///
+/// Objects are compared key by key, and a missing object (e.g. before a
+/// create) counts as an empty one. Any other value, including arrays, is
+/// compared as a whole.
+pub fn diff(before: Option<&Value>, after: Option<&Value>) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_at(String::new(), before, after, &mut changes);
+    changes
+}
+
+fn diff_at(path: String, before: Option<&Value>, after: Option<&Value>, changes: &mut Vec<Change>) {
+    let empty = Map::new();
+    let objects = match (before, after) {
+        (Some(Value::Object(before)), Some(Value::Object(after))) => Some((before, after)),
+        (None, Some(Value::Object(after))) => Some((&empty, after)),
+        (Some(Value::Object(before)), None) => Some((before, &empty)),
+        _ => None,
+    };
+
+    if let Some((before, after)) = objects {
+        let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+        for key in keys {
+            diff_at(
+                format!("{}/{}", path, escape(key)),
+                before.get(key),
+                after.get(key),
+                changes,
+            );
+        }
+    } else if before != after {
+        changes.push(Change {
+            path,
+            before: before.cloned(),
+            after: after.cloned(),
+        });
+    }
+}
+
+// Escape a key for use in a JSON Pointer (RFC 6901)
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_nested_objects() {
+        let before = json!({ "name": "Acme", "version": 1, "metadata": { "industry": "Retail", "tier": "gold" } });
+        let after = json!({ "name": "Acme Corp", "version": 2, "metadata": { "industry": "Retail", "region": "EU" } });
+
+        assert_eq!(
+            diff(Some(&before), Some(&after)),
+            vec![
+                Change { path: "/metadata/region".to_string(), before: None, after: Some(json!("EU")) },
+                Change { path: "/metadata/tier".to_string(), before: Some(json!("gold")), after: None },
+                Change { path: "/name".to_string(), before: Some(json!("Acme")), after: Some(json!("Acme Corp")) },
+                Change { path: "/version".to_string(), before: Some(json!(1)), after: Some(json!(2)) },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_create_and_delete() {
+        let value = json!({ "name": "Acme", "tags/labels": ["a"] });
+
+        let created = diff(None, Some(&value));
+        assert_eq!(created.len(), 2);
+        assert_eq!(created[0].path, "/name");
+        assert_eq!(created[1].path, "/tags~1labels");
+        assert_eq!(created[1].after, Some(json!(["a"])));
+
+        let deleted = diff(Some(&value), None);
+        assert!(deleted.iter().all(|change| change.after.is_none()));
+
+        assert!(diff(Some(&value), Some(&value)).is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::{
+    models::{AuditAction, AuditEvent},
+    store::{AuditFilter, AuditPage},
+};
+use crate::{errors::AppError, state::AppState};
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+/// Query parameters for the audit log
+#[derive(Serialize, Deserialize, Debug, Default, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQueryParams {
+    /// Only events for this kind of entity, e.g. `client`
+    pub entity_type: Option<String>,
+    pub entity_id: Option<String>,
+    /// Only changes made by this principal
+    pub actor: Option<String>,
+    #[param(inline)]
+    pub action: Option<AuditAction>,
+    /// Only events at or after this time (RFC 3339)
+    pub since: Option<DateTime<Utc>>,
+    /// Only events before this time (RFC 3339)
+    pub until: Option<DateTime<Utc>>,
+    /// Page size, between 1 and 500 (default 50)
+    pub limit: Option<usize>,
+    /// Opaque cursor taken from `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+/// Query parameters for the history of a single entity
+#[derive(Serialize, Deserialize, Debug, Default, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryParams {
+    /// Page size, between 1 and 500 (default 50)
+    pub limit: Option<usize>,
+    /// Opaque cursor taken from `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+impl HistoryParams {
+    pub fn to_filter(&self, entity_type: &str, entity_id: &str) -> Result<AuditFilter, AppError> {
+        AuditQueryParams {
+            entity_type: Some(entity_type.to_string()),
+            entity_id: Some(entity_id.to_string()),
+            limit: self.limit,
+            cursor: self.cursor.clone(),
+            ..AuditQueryParams::default()
+        }
+        .to_filter()
+    }
+}
+
+impl AuditQueryParams {
+    fn to_filter(&self) -> Result<AuditFilter, AppError> {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+            return Err(AppError::bad_request(format!(
+                "limit must be between 1 and {}",
+                MAX_PAGE_SIZE
+            )));
+        }
+
+        let after_id = self
+            .cursor
+            .as_deref()
+            .map(|cursor| cursor.parse::<u64>())
+            .transpose()
+            .map_err(|_| AppError::bad_request("Invalid cursor"))?;
+
+        Ok(AuditFilter {
+            entity_type: self.entity_type.clone(),
+            entity_id: self.entity_id.clone(),
+            actor: self.actor.clone(),
+            action: self.action,
+            since: self.since,
+            until: self.until,
+            after_id,
+            limit,
+        })
+    }
+}
+
+/// A page of audit events, oldest first
+#[derive(Serialize, Deserialize, Debug, PartialEq, ToSchema)]
+pub struct AuditListResponse {
+    pub data: Vec<AuditEvent>,
+    /// Cursor for the next page, absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+impl From<AuditPage> for AuditListResponse {
+    fn from(page: AuditPage) -> Self {
+        Self {
+            data: page.events,
+            next_cursor: page.next_after_id.map(|id| id.to_string()),
+        }
+    }
+}
+
+/// Query the audit log
+///
+/// Returns recorded changes across all entities, oldest first.
+#[utoipa::path(
+    get,
+    path = "/api/audit",
+    tag = "audit",
+    params(AuditQueryParams),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Matching audit events", body = AuditListResponse),
+        (status = 400, description = "Invalid query parameters"),
+        (status = 401, description = "Unauthorized - Missing or invalid token")
+    )
+)]
+pub async fn get_audit_log(
+    State(state): State<AppState>,
+    Query(params): Query<AuditQueryParams>,
+) -> Result<Json<AuditListResponse>, AppError> {
+    let page = state
+        .audit
+        .query(&params.to_filter()?)
+        .map_err(|err| AppError::internal_error(err.to_string()))?;
+
+    Ok(Json(page.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    #[test]
+    fn test_params_to_filter() {
+        let filter = AuditQueryParams {
+            action: Some(AuditAction::Delete),
+            cursor: Some("12".to_string()),
+            ..AuditQueryParams::default()
+        }
+        .to_filter()
+        .unwrap();
+        assert_eq!(filter.after_id, Some(12));
+        assert_eq!(filter.limit, DEFAULT_PAGE_SIZE);
+
+        let filter = HistoryParams::default().to_filter("client", "7").unwrap();
+        assert_eq!(filter.entity_type.as_deref(), Some("client"));
+        assert_eq!(filter.entity_id.as_deref(), Some("7"));
+
+        for params in [
+            AuditQueryParams { cursor: Some("abc".to_string()), ..AuditQueryParams::default() },
+            AuditQueryParams { limit: Some(0), ..AuditQueryParams::default() },
+        ] {
+            assert_eq!(params.to_filter().unwrap_err().status, StatusCode::BAD_REQUEST);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+pub mod diff;
+pub mod handlers;
+pub mod models;
+pub mod routes;
+pub mod store;
+
+use serde::Serialize;
+
+use self::{
+    diff::diff,
+    models::{AuditAction, AuditEvent},
+    store::AuditStore,
+};
+use crate::{auth::Principal, errors::AppError, request_id};
+
+/// Record a change to an entity, diffing its JSON before and after the change
+pub fn record<T: Serialize>(
+    store: &dyn AuditStore,
+    actor: &Principal,
+    action: AuditAction,
+    entity_type: &str,
+    entity_id: &str,
+    before: Option<&T>,
+    after: Option<&T>,
+) -> Result<AuditEvent, AppError> {
+    let to_json = |value: Option<&T>| {
+        value
+            .map(serde_json::to_value)
+            .transpose()
+            .map_err(|err| AppError::internal_error(err.to_string()))
+    };
+    let changes = diff(to_json(before)?.as_ref(), to_json(after)?.as_ref());
+
+    store
+        .append(AuditEvent {
+            id: 0,
+            timestamp: chrono::Utc::now(),
+            actor: actor.subject.clone(),
+            request_id: request_id::current(),
+            action,
+            entity_type: entity_type.to_string(),
+            entity_id: entity_id.to_string(),
+            changes,
+        })
+        .map_err(|err| AppError::internal_error(format!("Failed to record audit event: {}", err)))
+}
+
+/// Record a change that has already been stored.
+///
+/// The change can't be taken back, so failing the request would only make the
+/// caller retry something that succeeded. A failure to audit it is logged instead.
+pub fn record_committed<T: Serialize>(
+    store: &dyn AuditStore,
+    actor: &Principal,
+    action: AuditAction,
+    entity_type: &str,
+    entity_id: &str,
+    before: Option<&T>,
+    after: Option<&T>,
+) {
+    if let Err(err) = record(store, actor, action, entity_type, entity_id, before, after) {
+        tracing::error!(
+            entity_type,
+            entity_id,
+            ?action,
+            error = %err.message,
+            "Failed to record audit event"
+        );
+    }
+}
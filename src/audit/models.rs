@@ -0,0 +1,45 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+/// Kind of change recorded in the audit log
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+    /// A deleted client was removed for good after its retention period
+    Purge,
+    /// An IP or credential was locked out after repeated authentication failures
+    Lockout,
+}
+
+/// One changed value, addressed by a JSON Pointer such as `/metadata/industry`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Change {
+    pub path: String,
+    /// Absent when the value was added
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<Value>,
+    /// Absent when the value was removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<Value>,
+}
+
+/// An entry in the audit log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct AuditEvent {
+    /// Assigned by the store, increasing in the order events were recorded
+    pub id: u64,
+    pub timestamp: DateTime<Utc>,
+    /// Subject of the authenticated principal that made the change
+    pub actor: String,
+    pub request_id: Option<String>,
+    pub action: AuditAction,
+    pub entity_type: String,
+    pub entity_id: String,
+    pub changes: Vec<Change>,
+}
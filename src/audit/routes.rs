@@ -0,0 +1,15 @@
+use axum::{
+    routing::get,
+    Router,
+};
+
+use super::handlers;
+use crate::state::AppState;
+
+pub fn api_routes(state: AppState) -> Router {
+    // This router doesn't include authentication -
+    // Authentication is added in main.rs
+    Router::new()
+        .route("/", get(handlers::get_audit_log))
+        .with_state(state)
+}
@@ -0,0 +1,149 @@
+use std::sync::RwLock;
+
+use chrono::{DateTime, Utc};
+
+use super::models::{AuditAction, AuditEvent};
+
+/// Which audit events to return, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub entity_type: Option<String>,
+    pub entity_id: Option<String>,
+    pub actor: Option<String>,
+    pub action: Option<AuditAction>,
+    /// Only events at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only events before this time
+    pub until: Option<DateTime<Utc>>,
+    /// Only events recorded after the event with this id
+    pub after_id: Option<u64>,
+    pub limit: usize,
+}
+
+impl AuditFilter {
+    fn matches(&self, event: &AuditEvent) -> bool {
+        self.entity_type.as_ref().is_none_or(|value| *value == event.entity_type)
+            && self.entity_id.as_ref().is_none_or(|value| *value == event.entity_id)
+            && self.actor.as_ref().is_none_or(|value| *value == event.actor)
+            && self.action.is_none_or(|value| value == event.action)
+            && self.since.is_none_or(|since| event.timestamp >= since)
+            && self.until.is_none_or(|until| event.timestamp < until)
+            && self.after_id.is_none_or(|after_id| event.id > after_id)
+    }
+}
+
+/// One page of audit events
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditPage {
+    pub events: Vec<AuditEvent>,
+    /// Id to pass as `after_id` for the next page
+    pub next_after_id: Option<u64>,
+}
+
+/// Append-only storage for audit events: there is no way to change or remove one
+pub trait AuditStore: Send + Sync {
+    /// Store an event, assigning it the next id
+    fn append(&self, event: AuditEvent) -> anyhow::Result<AuditEvent>;
+
+    fn query(&self, filter: &AuditFilter) -> anyhow::Result<AuditPage>;
+}
+
+/// An [`AuditStore`] that keeps events in memory
+#[derive(Default)]
+pub struct InMemoryAuditStore {
+    events: RwLock<Vec<AuditEvent>>,
+}
+
+impl AuditStore for InMemoryAuditStore {
+    fn append(&self, mut event: AuditEvent) -> anyhow::Result<AuditEvent> {
+        let mut events = self
+            .events
+            .write()
+            .map_err(|_| anyhow::anyhow!("audit log lock poisoned"))?;
+
+        event.id = events.len() as u64 + 1;
+        events.push(event.clone());
+        Ok(event)
+    }
+
+    fn query(&self, filter: &AuditFilter) -> anyhow::Result<AuditPage> {
+        let events = self
+            .events
+            .read()
+            .map_err(|_| anyhow::anyhow!("audit log lock poisoned"))?;
+
+        let mut matching = events.iter().filter(|event| filter.matches(event));
+        let page: Vec<AuditEvent> = matching.by_ref().take(filter.limit).cloned().collect();
+        let has_more = matching.next().is_some();
+
+        Ok(AuditPage {
+            next_after_id: page.last().filter(|_| has_more).map(|event| event.id),
+            events: page,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(actor: &str, action: AuditAction, entity_id: &str) -> AuditEvent {
+        AuditEvent {
+            id: 0,
+            timestamp: Utc::now(),
+            actor: actor.to_string(),
+            request_id: None,
+            action,
+            entity_type: "client".to_string(),
+            entity_id: entity_id.to_string(),
+            changes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_append_assigns_ids() {
+        let store = InMemoryAuditStore::default();
+
+        assert_eq!(store.append(event("alice", AuditAction::Create, "1")).unwrap().id, 1);
+        assert_eq!(store.append(event("alice", AuditAction::Update, "1")).unwrap().id, 2);
+    }
+
+    #[test]
+    fn test_query_filters_and_pages() {
+        let store = InMemoryAuditStore::default();
+        store.append(event("alice", AuditAction::Create, "1")).unwrap();
+        store.append(event("bob", AuditAction::Create, "2")).unwrap();
+        store.append(event("alice", AuditAction::Update, "1")).unwrap();
+        store.append(event("alice", AuditAction::Delete, "1")).unwrap();
+
+        let mut filter = AuditFilter {
+            entity_id: Some("1".to_string()),
+            limit: 2,
+            ..AuditFilter::default()
+        };
+        let first = store.query(&filter).unwrap();
+        let ids: Vec<u64> = first.events.iter().map(|event| event.id).collect();
+        assert_eq!(ids, vec![1, 3]);
+        assert_eq!(first.next_after_id, Some(3));
+
+        filter.after_id = first.next_after_id;
+        let second = store.query(&filter).unwrap();
+        assert_eq!(second.events[0].action, AuditAction::Delete);
+        assert_eq!(second.next_after_id, None);
+
+        let filter = AuditFilter {
+            actor: Some("bob".to_string()),
+            action: Some(AuditAction::Create),
+            limit: 10,
+            ..AuditFilter::default()
+        };
+        assert_eq!(store.query(&filter).unwrap().events.len(), 1);
+
+        let filter = AuditFilter {
+            until: Some(Utc::now() - chrono::Duration::hours(1)),
+            limit: 10,
+            ..AuditFilter::default()
+        };
+        assert!(store.query(&filter).unwrap().events.is_empty());
+    }
+}
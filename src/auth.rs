@@ -1,37 +1,110 @@
 use axum::{
-    extract::Request,
-    http::{StatusCode, header},
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{request::Parts, StatusCode, header},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
 
+use crate::{client_ip, errors::AppError, lockout::Attempt, state::AppState};
+
 // Expected token for development
 pub const DEV_TOKEN: &str = "Bearer dev_token";
 
-// Auth middleware that checks for a valid Bearer token
+// Subject the development token authenticates as
+pub const DEV_SUBJECT: &str = "dev";
+
+/// The authenticated caller, added to request extensions by `auth_middleware`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Principal {
+    pub subject: String,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Principal
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Principal>()
+            .cloned()
+            .ok_or_else(|| AppError::unauthorized("Authentication required"))
+    }
+}
+
+/// Check an `Authorization` header value, counting failures in the auth metric
+pub fn authenticate(auth_header: Option<&str>) -> Option<Principal> {
+    let principal = verify(auth_header);
+    if principal.is_none() {
+        crate::metrics::record_auth_failure(failure_reason(auth_header));
+    }
+    principal
+}
+
+/// Check an `Authorization` header value without counting anything
+pub fn verify(auth_header: Option<&str>) -> Option<Principal> {
+    // Check if header exists and matches our expected token
+    match auth_header {
+        Some(token) if token == DEV_TOKEN => Some(Principal {
+            subject: DEV_SUBJECT.to_string(),
+        }),
+        _ => None,
+    }
+}
+
+// Auth middleware that checks for a valid Bearer token.
+// Repeated failures from one IP or against one credential are throttled,
+// then locked out for a while. Only an IP lockout refuses valid tokens.
 pub async fn auth_middleware(
-    request: Request,
+    State(state): State<AppState>,
+    mut request: Request,
     next: Next,
-) -> Result<Response, StatusCode> {
+) -> Response {
     // Get Authorization header
     let auth_header = request
         .headers()
         .get(header::AUTHORIZATION)
         .and_then(|header| header.to_str().ok());
 
-    // Check if header exists and matches our expected token
-    match auth_header {
-        Some(token) if token == DEV_TOKEN => {
+    let ip = client_ip::client_ip(&request, state.config.http.trust_forwarded_for);
+    let attempt = Attempt::new(&state, ip, auth_header);
+    if let Err(err) = attempt.check() {
+        return err.into_response();
+    }
+
+    match authenticate(auth_header) {
+        Some(principal) => {
             // Token is valid, proceed to handler
-            Ok(next.run(request).await)
+            attempt.succeeded();
+            request.extensions_mut().insert(principal);
+            next.run(request).await
         }
-        _ => {
+        None => {
+            // Requests without a token aren't guesses, so they aren't counted
+            if auth_header.is_some()
+                && let Err(err) = attempt.failed()
+            {
+                return err.into_response();
+            }
             // Return 401 Unauthorized if token is missing or invalid
-            Err(StatusCode::UNAUTHORIZED)
+            StatusCode::UNAUTHORIZED.into_response()
         }
     }
 }
 
+// Classify a rejected Authorization header for the auth failure metric
+fn failure_reason(auth_header: Option<&str>) -> &'static str {
+    match auth_header {
+        None => "missing_token",
+        Some(value) if !value.starts_with("Bearer ") => "invalid_scheme",
+        Some(_) => "invalid_token",
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -49,12 +122,16 @@ mod tests {
         "Protected content"
     }
 
+    async fn whoami(principal: Principal) -> String {
+        principal.subject
+    }
+
     #[tokio::test]
     async fn test_auth_middleware_no_token() {
         // Create a test app with auth middleware
         let app = Router::new()
             .route("/protected", get(test_handler))
-            .layer(middleware::from_fn(auth_middleware));
+            .layer(middleware::from_fn_with_state(AppState::default(), auth_middleware));
 
         // Send a request without an auth token
         let response = app
@@ -71,7 +148,7 @@ mod tests {
         // Create a test app with auth middleware
         let app = Router::new()
             .route("/protected", get(test_handler))
-            .layer(middleware::from_fn(auth_middleware));
+            .layer(middleware::from_fn_with_state(AppState::default(), auth_middleware));
 
         // Send a request with wrong token
         let response = app
@@ -94,7 +171,7 @@ mod tests {
         // Create a test app with auth middleware
         let app = Router::new()
             .route("/protected", get(test_handler))
-            .layer(middleware::from_fn(auth_middleware));
+            .layer(middleware::from_fn_with_state(AppState::default(), auth_middleware));
 
         // Send a request with valid token
         let response = app
@@ -111,4 +188,40 @@ mod tests {
         // Should get 200 OK
         assert_eq!(response.status(), StatusCode::OK);
     }
+
+    #[tokio::test]
+    async fn test_principal_extractor() {
+        let app = Router::new()
+            .route("/whoami", get(whoami))
+            .route_layer(middleware::from_fn_with_state(AppState::default(), auth_middleware))
+            .route("/unprotected", get(whoami));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/whoami")
+                    .header(header::AUTHORIZATION, DEV_TOKEN)
+                    .body(Body::empty())
+                    .unwrap()
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, DEV_SUBJECT);
+
+        // Without the middleware there is no principal to extract
+        let response = app
+            .oneshot(Request::builder().uri("/unprotected").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_failure_reason() {
+        assert_eq!(failure_reason(None), "missing_token");
+        assert_eq!(failure_reason(Some("Basic abc")), "invalid_scheme");
+        assert_eq!(failure_reason(Some("Bearer wrong")), "invalid_token");
+    }
 }
\ No newline at end of file
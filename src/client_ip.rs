@@ -0,0 +1,84 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::HeaderMap,
+};
+
+/// Determine the client IP for a request.
+///
+/// The first `X-Forwarded-For` entry is used when `trust_forwarded_for` is set,
+/// otherwise the peer address recorded by `into_make_service_with_connect_info`.
+pub fn client_ip(request: &Request, trust_forwarded_for: bool) -> Option<IpAddr> {
+    from_parts(
+        request.headers(),
+        request.extensions().get::<ConnectInfo<SocketAddr>>(),
+        trust_forwarded_for,
+    )
+}
+
+/// Like [`client_ip`], for handlers that extracted the headers and peer address
+pub fn from_parts(
+    headers: &HeaderMap,
+    connect_info: Option<&ConnectInfo<SocketAddr>>,
+    trust_forwarded_for: bool,
+) -> Option<IpAddr> {
+    if trust_forwarded_for {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .and_then(|value| value.trim().parse().ok());
+
+        if forwarded.is_some() {
+            return forwarded;
+        }
+    }
+
+    connect_info.map(|ConnectInfo(addr)| addr.ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    fn request_with(forwarded_for: Option<&str>, peer: Option<SocketAddr>) -> Request {
+        let mut builder = Request::builder().uri("/");
+        if let Some(value) = forwarded_for {
+            builder = builder.header("x-forwarded-for", value);
+        }
+
+        let mut request = builder.body(Body::empty()).unwrap();
+        if let Some(addr) = peer {
+            request.extensions_mut().insert(ConnectInfo(addr));
+        }
+        request
+    }
+
+    #[test]
+    fn test_uses_peer_address() {
+        let peer: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+        let request = request_with(Some("203.0.113.7"), Some(peer));
+
+        // Forwarded header is ignored unless trusted
+        assert_eq!(client_ip(&request, false), Some(peer.ip()));
+    }
+
+    #[test]
+    fn test_trusts_forwarded_for() {
+        let peer: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+        let request = request_with(Some("203.0.113.7, 10.0.0.1"), Some(peer));
+
+        assert_eq!(client_ip(&request, true), Some("203.0.113.7".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_invalid_forwarded_for_falls_back() {
+        let peer: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+        let request = request_with(Some("not-an-ip"), Some(peer));
+
+        assert_eq!(client_ip(&request, true), Some(peer.ip()));
+        assert_eq!(client_ip(&request_with(None, None), true), None);
+    }
+}
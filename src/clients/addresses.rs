@@ -0,0 +1,266 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::{
+    models::{Address, AddressKind},
+    validation,
+};
+use crate::{
+    audit::{self, models::AuditAction},
+    auth::Principal,
+    errors::AppError,
+    etag::{check_if_match, etag, etag_header, if_none_match},
+    state::AppState,
+};
+
+// Entity type of addresses in the audit log
+const ADDRESS_ENTITY: &str = "address";
+
+/// Body for creating or replacing an address
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct AddressRequest {
+    #[serde(default)]
+    pub kind: AddressKind,
+    pub line1: String,
+    #[serde(default)]
+    pub line2: Option<String>,
+    pub city: String,
+    #[serde(default)]
+    pub region: Option<String>,
+    #[serde(default)]
+    pub postal_code: Option<String>,
+    /// ISO 3166-1 alpha-2 country code; stored uppercased
+    pub country: String,
+}
+
+impl AddressRequest {
+    // Check and normalise the fields onto `address`
+    fn apply(self, address: Address) -> Result<Address, AppError> {
+        Ok(Address {
+            kind: self.kind,
+            line1: validation::required("line1", &self.line1)?,
+            line2: validation::optional("line2", self.line2.as_deref())?,
+            city: validation::required("city", &self.city)?,
+            region: validation::optional("region", self.region.as_deref())?,
+            postal_code: validation::optional("postal_code", self.postal_code.as_deref())?,
+            country: validation::country_code("country", &self.country)?,
+            ..address
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, ToSchema)]
+pub struct AddressListResponse {
+    pub data: Vec<Address>,
+}
+
+fn record(
+    state: &AppState,
+    principal: &Principal,
+    action: AuditAction,
+    before: Option<&Address>,
+    after: Option<&Address>,
+) {
+    let id = after.or(before).map(|address| address.id.as_str()).unwrap_or_default();
+    audit::record_committed(
+        state.audit.as_ref(),
+        principal,
+        action,
+        ADDRESS_ENTITY,
+        id,
+        before,
+        after,
+    );
+}
+
+/// List the addresses of a client
+#[utoipa::path(
+    get,
+    path = "/api/clients/{id}/addresses",
+    tag = "clients",
+    params(("id" = String, Path, description = "Client id")),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Addresses of the client", body = AddressListResponse),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Client not found")
+    )
+)]
+pub async fn list_addresses(
+    State(state): State<AppState>,
+    Path(client_id): Path<String>,
+) -> Result<Json<AddressListResponse>, AppError> {
+    let data = state.clients.list_addresses(&client_id)?;
+    Ok(Json(AddressListResponse { data }))
+}
+
+/// Add an address to a client
+#[utoipa::path(
+    post,
+    path = "/api/clients/{id}/addresses",
+    tag = "clients",
+    params(("id" = String, Path, description = "Client id")),
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = AddressRequest,
+    responses(
+        (status = 201, description = "Address created", body = Address,
+            headers(("ETag" = String), ("Location" = String))),
+        (status = 400, description = "Invalid address"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Client not found")
+    )
+)]
+pub async fn create_address(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(client_id): Path<String>,
+    Json(request): Json<AddressRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let address = request.apply(Address {
+        id: Uuid::now_v7().to_string(),
+        client_id,
+        kind: AddressKind::default(),
+        line1: String::new(),
+        line2: None,
+        city: String::new(),
+        region: None,
+        postal_code: None,
+        country: String::new(),
+        created_at: Utc::now(),
+        version: 1,
+        deleted_at: None,
+    })?;
+
+    let address = state.clients.insert_address(address)?;
+    record(&state, &principal, AuditAction::Create, None, Some(&address));
+
+    let mut headers = etag_header(address.version);
+    if let Ok(location) = HeaderValue::from_str(&format!(
+        "/api/clients/{}/addresses/{}",
+        address.client_id, address.id
+    )) {
+        headers.insert(header::LOCATION, location);
+    }
+
+    Ok((StatusCode::CREATED, headers, Json(address)))
+}
+
+/// Get an address
+#[utoipa::path(
+    get,
+    path = "/api/clients/{id}/addresses/{address_id}",
+    tag = "clients",
+    params(
+        ("id" = String, Path, description = "Client id"),
+        ("address_id" = String, Path, description = "Address id")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "The address", body = Address, headers(("ETag" = String))),
+        (status = 304, description = "Unchanged since the `If-None-Match` version"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Client or address not found")
+    )
+)]
+pub async fn get_address(
+    State(state): State<AppState>,
+    Path((client_id, id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let address = state.clients.get_address(&client_id, &id)?;
+
+    if if_none_match(&headers, &etag(address.version)) {
+        return Ok((StatusCode::NOT_MODIFIED, etag_header(address.version)).into_response());
+    }
+
+    Ok((etag_header(address.version), Json(address)).into_response())
+}
+
+/// Update an address
+#[utoipa::path(
+    put,
+    path = "/api/clients/{id}/addresses/{address_id}",
+    tag = "clients",
+    params(
+        ("id" = String, Path, description = "Client id"),
+        ("address_id" = String, Path, description = "Address id"),
+        ("If-Match" = Option<String>, Header, description = "`ETag` the update is based on")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = AddressRequest,
+    responses(
+        (status = 200, description = "Address updated", body = Address, headers(("ETag" = String))),
+        (status = 400, description = "Invalid address"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Client or address not found"),
+        (status = 412, description = "The address was modified since the `If-Match` version"),
+        (status = 428, description = "`If-Match` header is required")
+    )
+)]
+pub async fn update_address(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path((client_id, id)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(request): Json<AddressRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let current = state.clients.get_address(&client_id, &id)?;
+    let conditional = check_if_match(
+        &headers,
+        &etag(current.version),
+        state.config.clients.require_if_match,
+    )?;
+    let address = request.apply(current.clone())?;
+
+    let expected_version = conditional.then_some(current.version);
+    let address = state.clients.update_address(address, expected_version)?;
+    record(&state, &principal, AuditAction::Update, Some(&current), Some(&address));
+
+    Ok((etag_header(address.version), Json(address)))
+}
+
+/// Delete an address
+#[utoipa::path(
+    delete,
+    path = "/api/clients/{id}/addresses/{address_id}",
+    tag = "clients",
+    params(
+        ("id" = String, Path, description = "Client id"),
+        ("address_id" = String, Path, description = "Address id")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 204, description = "Address deleted"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Client or address not found")
+    )
+)]
+pub async fn delete_address(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path((client_id, id)): Path<(String, String)>,
+) -> Result<StatusCode, AppError> {
+    let before = state.clients.get_address(&client_id, &id)?;
+    let after = state.clients.delete_address(&client_id, &id)?;
+    record(&state, &principal, AuditAction::Delete, Some(&before), Some(&after));
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,758 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    sync::Arc,
+};
+
+use chrono::Utc;
+use futures_util::{stream, Stream};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::{
+    custom_fields,
+    models::{Client, CustomFieldDefinition},
+    repository::{ClientRepository, ClientStoreError, ListQuery},
+    validation,
+};
+
+// Clients fetched from the repository per chunk of an export
+const EXPORT_BATCH_SIZE: usize = 500;
+
+/// Columns written by the CSV export. Import reads `id`, `name`, `metadata`,
+/// `tags` and `custom_fields` and ignores the rest, so exported files can be
+/// imported again. The last three hold JSON.
+pub const CSV_COLUMNS: [&str; 8] = [
+    "id",
+    "name",
+    "created_at",
+    "version",
+    "deleted_at",
+    "metadata",
+    "tags",
+    "custom_fields",
+];
+
+/// File format for bulk import and export
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkFormat {
+    Csv,
+    Ndjson,
+}
+
+impl BulkFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv",
+            Self::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    /// Recognise a format from a request `Content-Type`
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        match mime {
+            "text/csv" => Some(Self::Csv),
+            "application/x-ndjson" | "application/ndjson" | "application/jsonl" => {
+                Some(Self::Ndjson)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What to do when some rows of an import are invalid
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Import nothing unless every row is valid
+    #[default]
+    AllOrNothing,
+    /// Import the valid rows and report the rest
+    BestEffort,
+}
+
+/// A client to import
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    /// Generated when absent
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    #[serde(default)]
+    pub custom_fields: BTreeMap<String, Value>,
+}
+
+/// Outcome of one row
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RowStatus {
+    /// The client was imported
+    Created,
+    /// The row is valid but was not imported: a dry run, or another row failed
+    Valid,
+    Failed,
+}
+
+/// Result for one data row, numbered from 1 (the CSV header is not counted)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct RowResult {
+    pub row: usize,
+    pub status: RowStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Summary of an import with a result for every row
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct ImportReport {
+    pub format: BulkFormat,
+    pub mode: ImportMode,
+    pub dry_run: bool,
+    /// Whether any clients were written
+    pub committed: bool,
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub rows: Vec<RowResult>,
+}
+
+/// Splits a byte stream into records as chunks arrive.
+///
+/// Records end at a newline; for CSV, newlines inside quoted fields don't count.
+/// A record longer than the limit is an error, so an unterminated record (or
+/// an unclosed quote) can't buffer the whole body.
+pub struct RecordSplitter {
+    buffer: Vec<u8>,
+    scanned: usize,
+    in_quotes: bool,
+    track_quotes: bool,
+    max_record_bytes: usize,
+}
+
+impl RecordSplitter {
+    pub fn new(format: BulkFormat, max_record_bytes: usize) -> Self {
+        Self {
+            buffer: Vec::new(),
+            scanned: 0,
+            in_quotes: false,
+            track_quotes: format == BulkFormat::Csv,
+            max_record_bytes,
+        }
+    }
+
+    /// Add a chunk, returning the records it completed
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut records = Vec::new();
+        let mut start = 0;
+        for index in self.scanned..self.buffer.len() {
+            match self.buffer[index] {
+                b'"' if self.track_quotes => self.in_quotes = !self.in_quotes,
+                b'\n' if !self.in_quotes => {
+                    self.check_length(index - start)?;
+                    records.push(self.buffer[start..index].to_vec());
+                    start = index + 1;
+                }
+                _ => {}
+            }
+        }
+        self.check_length(self.buffer.len() - start)?;
+
+        self.buffer.drain(..start);
+        self.scanned = self.buffer.len();
+        Ok(records.into_iter().filter_map(trim_record).collect())
+    }
+
+    fn check_length(&self, length: usize) -> Result<(), String> {
+        if length > self.max_record_bytes {
+            return Err(format!(
+                "Records must not be longer than {} bytes",
+                self.max_record_bytes
+            ));
+        }
+        Ok(())
+    }
+
+    /// The last record, if the input did not end with a newline
+    pub fn finish(self) -> Option<Vec<u8>> {
+        trim_record(self.buffer)
+    }
+}
+
+// Drop a trailing `\r` and skip blank lines
+fn trim_record(mut record: Vec<u8>) -> Option<Vec<u8>> {
+    if record.last() == Some(&b'\r') {
+        record.pop();
+    }
+    if record.iter().all(u8::is_ascii_whitespace) {
+        return None;
+    }
+    Some(record)
+}
+
+/// Parses records into [`ImportRow`]s; a CSV header is read from the first record
+pub struct RowParser {
+    format: BulkFormat,
+    columns: Option<CsvColumns>,
+}
+
+struct CsvColumns {
+    id: Option<usize>,
+    name: usize,
+    metadata: Option<usize>,
+    tags: Option<usize>,
+    custom_fields: Option<usize>,
+}
+
+impl RowParser {
+    pub fn new(format: BulkFormat) -> Self {
+        Self {
+            format,
+            columns: None,
+        }
+    }
+
+    /// Whether the next record is the CSV header
+    pub fn expects_header(&self) -> bool {
+        self.format == BulkFormat::Csv && self.columns.is_none()
+    }
+
+    /// Parse a record. Returns `Ok(None)` for the CSV header.
+    pub fn parse(&mut self, record: &[u8]) -> Result<Option<ImportRow>, String> {
+        match self.format {
+            BulkFormat::Ndjson => serde_json::from_slice(record)
+                .map(Some)
+                .map_err(|err| format!("Invalid JSON: {}", err)),
+            BulkFormat::Csv => self.parse_csv(record),
+        }
+    }
+
+    fn parse_csv(&mut self, record: &[u8]) -> Result<Option<ImportRow>, String> {
+        let fields = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(record)
+            .records()
+            .next()
+            .transpose()
+            .map_err(|err| format!("Invalid CSV: {}", err))?
+            .unwrap_or_default();
+
+        let Some(columns) = &self.columns else {
+            let position = |name: &str| fields.iter().position(|field| field.trim() == name);
+            self.columns = Some(CsvColumns {
+                id: position("id"),
+                name: position("name").ok_or("CSV header must include a name column")?,
+                metadata: position("metadata"),
+                tags: position("tags"),
+                custom_fields: position("custom_fields"),
+            });
+            return Ok(None);
+        };
+
+        let field = |index: Option<usize>| {
+            index
+                .and_then(|index| fields.get(index))
+                .filter(|value| !value.is_empty())
+        };
+
+        fn json<T: DeserializeOwned + Default>(column: &str, value: Option<&str>) -> Result<T, String> {
+            match value {
+                Some(json) => serde_json::from_str(json)
+                    .map_err(|err| format!("Invalid {} JSON: {}", column, err)),
+                None => Ok(T::default()),
+            }
+        }
+
+        Ok(Some(ImportRow {
+            id: field(columns.id).map(str::to_string),
+            name: fields.get(columns.name).unwrap_or_default().to_string(),
+            metadata: json("metadata", field(columns.metadata))?,
+            tags: json("tags", field(columns.tags))?,
+            custom_fields: json("custom_fields", field(columns.custom_fields))?,
+        }))
+    }
+}
+
+/// Validates and imports rows as they are parsed from the request body
+pub struct Importer<'a> {
+    clients: &'a dyn ClientRepository,
+    definitions: Vec<CustomFieldDefinition>,
+    format: BulkFormat,
+    mode: ImportMode,
+    dry_run: bool,
+    parser: RowParser,
+    seen_ids: HashSet<String>,
+    // Valid rows held back until all rows are known to be valid
+    pending: Vec<(usize, Client)>,
+    created: Vec<Client>,
+    rows: Vec<RowResult>,
+}
+
+impl<'a> Importer<'a> {
+    pub fn new(
+        clients: &'a dyn ClientRepository,
+        definitions: Vec<CustomFieldDefinition>,
+        format: BulkFormat,
+        mode: ImportMode,
+        dry_run: bool,
+    ) -> Self {
+        Self {
+            clients,
+            definitions,
+            format,
+            mode,
+            dry_run,
+            parser: RowParser::new(format),
+            seen_ids: HashSet::new(),
+            pending: Vec::new(),
+            created: Vec::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Handle one record from the input
+    pub fn record(&mut self, record: &[u8]) -> Result<(), ClientStoreError> {
+        // Without a usable header no row can be read, so fail the whole import
+        if self.parser.expects_header() {
+            return self
+                .parser
+                .parse(record)
+                .map(|_| ())
+                .map_err(ClientStoreError::InvalidQuery);
+        }
+
+        let parsed = match self.parser.parse(record) {
+            Ok(None) => return Ok(()),
+            Ok(Some(row)) => self.validate(row),
+            Err(err) => Err(err),
+        };
+        let row = self.rows.len() + 1;
+
+        let client = match parsed {
+            Ok(client) => client,
+            Err(error) => {
+                self.rows.push(RowResult {
+                    row,
+                    status: RowStatus::Failed,
+                    id: None,
+                    error: Some(error),
+                });
+                return Ok(());
+            }
+        };
+
+        let status = match (self.mode, self.dry_run) {
+            (ImportMode::BestEffort, false) => match self.clients.insert(client.clone()) {
+                Ok(client) => {
+                    self.created.push(client);
+                    RowStatus::Created
+                }
+                // Created by someone else since the row was validated
+                Err(err @ ClientStoreError::AlreadyExists(_)) => {
+                    self.rows.push(RowResult {
+                        row,
+                        status: RowStatus::Failed,
+                        id: Some(client.id),
+                        error: Some(err.to_string()),
+                    });
+                    return Ok(());
+                }
+                Err(err) => return Err(err),
+            },
+            (ImportMode::AllOrNothing, false) => {
+                self.pending.push((row, client.clone()));
+                RowStatus::Valid
+            }
+            (_, true) => RowStatus::Valid,
+        };
+
+        self.rows.push(RowResult {
+            row,
+            status,
+            id: Some(client.id),
+            error: None,
+        });
+        Ok(())
+    }
+
+    fn validate(&mut self, row: ImportRow) -> Result<Client, String> {
+        if row.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        let tags = validation::tags("tags", &row.tags).map_err(|err| err.message)?;
+        let custom_fields =
+            custom_fields::check_values(&self.definitions, row.custom_fields).map_err(|err| err.message)?;
+
+        let id = match row.id {
+            Some(id) if self.seen_ids.contains(&id) => {
+                return Err(format!("Duplicate id {} in import", id));
+            }
+            Some(id) if self.clients.get(&id, true).is_ok() => {
+                return Err(format!("Client {} already exists", id));
+            }
+            Some(id) => id,
+            None => Uuid::now_v7().to_string(),
+        };
+        self.seen_ids.insert(id.clone());
+
+        Ok(Client {
+            id,
+            name: row.name,
+            created_at: Utc::now(),
+            version: 1,
+            deleted_at: None,
+            metadata: row.metadata,
+            tags,
+            custom_fields,
+        })
+    }
+
+    /// Clients written since the last call, so each can be audited and
+    /// published as soon as it is committed
+    pub fn take_created(&mut self) -> Vec<Client> {
+        std::mem::take(&mut self.created)
+    }
+
+    /// Commit held-back rows if allowed and return the report with the clients
+    /// created since the last [`take_created`](Self::take_created)
+    pub fn finish(mut self) -> Result<(ImportReport, Vec<Client>), ClientStoreError> {
+        let any_failed = self.rows.iter().any(|row| row.status == RowStatus::Failed);
+
+        if !any_failed && !self.pending.is_empty() {
+            let (rows, clients): (Vec<usize>, Vec<Client>) = std::mem::take(&mut self.pending).into_iter().unzip();
+            match self.clients.insert_all(clients) {
+                Ok(created) => {
+                    for row in rows {
+                        self.rows[row - 1].status = RowStatus::Created;
+                    }
+                    self.created.extend(created);
+                }
+                // Created by someone else since the row was validated, so nothing was written
+                Err(ClientStoreError::AlreadyExists(id)) => {
+                    if let Some(result) = self.rows.iter_mut().find(|row| row.id.as_deref() == Some(id.as_str())) {
+                        result.status = RowStatus::Failed;
+                        result.error = Some(format!("Client {} already exists", id));
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        let failed = self
+            .rows
+            .iter()
+            .filter(|row| row.status == RowStatus::Failed)
+            .count();
+
+        let report = ImportReport {
+            format: self.format,
+            mode: self.mode,
+            dry_run: self.dry_run,
+            committed: self.rows.iter().any(|row| row.status == RowStatus::Created),
+            total: self.rows.len(),
+            succeeded: self.rows.len() - failed,
+            failed,
+            rows: self.rows,
+        };
+        Ok((report, self.created))
+    }
+}
+
+/// Stream every client in `format`, fetching them from the repository in batches
+pub fn export_stream(
+    clients: Arc<dyn ClientRepository>,
+    format: BulkFormat,
+    include_deleted: bool,
+) -> impl Stream<Item = Result<Vec<u8>, ClientStoreError>> {
+    // The cursor of the next batch; `None` once the last batch was sent
+    let start = Some(None::<String>);
+
+    stream::unfold(start, move |cursor| {
+        let clients = clients.clone();
+        async move {
+            let cursor = cursor?;
+            let first = cursor.is_none();
+
+            let query = ListQuery {
+                limit: EXPORT_BATCH_SIZE,
+                cursor,
+                include_deleted,
+                ..ListQuery::default()
+            };
+            let page = match clients.list(&query) {
+                Ok(page) => page,
+                Err(err) => return Some((Err(err), None)),
+            };
+
+            let mut chunk = if first && format == BulkFormat::Csv {
+                csv_header()
+            } else {
+                Vec::new()
+            };
+            for client in &page.items {
+                chunk.extend(encode_client(format, client));
+            }
+
+            Some((Ok(chunk), page.next_cursor.map(Some)))
+        }
+    })
+}
+
+/// Header line for a CSV export
+pub fn csv_header() -> Vec<u8> {
+    write_csv(&CSV_COLUMNS.map(str::to_string))
+}
+
+/// One client as a CSV or NDJSON line
+pub fn encode_client(format: BulkFormat, client: &Client) -> Vec<u8> {
+    match format {
+        BulkFormat::Csv => write_csv(&[
+            client.id.clone(),
+            client.name.clone(),
+            client.created_at.to_rfc3339(),
+            client.version.to_string(),
+            client.deleted_at.map(|at| at.to_rfc3339()).unwrap_or_default(),
+            json_column(&client.metadata, client.metadata.is_empty()),
+            json_column(&client.tags, client.tags.is_empty()),
+            json_column(&client.custom_fields, client.custom_fields.is_empty()),
+        ]),
+        BulkFormat::Ndjson => {
+            let mut line = serde_json::to_vec(client).unwrap_or_default();
+            line.push(b'\n');
+            line
+        }
+    }
+}
+
+// A JSON-valued CSV column, left blank when empty
+fn json_column<T: Serialize>(value: &T, empty: bool) -> String {
+    if empty {
+        String::new()
+    } else {
+        serde_json::to_string(value).unwrap_or_default()
+    }
+}
+
+fn write_csv(fields: &[String]) -> Vec<u8> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    // Writing to memory can't fail
+    let _ = writer.write_record(fields);
+    writer.into_inner().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clients::repository::InMemoryClientRepository;
+
+    #[test]
+    fn test_splitter_handles_chunk_boundaries_and_quotes() {
+        let mut splitter = RecordSplitter::new(BulkFormat::Csv, 1024);
+
+        let mut records = splitter.push(b"name,metadata\r\n\"Acme\nLabs\",").unwrap();
+        records.extend(splitter.push(b"\"{\"\"a\"\":\"\"1\"\"}\"\n\nGlob").unwrap());
+        records.extend(splitter.push(b"ex").unwrap());
+        records.extend(splitter.finish());
+
+        assert_eq!(
+            records,
+            vec![
+                b"name,metadata".to_vec(),
+                b"\"Acme\nLabs\",\"{\"\"a\"\":\"\"1\"\"}\"".to_vec(),
+                b"Globex".to_vec(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_splitter_limits_record_length() {
+        let mut splitter = RecordSplitter::new(BulkFormat::Csv, 8);
+        assert_eq!(splitter.push(b"name\nAcme\n").unwrap().len(), 2);
+        assert!(splitter.push(b"Acme Labs Inc\n").is_err());
+
+        // An unclosed quote never ends the record
+        let mut splitter = RecordSplitter::new(BulkFormat::Csv, 8);
+        assert_eq!(splitter.push(b"\"Acme\n").unwrap(), Vec::<Vec<u8>>::new());
+        assert!(splitter.push(b"Labs\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_csv_rows() {
+        let mut parser = RowParser::new(BulkFormat::Csv);
+
+        assert_eq!(parser.parse(b"metadata,name,id,tags").unwrap(), None);
+        assert_eq!(
+            parser.parse(br#""{""tier"":""gold""}",Acme,42,"[""vip""]""#).unwrap(),
+            Some(ImportRow {
+                id: Some("42".to_string()),
+                name: "Acme".to_string(),
+                metadata: BTreeMap::from([("tier".to_string(), "gold".to_string())]),
+                tags: BTreeSet::from(["vip".to_string()]),
+                custom_fields: BTreeMap::new(),
+            })
+        );
+        assert_eq!(parser.parse(b",Globex,").unwrap().unwrap().id, None);
+        assert!(parser.parse(b"not json,Initech,").is_err());
+
+        assert!(RowParser::new(BulkFormat::Csv).parse(b"id,title").is_err());
+    }
+
+    #[test]
+    fn test_parse_ndjson_rows() {
+        let mut parser = RowParser::new(BulkFormat::Ndjson);
+
+        let row = parser.parse(br#"{"name":"Acme","metadata":{"tier":"gold"}}"#).unwrap().unwrap();
+        assert_eq!(row.name, "Acme");
+        assert!(parser.parse(br#"{"id":"1"}"#).is_err());
+    }
+
+    #[test]
+    fn test_exported_csv_can_be_imported() {
+        let client = Client {
+            id: "7".to_string(),
+            name: "Acme, Inc.".to_string(),
+            created_at: Utc::now(),
+            version: 3,
+            deleted_at: None,
+            metadata: BTreeMap::from([("tier".to_string(), "gold".to_string())]),
+            tags: BTreeSet::new(),
+            custom_fields: BTreeMap::new(),
+        };
+
+        let mut parser = RowParser::new(BulkFormat::Csv);
+        let mut splitter = RecordSplitter::new(BulkFormat::Csv, 1024);
+        let mut records = splitter.push(&csv_header()).unwrap();
+        records.extend(splitter.push(&encode_client(BulkFormat::Csv, &client)).unwrap());
+
+        assert_eq!(parser.parse(&records[0]).unwrap(), None);
+        let row = parser.parse(&records[1]).unwrap().unwrap();
+        assert_eq!(row.id.as_deref(), Some("7"));
+        assert_eq!(row.name, "Acme, Inc.");
+        assert_eq!(row.metadata, client.metadata);
+    }
+
+    fn import(mode: ImportMode, dry_run: bool, input: &[u8]) -> (ImportReport, InMemoryClientRepository) {
+        let clients = InMemoryClientRepository::with_example_data();
+        let mut importer = Importer::new(&clients, Vec::new(), BulkFormat::Ndjson, mode, dry_run);
+        for line in input.split(|byte| *byte == b'\n') {
+            importer.record(line).unwrap();
+        }
+        let (report, _) = importer.finish().unwrap();
+        (report, clients)
+    }
+
+    const MIXED: &[u8] = b"{\"name\":\"Acme\"}\n{\"id\":\"1\",\"name\":\"Taken\"}\n{\"id\":\"9\",\"name\":\"Globex\"}";
+
+    #[test]
+    fn test_all_or_nothing_import() {
+        let (report, clients) = import(ImportMode::AllOrNothing, false, MIXED);
+
+        assert!(!report.committed);
+        assert_eq!((report.total, report.succeeded, report.failed), (3, 2, 1));
+        assert_eq!(report.rows[0].status, RowStatus::Valid);
+        assert_eq!(report.rows[1].status, RowStatus::Failed);
+        assert_eq!(report.rows[1].error.as_deref(), Some("Client 1 already exists"));
+        assert!(clients.get("9", true).is_err());
+
+        let (report, clients) = import(ImportMode::AllOrNothing, false, b"{\"id\":\"9\",\"name\":\"Globex\"}");
+        assert!(report.committed);
+        assert_eq!(report.rows[0].status, RowStatus::Created);
+        assert!(clients.get("9", false).is_ok());
+    }
+
+    #[test]
+    fn test_all_or_nothing_import_is_atomic() {
+        let clients = InMemoryClientRepository::with_example_data();
+        let mut importer = Importer::new(&clients, Vec::new(), BulkFormat::Ndjson, ImportMode::AllOrNothing, false);
+        importer.record(b"{\"id\":\"8\",\"name\":\"Acme\"}").unwrap();
+        importer.record(b"{\"id\":\"9\",\"name\":\"Globex\"}").unwrap();
+
+        // Another request takes an id after the row was validated
+        clients
+            .insert(Client {
+                id: "9".to_string(),
+                name: "Taken".to_string(),
+                created_at: Utc::now(),
+                version: 1,
+                deleted_at: None,
+                metadata: BTreeMap::new(),
+                tags: BTreeSet::new(),
+                custom_fields: BTreeMap::new(),
+            })
+            .unwrap();
+
+        let (report, created) = importer.finish().unwrap();
+        assert!(!report.committed);
+        assert!(created.is_empty());
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.rows[1].error.as_deref(), Some("Client 9 already exists"));
+        assert!(clients.get("8", true).is_err());
+    }
+
+    #[test]
+    fn test_best_effort_import() {
+        let (report, clients) = import(ImportMode::BestEffort, false, MIXED);
+
+        assert!(report.committed);
+        assert_eq!(report.rows[0].status, RowStatus::Created);
+        assert_eq!(report.rows[1].status, RowStatus::Failed);
+        assert_eq!(report.rows[2].status, RowStatus::Created);
+        assert_eq!(clients.get("9", false).unwrap().name, "Globex");
+    }
+
+    #[test]
+    fn test_dry_run_imports_nothing() {
+        let (report, clients) = import(ImportMode::BestEffort, true, MIXED);
+
+        assert!(!report.committed);
+        assert_eq!(report.rows[2].status, RowStatus::Valid);
+        assert!(clients.get("9", true).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_export_stream_batches() {
+        use futures_util::StreamExt;
+
+        let clients = InMemoryClientRepository::default();
+        for index in 0..(EXPORT_BATCH_SIZE + 1) {
+            clients
+                .insert(Client {
+                    id: format!("{:04}", index),
+                    name: format!("Client {}", index),
+                    created_at: Utc::now(),
+                    version: 1,
+                    deleted_at: None,
+                    metadata: BTreeMap::new(),
+                    tags: BTreeSet::new(),
+                    custom_fields: BTreeMap::new(),
+                })
+                .unwrap();
+        }
+
+        let chunks: Vec<Vec<u8>> = export_stream(Arc::new(clients), BulkFormat::Csv, false)
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(chunks.len(), 2);
+        let csv = String::from_utf8(chunks.concat()).unwrap();
+        assert!(csv.starts_with("id,name,created_at,version,deleted_at,metadata,tags,custom_fields\n"));
+        assert_eq!(csv.lines().count(), EXPORT_BATCH_SIZE + 2);
+    }
+
+    #[test]
+    fn test_format_from_content_type() {
+        assert_eq!(BulkFormat::from_content_type("text/csv; charset=utf-8"), Some(BulkFormat::Csv));
+        assert_eq!(BulkFormat::from_content_type("application/x-ndjson"), Some(BulkFormat::Ndjson));
+        assert_eq!(BulkFormat::from_content_type("application/json"), None);
+    }
+}
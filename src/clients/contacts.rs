@@ -0,0 +1,285 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::{models::Contact, repository::Change, validation};
+use crate::{
+    audit::{self, models::AuditAction},
+    auth::Principal,
+    errors::AppError,
+    etag::{check_if_match, etag, etag_header, if_none_match},
+    state::AppState,
+};
+
+// Entity type of contacts in the audit log
+const CONTACT_ENTITY: &str = "contact";
+
+/// Body for creating or replacing a contact
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct ContactRequest {
+    pub name: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub phone: Option<String>,
+    #[serde(default)]
+    pub role: Option<String>,
+    /// Make this the client's primary contact, replacing the current one.
+    /// Left unchanged when omitted; the primary contact stays primary until
+    /// another contact is made primary.
+    #[serde(default)]
+    pub is_primary: Option<bool>,
+}
+
+impl ContactRequest {
+    // Check and normalise the fields onto `contact`
+    fn apply(self, contact: Contact) -> Result<Contact, AppError> {
+        if contact.is_primary && self.is_primary == Some(false) {
+            return Err(AppError::bad_request(
+                "is_primary can't be cleared; make another contact primary instead",
+            ));
+        }
+
+        Ok(Contact {
+            name: validation::required("name", &self.name)?,
+            email: validation::email("email", self.email.as_deref())?,
+            phone: validation::phone("phone", self.phone.as_deref())?,
+            role: validation::optional("role", self.role.as_deref())?,
+            is_primary: self.is_primary.unwrap_or(contact.is_primary),
+            ..contact
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, ToSchema)]
+pub struct ContactListResponse {
+    pub data: Vec<Contact>,
+}
+
+fn record(
+    state: &AppState,
+    principal: &Principal,
+    action: AuditAction,
+    before: Option<&Contact>,
+    after: Option<&Contact>,
+) {
+    let id = after.or(before).map(|contact| contact.id.as_str()).unwrap_or_default();
+    audit::record_committed(
+        state.audit.as_ref(),
+        principal,
+        action,
+        CONTACT_ENTITY,
+        id,
+        before,
+        after,
+    );
+}
+
+// Contacts that lost the primary designation to another one were updated too
+fn record_demoted(state: &AppState, principal: &Principal, demoted: &[Change<Contact>]) {
+    for change in demoted {
+        record(state, principal, AuditAction::Update, Some(&change.before), Some(&change.after));
+    }
+}
+
+/// List the contacts of a client
+///
+/// The primary contact comes first.
+#[utoipa::path(
+    get,
+    path = "/api/clients/{id}/contacts",
+    tag = "clients",
+    params(("id" = String, Path, description = "Client id")),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Contacts of the client", body = ContactListResponse),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Client not found")
+    )
+)]
+pub async fn list_contacts(
+    State(state): State<AppState>,
+    Path(client_id): Path<String>,
+) -> Result<Json<ContactListResponse>, AppError> {
+    let data = state.clients.list_contacts(&client_id)?;
+    Ok(Json(ContactListResponse { data }))
+}
+
+/// Add a contact to a client
+///
+/// The client's first contact always becomes its primary contact.
+#[utoipa::path(
+    post,
+    path = "/api/clients/{id}/contacts",
+    tag = "clients",
+    params(("id" = String, Path, description = "Client id")),
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = ContactRequest,
+    responses(
+        (status = 201, description = "Contact created", body = Contact,
+            headers(("ETag" = String), ("Location" = String))),
+        (status = 400, description = "Invalid contact"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Client not found")
+    )
+)]
+pub async fn create_contact(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(client_id): Path<String>,
+    Json(request): Json<ContactRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let contact = request.apply(Contact {
+        id: Uuid::now_v7().to_string(),
+        client_id,
+        name: String::new(),
+        email: None,
+        phone: None,
+        role: None,
+        is_primary: false,
+        created_at: Utc::now(),
+        version: 1,
+        deleted_at: None,
+    })?;
+
+    let (contact, demoted) = state.clients.insert_contact(contact)?;
+    record(&state, &principal, AuditAction::Create, None, Some(&contact));
+    record_demoted(&state, &principal, &demoted);
+
+    let mut headers = etag_header(contact.version);
+    if let Ok(location) = HeaderValue::from_str(&format!(
+        "/api/clients/{}/contacts/{}",
+        contact.client_id, contact.id
+    )) {
+        headers.insert(header::LOCATION, location);
+    }
+
+    Ok((StatusCode::CREATED, headers, Json(contact)))
+}
+
+/// Get a contact
+#[utoipa::path(
+    get,
+    path = "/api/clients/{id}/contacts/{contact_id}",
+    tag = "clients",
+    params(
+        ("id" = String, Path, description = "Client id"),
+        ("contact_id" = String, Path, description = "Contact id")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "The contact", body = Contact, headers(("ETag" = String))),
+        (status = 304, description = "Unchanged since the `If-None-Match` version"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Client or contact not found")
+    )
+)]
+pub async fn get_contact(
+    State(state): State<AppState>,
+    Path((client_id, id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let contact = state.clients.get_contact(&client_id, &id)?;
+
+    if if_none_match(&headers, &etag(contact.version)) {
+        return Ok((StatusCode::NOT_MODIFIED, etag_header(contact.version)).into_response());
+    }
+
+    Ok((etag_header(contact.version), Json(contact)).into_response())
+}
+
+/// Update a contact
+///
+/// Setting `is_primary` makes this the client's primary contact; leaving it out
+/// keeps the current designation. Send the `ETag` from a previous GET in
+/// `If-Match`, as for clients.
+#[utoipa::path(
+    put,
+    path = "/api/clients/{id}/contacts/{contact_id}",
+    tag = "clients",
+    params(
+        ("id" = String, Path, description = "Client id"),
+        ("contact_id" = String, Path, description = "Contact id"),
+        ("If-Match" = Option<String>, Header, description = "`ETag` the update is based on")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = ContactRequest,
+    responses(
+        (status = 200, description = "Contact updated", body = Contact, headers(("ETag" = String))),
+        (status = 400, description = "Invalid contact"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Client or contact not found"),
+        (status = 412, description = "The contact was modified since the `If-Match` version"),
+        (status = 428, description = "`If-Match` header is required")
+    )
+)]
+pub async fn update_contact(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path((client_id, id)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(request): Json<ContactRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let current = state.clients.get_contact(&client_id, &id)?;
+    let conditional = check_if_match(
+        &headers,
+        &etag(current.version),
+        state.config.clients.require_if_match,
+    )?;
+    let contact = request.apply(current.clone())?;
+
+    let expected_version = conditional.then_some(current.version);
+    let (contact, demoted) = state.clients.update_contact(contact, expected_version)?;
+    record(&state, &principal, AuditAction::Update, Some(&current), Some(&contact));
+    record_demoted(&state, &principal, &demoted);
+
+    Ok((etag_header(contact.version), Json(contact)))
+}
+
+/// Delete a contact
+///
+/// Deleting the primary contact leaves the client without one until another
+/// contact is made primary.
+#[utoipa::path(
+    delete,
+    path = "/api/clients/{id}/contacts/{contact_id}",
+    tag = "clients",
+    params(
+        ("id" = String, Path, description = "Client id"),
+        ("contact_id" = String, Path, description = "Contact id")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 204, description = "Contact deleted"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Client or contact not found")
+    )
+)]
+pub async fn delete_contact(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path((client_id, id)): Path<(String, String)>,
+) -> Result<StatusCode, AppError> {
+    let before = state.clients.get_contact(&client_id, &id)?;
+    let after = state.clients.delete_contact(&client_id, &id)?;
+    record(&state, &principal, AuditAction::Delete, Some(&before), Some(&after));
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,525 @@
+use std::{collections::BTreeMap, sync::RwLock};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Number, Value};
+use utoipa::ToSchema;
+
+use super::{
+    models::{CustomFieldDefinition, CustomFieldType},
+    repository::{CustomFieldFilter, ListQuery},
+    validation,
+};
+use crate::{
+    audit::{self, models::AuditAction},
+    auth::Principal,
+    errors::AppError,
+    state::AppState,
+};
+
+// Entity type of custom field definitions in the audit log
+const CUSTOM_FIELD_ENTITY: &str = "custom_field";
+
+const MAX_KEY_LEN: usize = 50;
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Storage for custom field definitions
+pub trait CustomFieldRepository: Send + Sync {
+    /// All definitions, ordered by key
+    fn list(&self) -> anyhow::Result<Vec<CustomFieldDefinition>>;
+
+    fn get(&self, key: &str) -> anyhow::Result<Option<CustomFieldDefinition>>;
+
+    /// Create or replace a definition, returning the one it replaced
+    fn put(&self, definition: CustomFieldDefinition) -> anyhow::Result<Option<CustomFieldDefinition>>;
+
+    /// Remove a definition, returning it if it existed
+    fn delete(&self, key: &str) -> anyhow::Result<Option<CustomFieldDefinition>>;
+}
+
+/// A [`CustomFieldRepository`] that keeps definitions in memory
+#[derive(Default)]
+pub struct InMemoryCustomFieldRepository {
+    definitions: RwLock<BTreeMap<String, CustomFieldDefinition>>,
+}
+
+impl CustomFieldRepository for InMemoryCustomFieldRepository {
+    fn list(&self) -> anyhow::Result<Vec<CustomFieldDefinition>> {
+        let definitions = self
+            .definitions
+            .read()
+            .map_err(|_| anyhow::anyhow!("custom field lock poisoned"))?;
+        Ok(definitions.values().cloned().collect())
+    }
+
+    fn get(&self, key: &str) -> anyhow::Result<Option<CustomFieldDefinition>> {
+        let definitions = self
+            .definitions
+            .read()
+            .map_err(|_| anyhow::anyhow!("custom field lock poisoned"))?;
+        Ok(definitions.get(key).cloned())
+    }
+
+    fn put(&self, definition: CustomFieldDefinition) -> anyhow::Result<Option<CustomFieldDefinition>> {
+        let mut definitions = self
+            .definitions
+            .write()
+            .map_err(|_| anyhow::anyhow!("custom field lock poisoned"))?;
+        Ok(definitions.insert(definition.key.clone(), definition))
+    }
+
+    fn delete(&self, key: &str) -> anyhow::Result<Option<CustomFieldDefinition>> {
+        let mut definitions = self
+            .definitions
+            .write()
+            .map_err(|_| anyhow::anyhow!("custom field lock poisoned"))?;
+        Ok(definitions.remove(key))
+    }
+}
+
+fn store_error(err: anyhow::Error) -> AppError {
+    AppError::internal_error(format!("Failed to access custom fields: {}", err))
+}
+
+/// All custom field definitions, for validating client values
+pub fn definitions(state: &AppState) -> Result<Vec<CustomFieldDefinition>, AppError> {
+    state.custom_fields.list().map_err(store_error)
+}
+
+/// Check client custom field values against the definitions.
+///
+/// `null` values are dropped, so a merge patch can clear a field. Dates are
+/// normalised to `YYYY-MM-DD`, and required fields must have a value.
+pub fn check_values(
+    definitions: &[CustomFieldDefinition],
+    values: BTreeMap<String, Value>,
+) -> Result<BTreeMap<String, Value>, AppError> {
+    let mut checked = BTreeMap::new();
+
+    for (key, value) in values {
+        if value.is_null() {
+            continue;
+        }
+        let field = format!("custom_fields.{}", key);
+        let definition = definitions
+            .iter()
+            .find(|definition| definition.key == key)
+            .ok_or_else(|| validation::invalid(&field, format!("Unknown custom field {}", key)))?;
+
+        let value = check_value(definition, value).map_err(|message| validation::invalid(&field, message))?;
+        checked.insert(key, value);
+    }
+
+    if let Some(missing) = definitions
+        .iter()
+        .find(|definition| definition.required && !checked.contains_key(&definition.key))
+    {
+        let field = format!("custom_fields.{}", missing.key);
+        return Err(validation::invalid(&field, format!("{} is required", field)));
+    }
+
+    Ok(checked)
+}
+
+// Check one value against its definition, returning it normalised
+fn check_value(definition: &CustomFieldDefinition, value: Value) -> Result<Value, String> {
+    let key = &definition.key;
+    match (definition.field_type, value) {
+        (CustomFieldType::String, Value::String(text)) => validation::optional(key, Some(&text))
+            .map_err(|err| err.message)?
+            .map(Value::String)
+            .ok_or_else(|| format!("{} must not be empty", key)),
+        (CustomFieldType::Number, value @ Value::Number(_)) => Ok(value),
+        (CustomFieldType::Date, Value::String(text)) => NaiveDate::parse_from_str(text.trim(), DATE_FORMAT)
+            .map(|date| Value::String(date.format(DATE_FORMAT).to_string()))
+            .map_err(|_| format!("{} must be a date formatted as YYYY-MM-DD", key)),
+        (CustomFieldType::Enum, Value::String(text)) if definition.options.contains(&text) => {
+            Ok(Value::String(text))
+        }
+        (CustomFieldType::Enum, _) => Err(format!(
+            "{} must be one of {}",
+            key,
+            definition.options.join(", ")
+        )),
+        (CustomFieldType::String, _) => Err(format!("{} must be a string", key)),
+        (CustomFieldType::Number, _) => Err(format!("{} must be a number", key)),
+        (CustomFieldType::Date, _) => Err(format!("{} must be a date formatted as YYYY-MM-DD", key)),
+    }
+}
+
+/// Parse the `custom_field` list filter: comma-separated `key:value`
+/// conditions, where a bare `key` matches any client that has the field set
+pub fn parse_filters(
+    definitions: &[CustomFieldDefinition],
+    filter: &str,
+) -> Result<Vec<CustomFieldFilter>, AppError> {
+    filter
+        .split(',')
+        .map(str::trim)
+        .filter(|condition| !condition.is_empty())
+        .map(|condition| {
+            let (key, raw) = match condition.split_once(':') {
+                Some((key, raw)) => (key.trim(), Some(raw.trim())),
+                None => (condition, None),
+            };
+            let definition = definitions
+                .iter()
+                .find(|definition| definition.key == key)
+                .ok_or_else(|| AppError::bad_request(format!("Unknown custom field {}", key)))?;
+
+            let value = raw
+                .map(|raw| {
+                    let value = match definition.field_type {
+                        CustomFieldType::Number => raw
+                            .parse::<f64>()
+                            .ok()
+                            .and_then(Number::from_f64)
+                            .map(Value::Number)
+                            .ok_or_else(|| format!("{} must be a number", key))?,
+                        _ => Value::String(raw.to_string()),
+                    };
+                    check_value(definition, value)
+                })
+                .transpose()
+                .map_err(AppError::bad_request)?;
+
+            Ok(CustomFieldFilter {
+                key: key.to_string(),
+                value,
+            })
+        })
+        .collect()
+}
+
+/// Body for creating or replacing a custom field definition
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct CustomFieldRequest {
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(rename = "type")]
+    pub field_type: CustomFieldType,
+    /// Allowed values; required for `enum` fields and not allowed otherwise
+    #[serde(default)]
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+impl CustomFieldRequest {
+    fn into_definition(self, key: &str) -> Result<CustomFieldDefinition, AppError> {
+        let valid_key = key.len() <= MAX_KEY_LEN
+            && key.starts_with(|c: char| c.is_ascii_lowercase())
+            && key
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !valid_key {
+            return Err(validation::invalid(
+                "key",
+                format!(
+                    "key must start with a lowercase letter and contain only lowercase letters, digits and underscores, up to {} characters",
+                    MAX_KEY_LEN
+                ),
+            ));
+        }
+
+        let mut options = Vec::with_capacity(self.options.len());
+        for option in &self.options {
+            let option = validation::required("options", option)?;
+            if options.contains(&option) {
+                return Err(validation::invalid("options", format!("Duplicate option {}", option)));
+            }
+            options.push(option);
+        }
+        match (self.field_type, options.is_empty()) {
+            (CustomFieldType::Enum, true) => {
+                return Err(validation::invalid("options", "An enum field needs at least one option"));
+            }
+            (CustomFieldType::String | CustomFieldType::Number | CustomFieldType::Date, false) => {
+                return Err(validation::invalid("options", "Only enum fields have options"));
+            }
+            _ => {}
+        }
+
+        Ok(CustomFieldDefinition {
+            key: key.to_string(),
+            label: validation::optional("label", self.label.as_deref())?,
+            field_type: self.field_type,
+            options,
+            required: self.required,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, ToSchema)]
+pub struct CustomFieldListResponse {
+    pub data: Vec<CustomFieldDefinition>,
+}
+
+// Whether any client, deleted ones included, has the field set (to `value` if given)
+fn in_use(state: &AppState, key: &str, value: Option<Value>) -> Result<bool, AppError> {
+    let page = state.clients.list(&ListQuery {
+        limit: 1,
+        include_deleted: true,
+        custom_fields: vec![CustomFieldFilter {
+            key: key.to_string(),
+            value,
+        }],
+        ..ListQuery::default()
+    })?;
+    Ok(!page.items.is_empty())
+}
+
+fn record(
+    state: &AppState,
+    principal: &Principal,
+    action: AuditAction,
+    before: Option<&CustomFieldDefinition>,
+    after: Option<&CustomFieldDefinition>,
+) {
+    let key = after.or(before).map(|definition| definition.key.as_str()).unwrap_or_default();
+    audit::record_committed(
+        state.audit.as_ref(),
+        principal,
+        action,
+        CUSTOM_FIELD_ENTITY,
+        key,
+        before,
+        after,
+    );
+}
+
+/// List custom field definitions
+#[utoipa::path(
+    get,
+    path = "/api/clients/custom-fields",
+    tag = "clients",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "All custom field definitions", body = CustomFieldListResponse),
+        (status = 401, description = "Unauthorized - Missing or invalid token")
+    )
+)]
+pub async fn list_custom_fields(
+    State(state): State<AppState>,
+) -> Result<Json<CustomFieldListResponse>, AppError> {
+    Ok(Json(CustomFieldListResponse {
+        data: definitions(&state)?,
+    }))
+}
+
+/// Get a custom field definition
+#[utoipa::path(
+    get,
+    path = "/api/clients/custom-fields/{key}",
+    tag = "clients",
+    params(("key" = String, Path, description = "Custom field key")),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "The definition", body = CustomFieldDefinition),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Custom field not found")
+    )
+)]
+pub async fn get_custom_field(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+) -> Result<Json<CustomFieldDefinition>, AppError> {
+    state
+        .custom_fields
+        .get(&key)
+        .map_err(store_error)?
+        .map(Json)
+        .ok_or_else(|| AppError::not_found(format!("Custom field {} not found", key)))
+}
+
+/// Create or replace a custom field definition
+///
+/// The type of a field can only change while no client has a value for it,
+/// and enum options can only be removed while no client uses them.
+#[utoipa::path(
+    put,
+    path = "/api/clients/custom-fields/{key}",
+    tag = "clients",
+    params(("key" = String, Path, description = "Custom field key")),
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = CustomFieldRequest,
+    responses(
+        (status = 200, description = "Definition replaced", body = CustomFieldDefinition),
+        (status = 201, description = "Definition created", body = CustomFieldDefinition),
+        (status = 400, description = "Invalid definition"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 409, description = "The change conflicts with values clients already have")
+    )
+)]
+pub async fn put_custom_field(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(key): Path<String>,
+    Json(request): Json<CustomFieldRequest>,
+) -> Result<(StatusCode, Json<CustomFieldDefinition>), AppError> {
+    let definition = request.into_definition(&key)?;
+    let current = state.custom_fields.get(&key).map_err(store_error)?;
+
+    if let Some(current) = &current {
+        if current.field_type != definition.field_type && in_use(&state, &key, None)? {
+            return Err(AppError::conflict(format!(
+                "The type of {} can't change while clients have a value for it",
+                key
+            )));
+        }
+        if current.field_type == CustomFieldType::Enum && definition.field_type == CustomFieldType::Enum {
+            for removed in current.options.iter().filter(|option| !definition.options.contains(option)) {
+                if in_use(&state, &key, Some(Value::String(removed.clone())))? {
+                    return Err(AppError::conflict(format!("Option {} of {} is in use", removed, key))
+                        .with_details(json!({ "option": removed })));
+                }
+            }
+        }
+    }
+
+    state.custom_fields.put(definition.clone()).map_err(store_error)?;
+
+    let (status, action) = match &current {
+        Some(_) => (StatusCode::OK, AuditAction::Update),
+        None => (StatusCode::CREATED, AuditAction::Create),
+    };
+    record(&state, &principal, action, current.as_ref(), Some(&definition));
+
+    Ok((status, Json(definition)))
+}
+
+/// Delete a custom field definition
+///
+/// Only possible while no client, including deleted ones, has a value for it.
+#[utoipa::path(
+    delete,
+    path = "/api/clients/custom-fields/{key}",
+    tag = "clients",
+    params(("key" = String, Path, description = "Custom field key")),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 204, description = "Definition deleted"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Custom field not found"),
+        (status = 409, description = "Clients still have a value for the field")
+    )
+)]
+pub async fn delete_custom_field(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(key): Path<String>,
+) -> Result<StatusCode, AppError> {
+    if in_use(&state, &key, None)? {
+        return Err(AppError::conflict(format!(
+            "Custom field {} can't be deleted while clients have a value for it",
+            key
+        )));
+    }
+
+    let deleted = state
+        .custom_fields
+        .delete(&key)
+        .map_err(store_error)?
+        .ok_or_else(|| AppError::not_found(format!("Custom field {} not found", key)))?;
+    record(&state, &principal, AuditAction::Delete, Some(&deleted), None);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(key: &str, field_type: CustomFieldType, options: &[&str], required: bool) -> CustomFieldDefinition {
+        CustomFieldDefinition {
+            key: key.to_string(),
+            label: None,
+            field_type,
+            options: options.iter().map(|option| option.to_string()).collect(),
+            required,
+        }
+    }
+
+    fn definitions() -> Vec<CustomFieldDefinition> {
+        vec![
+            definition("tier", CustomFieldType::Enum, &["gold", "silver"], false),
+            definition("employees", CustomFieldType::Number, &[], false),
+            definition("renewal", CustomFieldType::Date, &[], false),
+            definition("owner", CustomFieldType::String, &[], true),
+        ]
+    }
+
+    fn values(values: Value) -> BTreeMap<String, Value> {
+        serde_json::from_value(values).unwrap()
+    }
+
+    #[test]
+    fn test_check_values() {
+        let checked = check_values(
+            &definitions(),
+            values(json!({ "tier": "gold", "employees": 120, "renewal": " 2025-1-5 ", "owner": " Ada ", "unset": null })),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Value::Object(checked.into_iter().collect()),
+            json!({ "tier": "gold", "employees": 120, "renewal": "2025-01-05", "owner": "Ada" })
+        );
+    }
+
+    #[test]
+    fn test_check_values_rejects() {
+        let error = |input: Value| check_values(&definitions(), values(input)).unwrap_err();
+
+        let err = error(json!({ "owner": "Ada", "tier": "bronze" }));
+        assert_eq!(err.message, "tier must be one of gold, silver");
+        assert_eq!(err.details.unwrap()["field"], "custom_fields.tier");
+
+        assert_eq!(error(json!({ "owner": "Ada", "employees": "many" })).message, "employees must be a number");
+        assert!(error(json!({ "owner": "Ada", "renewal": "05/01/2025" })).message.contains("YYYY-MM-DD"));
+        assert_eq!(error(json!({ "owner": "Ada", "color": "red" })).message, "Unknown custom field color");
+        assert_eq!(error(json!({})).message, "custom_fields.owner is required");
+    }
+
+    #[test]
+    fn test_parse_filters() {
+        let filters = parse_filters(&definitions(), "tier:gold, employees:50,renewal").unwrap();
+
+        assert_eq!(filters[0].value, Some(json!("gold")));
+        assert_eq!(filters[1].value.as_ref().and_then(Value::as_f64), Some(50.0));
+        assert_eq!(filters[2], CustomFieldFilter { key: "renewal".to_string(), value: None });
+
+        assert!(parse_filters(&definitions(), "employees:lots").is_err());
+        assert!(parse_filters(&definitions(), "color:red").is_err());
+    }
+
+    #[test]
+    fn test_definition_validation() {
+        let request = |field_type, options: &[&str]| CustomFieldRequest {
+            label: None,
+            field_type,
+            options: options.iter().map(|option| option.to_string()).collect(),
+            required: false,
+        };
+
+        assert!(request(CustomFieldType::Enum, &["a", "b"]).into_definition("tier").is_ok());
+        assert!(request(CustomFieldType::Enum, &[]).into_definition("tier").is_err());
+        assert!(request(CustomFieldType::Enum, &["a", "a"]).into_definition("tier").is_err());
+        assert!(request(CustomFieldType::Number, &["a"]).into_definition("size").is_err());
+        assert!(request(CustomFieldType::Number, &[]).into_definition("Size").is_err());
+        assert!(request(CustomFieldType::Number, &[]).into_definition("1size").is_err());
+    }
+}
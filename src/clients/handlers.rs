@@ -1,72 +1,942 @@
-use axum::Json;
+use std::collections::{BTreeMap, BTreeSet};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{OriginalUri, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use super::{
+    bulk::{export_stream, BulkFormat, ImportMode, ImportReport, Importer, RecordSplitter},
+    custom_fields,
+    models::{Client, CustomFieldDefinition},
+    patch::{self, PatchFormat},
+    repository::{Change, ClientStoreError, ListQuery, Sort},
+    search::{SearchHit, SearchQuery},
+    validation,
+};
+use crate::{
+    audit::{
+        self,
+        handlers::{AuditListResponse, HistoryParams},
+        models::AuditAction,
+    },
+    auth::Principal,
+    errors::AppError,
+    etag::{check_if_match, etag, etag_header, if_none_match},
+    events::{self, ClientEvent},
+    state::AppState,
+};
+
+// Entity type of clients in the audit log
+pub const CLIENT_ENTITY: &str = "client";
+
+const DEFAULT_PAGE_SIZE: usize = 20;
+const MAX_PAGE_SIZE: usize = 100;
+// Times an unconditional patch is re-applied when another update lands first
+const MAX_PATCH_ATTEMPTS: usize = 3;
 
+/// A client's id and name, as shown by the public example list
 #[derive(Serialize, Deserialize, Debug, PartialEq, ToSchema)]
-pub struct Client {
+pub struct ClientSummary {
     pub id: String,
     pub name: String,
 }
 
+/// Body for creating or replacing a client
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct ClientRequest {
+    pub name: String,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    /// Values for the defined custom fields; `null` clears a value
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub custom_fields: BTreeMap<String, Value>,
+}
+
+impl ClientRequest {
+    // Check the request, normalising tags and custom field values
+    fn validate(self, definitions: &[CustomFieldDefinition]) -> Result<Self, AppError> {
+        if self.name.trim().is_empty() {
+            return Err(AppError::bad_request("name must not be empty"));
+        }
+        Ok(Self {
+            tags: validation::tags("tags", &self.tags)?,
+            custom_fields: custom_fields::check_values(definitions, self.custom_fields)?,
+            ..self
+        })
+    }
+}
+
+/// Query parameters for listing clients
+#[derive(Serialize, Deserialize, Debug, Default, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListClientsParams {
+    /// Page size, between 1 and 100 (default 20)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    /// Opaque cursor taken from `next_cursor` of the previous page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    /// Sort field, prefixed with `-` for descending order: `name`, `created_at`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    /// Only clients whose name contains this text (case-insensitive)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_contains: Option<String>,
+    /// Only clients whose name starts with this text (case-insensitive)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_prefix: Option<String>,
+    /// Include the total number of matching clients
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_total: Option<bool>,
+    /// Include soft-deleted clients
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_deleted: Option<bool>,
+    /// Only clients with all of these comma-separated tags
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Only clients matching all of these comma-separated `key:value` custom
+    /// field conditions; a bare `key` matches any client with the field set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_field: Option<String>,
+}
+
+impl ListClientsParams {
+    fn to_query(&self, definitions: &[CustomFieldDefinition]) -> Result<ListQuery, AppError> {
+        let limit = page_size(self.limit)?;
+
+        let sort = match &self.sort {
+            Some(sort) => sort.parse::<Sort>()?,
+            None => Sort::default(),
+        };
+
+        Ok(ListQuery {
+            limit,
+            cursor: self.cursor.clone(),
+            sort,
+            name_contains: self.name_contains.clone(),
+            name_prefix: self.name_prefix.clone(),
+            include_total: self.include_total.unwrap_or(false),
+            include_deleted: self.include_deleted.unwrap_or(false),
+            tags: self
+                .tag
+                .iter()
+                .flat_map(|tags| tags.split(','))
+                .map(|tag| tag.trim().to_lowercase())
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+            custom_fields: match &self.custom_field {
+                Some(filter) => custom_fields::parse_filters(definitions, filter)?,
+                None => Vec::new(),
+            },
+        })
+    }
+}
+
+/// Query parameters for searching clients
+#[derive(Serialize, Deserialize, Debug, Default, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchClientsParams {
+    /// Words to look for in client names and metadata; small typos are tolerated
+    pub q: String,
+    /// Page size, between 1 and 100 (default 20)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    /// Opaque cursor taken from `next_cursor` of the previous page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    /// Include the total number of matching clients
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_total: Option<bool>,
+    /// Include soft-deleted clients
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_deleted: Option<bool>,
+}
+
+impl SearchClientsParams {
+    fn to_query(&self) -> Result<SearchQuery, AppError> {
+        Ok(SearchQuery {
+            q: self.q.clone(),
+            limit: page_size(self.limit)?,
+            cursor: self.cursor.clone(),
+            include_total: self.include_total.unwrap_or(false),
+            include_deleted: self.include_deleted.unwrap_or(false),
+        })
+    }
+}
+
+fn page_size(limit: Option<usize>) -> Result<usize, AppError> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::bad_request(format!(
+            "limit must be between 1 and {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+    Ok(limit)
+}
+
+// `Link` header pointing at the next page: the same query with the cursor replaced
+fn next_link<P: Serialize>(path: &str, params: &P) -> Result<HeaderMap, AppError> {
+    let query = serde_urlencoded::to_string(params)
+        .map_err(|err| AppError::internal_error(err.to_string()))?;
+
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(&format!("<{}?{}>; rel=\"next\"", path, query)) {
+        headers.insert(header::LINK, value);
+    }
+    Ok(headers)
+}
+
+/// Query parameters for fetching a single client
+#[derive(Serialize, Deserialize, Debug, Default, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetClientParams {
+    /// Return the client even if it is soft-deleted
+    pub include_deleted: Option<bool>,
+}
+
+/// Query parameters for importing clients
+#[derive(Serialize, Deserialize, Debug, Default, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportParams {
+    /// `csv` or `ndjson`; taken from the `Content-Type` when absent
+    #[param(inline)]
+    pub format: Option<BulkFormat>,
+    /// `all_or_nothing` (default) or `best_effort`
+    #[param(inline)]
+    pub mode: Option<ImportMode>,
+    /// Validate the rows without importing anything
+    pub dry_run: Option<bool>,
+}
+
+/// Query parameters for exporting clients
+#[derive(Serialize, Deserialize, Debug, Default, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportParams {
+    /// `csv` (default) or `ndjson`
+    #[param(inline)]
+    pub format: Option<BulkFormat>,
+    /// Include soft-deleted clients
+    pub include_deleted: Option<bool>,
+}
+
+/// A page of clients
 #[derive(Serialize, Deserialize, Debug, PartialEq, ToSchema)]
-pub struct ClientResponse {
-    pub message: String,
+pub struct ClientListResponse {
+    pub data: Vec<Client>,
+    /// Cursor for the next page, absent on the last page
+    pub next_cursor: Option<String>,
+    /// Number of matching clients, only present when `include_total=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
 }
 
-/// Get clients endpoint (secured)
-/// 
-/// This endpoint requires authentication with a valid Bearer token.
+/// A page of search results, best matches first
+#[derive(Serialize, Deserialize, Debug, PartialEq, ToSchema)]
+pub struct SearchResponse {
+    pub data: Vec<SearchHit>,
+    /// Cursor for the next page, absent on the last page
+    pub next_cursor: Option<String>,
+    /// Number of matching clients, only present when `include_total=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
+}
+
+/// Get example client list
+///
+/// Returns a fixed example list. Real clients are only listed by the secured
+/// `GET /api/clients`.
+#[utoipa::path(
+    get,
+    path = "/clients",
+    tag = "clients",
+    responses(
+        (status = 200, description = "Successfully retrieved example client list", body = Vec<ClientSummary>),
+    )
+)]
+pub async fn get_clients() -> Json<Vec<ClientSummary>> {
+    Json(vec![ClientSummary {
+        id: "1".to_string(),
+        name: "Example Client".to_string(),
+    }])
+}
+
+/// List clients
+///
+/// Returns a page of clients. The URL of the next page is also given in a `Link` header.
 #[utoipa::path(
     get,
     path = "/api/clients",
     tag = "clients",
+    params(ListClientsParams),
     security(
         ("bearer_auth" = [])
     ),
     responses(
-        (status = 200, description = "Successfully retrieved clients message", body = ClientResponse),
+        (status = 200, description = "Successfully retrieved client list", body = ClientListResponse,
+            headers(("Link" = String, description = "URL of the next page with `rel=\"next\"`"))),
+        (status = 400, description = "Invalid query parameters or cursor"),
         (status = 401, description = "Unauthorized - Missing or invalid token")
     )
 )]
-pub async fn get_secured_clients() -> Json<ClientResponse> {
-    Json(ClientResponse {
-        message: "Clients endpoint".to_string(),
-    })
+pub async fn list_clients(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(params): Query<ListClientsParams>,
+) -> Result<(HeaderMap, Json<ClientListResponse>), AppError> {
+    let definitions = custom_fields::definitions(&state)?;
+    let page = state.clients.list(&params.to_query(&definitions)?)?;
+
+    let headers = match &page.next_cursor {
+        Some(cursor) => next_link(
+            uri.path(),
+            &ListClientsParams {
+                cursor: Some(cursor.clone()),
+                ..params
+            },
+        )?,
+        None => HeaderMap::new(),
+    };
+
+    Ok((
+        headers,
+        Json(ClientListResponse {
+            data: page.items,
+            next_cursor: page.next_cursor,
+            total: page.total,
+        }),
+    ))
 }
 
-/// Get client list
-/// 
-/// Returns a list of clients.
+/// Search clients
+///
+/// Ranked, typo-tolerant search over client names and metadata. Matching words
+/// are wrapped in `<mark>` in the returned highlights; all other text is HTML-escaped.
 #[utoipa::path(
     get,
-    path = "/clients",
+    path = "/api/clients/search",
+    tag = "clients",
+    params(SearchClientsParams),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Matching clients, best first", body = SearchResponse,
+            headers(("Link" = String, description = "URL of the next page with `rel=\"next\"`"))),
+        (status = 400, description = "Missing query or invalid parameters"),
+        (status = 401, description = "Unauthorized - Missing or invalid token")
+    )
+)]
+pub async fn search_clients(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(params): Query<SearchClientsParams>,
+) -> Result<(HeaderMap, Json<SearchResponse>), AppError> {
+    let page = state.clients.search(&params.to_query()?)?;
+
+    let headers = match &page.next_cursor {
+        Some(cursor) => next_link(
+            uri.path(),
+            &SearchClientsParams {
+                cursor: Some(cursor.clone()),
+                ..params
+            },
+        )?,
+        None => HeaderMap::new(),
+    };
+
+    Ok((
+        headers,
+        Json(SearchResponse {
+            data: page.items,
+            next_cursor: page.next_cursor,
+            total: page.total,
+        }),
+    ))
+}
+
+/// Create a client
+#[utoipa::path(
+    post,
+    path = "/api/clients",
+    tag = "clients",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = ClientRequest,
+    responses(
+        (status = 201, description = "Client created", body = Client,
+            headers(("ETag" = String), ("Location" = String))),
+        (status = 400, description = "Invalid client"),
+        (status = 401, description = "Unauthorized - Missing or invalid token")
+    )
+)]
+pub async fn create_client(
+    State(state): State<AppState>,
+    principal: Principal,
+    Json(request): Json<ClientRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request = request.validate(&custom_fields::definitions(&state)?)?;
+
+    let client = state.clients.insert(Client {
+        id: Uuid::now_v7().to_string(),
+        name: request.name,
+        created_at: Utc::now(),
+        version: 1,
+        deleted_at: None,
+        metadata: request.metadata,
+        tags: request.tags,
+        custom_fields: request.custom_fields,
+    })?;
+    record(&state, &principal, AuditAction::Create, None, Some(&client));
+
+    let mut headers = etag_header(client.version);
+    if let Ok(location) = HeaderValue::from_str(&format!("/api/clients/{}", client.id)) {
+        headers.insert(header::LOCATION, location);
+    }
+
+    Ok((StatusCode::CREATED, headers, Json(client)))
+}
+
+/// Get a client
+///
+/// Returns a strong `ETag`. Send it back in `If-None-Match` to get a 304 when
+/// the client is unchanged, or in `If-Match` when updating it.
+#[utoipa::path(
+    get,
+    path = "/api/clients/{id}",
     tag = "clients",
+    params(
+        ("id" = String, Path, description = "Client id"),
+        GetClientParams
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
     responses(
-        (status = 200, description = "Successfully retrieved client list", body = Vec<Client>),
+        (status = 200, description = "The client", body = Client, headers(("ETag" = String))),
+        (status = 304, description = "Not modified since the given `If-None-Match`"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Client not found")
     )
 )]
-pub async fn get_clients() -> Json<Vec<Client>> {
-    Json(vec![
+pub async fn get_client(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<GetClientParams>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let client = state
+        .clients
+        .get(&id, params.include_deleted.unwrap_or(false))?;
+
+    if if_none_match(&headers, &etag(client.version)) {
+        return Ok((StatusCode::NOT_MODIFIED, etag_header(client.version)).into_response());
+    }
+
+    Ok((etag_header(client.version), Json(client)).into_response())
+}
+
+/// Update a client
+///
+/// Replaces the client's name and metadata. Send the `ETag` from a previous GET
+/// in `If-Match` so concurrent edits are detected instead of overwritten.
+#[utoipa::path(
+    put,
+    path = "/api/clients/{id}",
+    tag = "clients",
+    params(
+        ("id" = String, Path, description = "Client id"),
+        ("If-Match" = Option<String>, Header, description = "`ETag` the update is based on")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = ClientRequest,
+    responses(
+        (status = 200, description = "Client updated", body = Client, headers(("ETag" = String))),
+        (status = 400, description = "Invalid client"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Client not found"),
+        (status = 412, description = "The client was modified since the `If-Match` version"),
+        (status = 428, description = "`If-Match` header is required")
+    )
+)]
+pub async fn update_client(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<ClientRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request = request.validate(&custom_fields::definitions(&state)?)?;
+
+    let current = state.clients.get(&id, false)?;
+    let conditional = check_if_match(
+        &headers,
+        &etag(current.version),
+        state.config.clients.require_if_match,
+    )?;
+
+    // Re-checked in storage in case another update landed in between
+    let expected_version = conditional.then_some(current.version);
+    let change = state.clients.update(
         Client {
-            id: "1".to_string(),
-            name: "Example Client".to_string(),
+            name: request.name,
+            metadata: request.metadata,
+            tags: request.tags,
+            custom_fields: request.custom_fields,
+            ..current
+        },
+        expected_version,
+    )?;
+    let client = record_change(&state, &principal, AuditAction::Update, change);
+
+    Ok((etag_header(client.version), Json(client)))
+}
+
+/// Partially update a client
+///
+/// Accepts a JSON Merge Patch (`application/merge-patch+json`) or a JSON Patch
+/// (`application/json-patch+json`) against the client as returned by GET. Only
+/// `name`, `metadata`, `tags` and `custom_fields` can be changed. When a JSON
+/// Patch operation fails, its index is returned in `error.details.operation`.
+#[utoipa::path(
+    patch,
+    path = "/api/clients/{id}",
+    tag = "clients",
+    params(
+        ("id" = String, Path, description = "Client id"),
+        ("If-Match" = Option<String>, Header, description = "`ETag` the patch is based on")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body(
+        content = Object,
+        content_type = "application/merge-patch+json",
+        description = "JSON Merge Patch, or an array of JSON Patch operations sent as application/json-patch+json"
+    ),
+    responses(
+        (status = 200, description = "Client updated", body = Client, headers(("ETag" = String))),
+        (status = 400, description = "Malformed patch document or invalid client"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Client not found"),
+        (status = 409, description = "The client kept changing while the patch was applied"),
+        (status = 412, description = "The client was modified since the `If-Match` version"),
+        (status = 415, description = "Unsupported patch format"),
+        (status = 422, description = "A patch operation failed or the result is not a valid client"),
+        (status = 428, description = "`If-Match` header is required")
+    )
+)]
+pub async fn patch_client(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let format = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(PatchFormat::from_content_type)
+        .ok_or_else(|| {
+            AppError::unsupported_media_type(format!(
+                "Send a {} or {} body",
+                PatchFormat::Merge.content_type(),
+                PatchFormat::Json.content_type()
+            ))
+        })?;
+
+    let definitions = custom_fields::definitions(&state)?;
+    let mut attempt = 1;
+    loop {
+        let current = state.clients.get(&id, false)?;
+        let conditional = check_if_match(
+            &headers,
+            &etag(current.version),
+            state.config.clients.require_if_match,
+        )?;
+
+        let patched = patch::apply(&current, format, &body)?;
+        let request = ClientRequest {
+            name: patched.name,
+            metadata: patched.metadata,
+            tags: patched.tags,
+            custom_fields: patched.custom_fields,
         }
-    ])
+        .validate(&definitions)?;
+
+        // The patch was applied to `current`, so it must not overwrite a newer
+        // version even without If-Match
+        let updated = state.clients.update(
+            Client {
+                name: request.name,
+                metadata: request.metadata,
+                tags: request.tags,
+                custom_fields: request.custom_fields,
+                ..current.clone()
+            },
+            Some(current.version),
+        );
+        match updated {
+            Ok(change) => {
+                let client = record_change(&state, &principal, AuditAction::Update, change);
+                return Ok((etag_header(client.version), Json(client)));
+            }
+            // Without If-Match the caller didn't ask for a particular version,
+            // so apply the patch again to the new one
+            Err(ClientStoreError::VersionConflict { .. }) if !conditional && attempt < MAX_PATCH_ATTEMPTS => {
+                attempt += 1;
+            }
+            Err(err @ ClientStoreError::VersionConflict { .. }) if !conditional => {
+                return Err(AppError::conflict(err.to_string()));
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// Delete a client
+///
+/// Soft-deletes the client. It can be restored until it is purged after the
+/// configured retention period.
+#[utoipa::path(
+    delete,
+    path = "/api/clients/{id}",
+    tag = "clients",
+    params(("id" = String, Path, description = "Client id")),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 204, description = "Client deleted"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Client not found")
+    )
+)]
+pub async fn delete_client(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let change = state.clients.delete(&id)?;
+    record_change(&state, &principal, AuditAction::Delete, change);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Restore a deleted client
+#[utoipa::path(
+    post,
+    path = "/api/clients/{id}/restore",
+    tag = "clients",
+    params(("id" = String, Path, description = "Client id")),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Client restored", body = Client, headers(("ETag" = String))),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Client not found or already purged"),
+        (status = 409, description = "Client is not deleted")
+    )
+)]
+pub async fn restore_client(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let change = state.clients.restore(&id)?;
+    let client = record_change(&state, &principal, AuditAction::Restore, change);
+
+    Ok((etag_header(client.version), Json(client)))
+}
+
+/// Get the change history of a client
+///
+/// Returns the audit events for the client, oldest first. History is kept
+/// after the client is deleted or purged.
+#[utoipa::path(
+    get,
+    path = "/api/clients/{id}/history",
+    tag = "clients",
+    params(
+        ("id" = String, Path, description = "Client id"),
+        HistoryParams
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Audit events for the client", body = AuditListResponse),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Client not found and no history recorded")
+    )
+)]
+pub async fn get_client_history(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<AuditListResponse>, AppError> {
+    let page = state
+        .audit
+        .query(&params.to_filter(CLIENT_ENTITY, &id)?)
+        .map_err(|err| AppError::internal_error(err.to_string()))?;
+
+    if page.events.is_empty() && params.cursor.is_none() {
+        state.clients.get(&id, true)?;
+    }
+
+    Ok(Json(page.into()))
+}
+
+/// Import clients
+///
+/// Accepts CSV (with a header row; `name` is required, `id` and `metadata` as a
+/// JSON object are optional) or NDJSON with one client object per line. The
+/// body is processed as it streams in and a result is reported for every row.
+#[utoipa::path(
+    post,
+    path = "/api/clients/import",
+    tag = "clients",
+    params(ImportParams),
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body(
+        content = String,
+        description = "CSV or NDJSON clients",
+        content_type = "text/csv"
+    ),
+    responses(
+        (status = 200, description = "Import finished; see the per-row results", body = ImportReport),
+        (status = 400, description = "Unknown format or unreadable body or CSV header"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 413, description = "A record is longer than the configured limit"),
+        (status = 422, description = "Some rows are invalid, so nothing was imported", body = ImportReport)
+    )
+)]
+pub async fn import_clients(
+    State(state): State<AppState>,
+    principal: Principal,
+    Query(params): Query<ImportParams>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<(StatusCode, Json<ImportReport>), AppError> {
+    let format = params
+        .format
+        .or_else(|| {
+            headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(BulkFormat::from_content_type)
+        })
+        .ok_or_else(|| {
+            AppError::bad_request(
+                "Send a text/csv or application/x-ndjson body, or set format=csv or format=ndjson",
+            )
+        })?;
+    let mode = params.mode.unwrap_or_default();
+    let dry_run = params.dry_run.unwrap_or(false);
+
+    let mut importer = Importer::new(
+        state.clients.as_ref(),
+        custom_fields::definitions(&state)?,
+        format,
+        mode,
+        dry_run,
+    );
+    let mut splitter = RecordSplitter::new(format, state.config.clients.max_import_record_bytes);
+    let mut body = body.into_data_stream();
+
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk
+            .map_err(|err| AppError::bad_request(format!("Failed to read request body: {}", err)))?;
+        for record in splitter.push(&chunk).map_err(AppError::payload_too_large)? {
+            importer.record(&record)?;
+            record_created(&state, &principal, importer.take_created());
+        }
+    }
+    if let Some(record) = splitter.finish() {
+        importer.record(&record)?;
+        record_created(&state, &principal, importer.take_created());
+    }
+
+    let (report, created) = importer.finish()?;
+    record_created(&state, &principal, created);
+
+    let status = if mode == ImportMode::AllOrNothing && report.failed > 0 && !dry_run {
+        StatusCode::UNPROCESSABLE_ENTITY
+    } else {
+        StatusCode::OK
+    };
+    Ok((status, Json(report)))
+}
+
+/// Export clients
+///
+/// Streams every client as CSV or NDJSON. Clients are read in batches, so the
+/// export is never held in memory as a whole.
+#[utoipa::path(
+    get,
+    path = "/api/clients/export",
+    tag = "clients",
+    params(ExportParams),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "All clients", body = String, content_type = "text/csv"),
+        (status = 401, description = "Unauthorized - Missing or invalid token")
+    )
+)]
+pub async fn export_clients(
+    State(state): State<AppState>,
+    Query(params): Query<ExportParams>,
+) -> impl IntoResponse {
+    let format = params.format.unwrap_or(BulkFormat::Csv);
+    let extension = match format {
+        BulkFormat::Csv => "csv",
+        BulkFormat::Ndjson => "ndjson",
+    };
+
+    let stream = export_stream(
+        state.clients.clone(),
+        format,
+        params.include_deleted.unwrap_or(false),
+    );
+
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"clients.{}\"", extension),
+            ),
+        ],
+        Body::from_stream(stream),
+    )
+}
+
+// Record a stored client change in the audit log and publish it to subscribers
+fn record(
+    state: &AppState,
+    principal: &Principal,
+    action: AuditAction,
+    before: Option<&Client>,
+    after: Option<&Client>,
+) {
+    let id = after.or(before).map(|client| client.id.as_str()).unwrap_or_default();
+    audit::record_committed(
+        state.audit.as_ref(),
+        principal,
+        action,
+        CLIENT_ENTITY,
+        id,
+        before,
+        after,
+    );
+    if let Some(client) = after
+        && let Ok(event_type) = action.try_into()
+    {
+        events::publish(state, ClientEvent::new(event_type, client));
+    }
+}
+
+// Record a change returned by the repository, returning the client as it is now
+fn record_change(
+    state: &AppState,
+    principal: &Principal,
+    action: AuditAction,
+    change: Change<Client>,
+) -> Client {
+    record(state, principal, action, Some(&change.before), Some(&change.after));
+    change.after
+}
+
+// Audit and publish imported clients once they are stored, so a request that
+// fails part-way still records the rows it wrote
+fn record_created(state: &AppState, principal: &Principal, created: Vec<Client>) {
+    for client in &created {
+        record(state, principal, AuditAction::Create, None, Some(client));
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use axum::http::{StatusCode, Uri};
+
+    fn list(params: ListClientsParams) -> ListQuery {
+        params.to_query(&[]).unwrap()
+    }
 
     #[tokio::test]
     async fn test_get_clients() {
-        let result = get_clients().await;
-        let clients = result.0;
-        
-        assert_eq!(clients.len(), 1);
-        assert_eq!(clients[0], Client {
+        let clients = get_clients().await.0;
+
+        assert_eq!(clients, vec![ClientSummary {
             id: "1".to_string(),
             name: "Example Client".to_string(),
+        }]);
+    }
+
+    #[tokio::test]
+    async fn test_list_clients() {
+        let (headers, result) = list_clients(
+            State(AppState::default()),
+            OriginalUri(Uri::from_static("/api/clients")),
+            Query(ListClientsParams::default()),
+        )
+        .await
+        .unwrap();
+        let clients = result.0;
+
+        assert_eq!(clients.data.len(), 1);
+        assert_eq!(clients.data[0].id, "1");
+        assert_eq!(clients.data[0].name, "Example Client");
+        assert_eq!(clients.next_cursor, None);
+        assert!(headers.get(header::LINK).is_none());
+    }
+
+    #[test]
+    fn test_params_to_query() {
+        let query = list(ListClientsParams::default());
+        assert_eq!(query.limit, DEFAULT_PAGE_SIZE);
+        assert_eq!(query.sort, Sort::default());
+        assert!(!query.include_total);
+
+        let query = list(ListClientsParams {
+            sort: Some("-name".to_string()),
+            include_total: Some(true),
+            ..ListClientsParams::default()
         });
+        assert!(query.sort.descending);
+        assert!(query.include_total);
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        for params in [
+            ListClientsParams { limit: Some(0), ..ListClientsParams::default() },
+            ListClientsParams { limit: Some(101), ..ListClientsParams::default() },
+            ListClientsParams { sort: Some("email".to_string()), ..ListClientsParams::default() },
+        ] {
+            assert_eq!(params.to_query(&[]).unwrap_err().status, StatusCode::BAD_REQUEST);
+        }
     }
-}
\ No newline at end of file
+}
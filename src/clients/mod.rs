@@ -1,2 +1,13 @@
+pub mod addresses;
+pub mod bulk;
+pub mod contacts;
+pub mod custom_fields;
 pub mod handlers;
-pub mod routes;
\ No newline at end of file
+pub mod models;
+pub mod patch;
+pub mod purge;
+pub mod repository;
+pub mod routes;
+pub mod search;
+pub mod stream;
+pub mod validation;
@@ -0,0 +1,123 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Client {
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    /// Incremented on every change; used as the `ETag`
+    #[serde(default = "initial_version")]
+    pub version: u64,
+    /// Set while the client is soft-deleted and can still be restored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Free-form string attributes, e.g. `industry` or `account_manager`
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// Free-form lowercase labels, e.g. `vip`
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    /// Values of the custom fields defined under `/api/clients/custom-fields`
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub custom_fields: BTreeMap<String, Value>,
+}
+
+fn initial_version() -> u64 {
+    1
+}
+
+/// A person at a client
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Contact {
+    pub id: String,
+    pub client_id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phone: Option<String>,
+    /// Job title or role at the client, e.g. `CFO`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    /// The client's main point of contact; a client has at most one
+    #[serde(default)]
+    pub is_primary: bool,
+    pub created_at: DateTime<Utc>,
+    #[serde(default = "initial_version")]
+    pub version: u64,
+    /// Set when the client was soft-deleted, or the contact itself was deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+/// What an address is used for
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressKind {
+    Billing,
+    Shipping,
+    Office,
+    #[default]
+    Other,
+}
+
+/// A postal address of a client
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Address {
+    pub id: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub kind: AddressKind,
+    pub line1: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line2: Option<String>,
+    pub city: String,
+    /// State, province or county
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub postal_code: Option<String>,
+    /// ISO 3166-1 alpha-2 country code, e.g. `DE`
+    pub country: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default = "initial_version")]
+    pub version: u64,
+    /// Set when the client was soft-deleted, or the address itself was deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+/// Type of the values of a custom field
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CustomFieldType {
+    String,
+    Number,
+    /// A calendar date, `YYYY-MM-DD`
+    Date,
+    /// One of the definition's `options`
+    Enum,
+}
+
+/// An admin-defined field that clients can hold a value for
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct CustomFieldDefinition {
+    /// Lowercase letters, digits and underscores, starting with a letter
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(rename = "type")]
+    pub field_type: CustomFieldType,
+    /// Allowed values of an `enum` field
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+    /// Whether clients must have a value when they are created or updated
+    #[serde(default)]
+    pub required: bool,
+}
@@ -0,0 +1,227 @@
+use json_patch::PatchOperation;
+use serde_json::{json, Value};
+
+use super::models::Client;
+use crate::errors::AppError;
+
+// Fields a patch may not change or add
+const READ_ONLY_FIELDS: [&str; 4] = ["id", "created_at", "version", "deleted_at"];
+const WRITABLE_FIELDS: [&str; 4] = ["name", "metadata", "tags", "custom_fields"];
+
+/// Media type of a `PATCH` body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    /// JSON Merge Patch (RFC 7396)
+    Merge,
+    /// JSON Patch (RFC 6902)
+    Json,
+}
+
+impl PatchFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Merge => "application/merge-patch+json",
+            Self::Json => "application/json-patch+json",
+        }
+    }
+
+    /// Recognise a format from a request `Content-Type`
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        match mime {
+            "application/merge-patch+json" => Some(Self::Merge),
+            "application/json-patch+json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Apply a patch document to the client as it is returned by GET.
+///
+/// JSON Patch operations are applied atomically; the first one that fails is
+/// reported with its index in `details.operation`. The result may only change
+/// `name`, `metadata`, `tags` and `custom_fields`, and must still deserialize
+/// as a client.
+pub fn apply(current: &Client, format: PatchFormat, body: &[u8]) -> Result<Client, AppError> {
+    let patch: Value = serde_json::from_slice(body)
+        .map_err(|err| AppError::bad_request(format!("Invalid patch document: {}", err)))?;
+
+    let original = serde_json::to_value(current).map_err(crate::errors::internal_error)?;
+    let mut document = original.clone();
+
+    match format {
+        PatchFormat::Merge => json_patch::merge(&mut document, &patch),
+        PatchFormat::Json => {
+            let operations = operations(patch)?;
+            json_patch::patch(&mut document, &operations).map_err(|err| {
+                AppError::unprocessable_entity(format!(
+                    "Patch operation {} failed: {}",
+                    err.operation, err.kind
+                ))
+                .with_details(json!({ "operation": err.operation, "path": err.path }))
+            })?;
+        }
+    }
+
+    check_fields(&original, &document)?;
+
+    serde_json::from_value(document)
+        .map_err(|err| AppError::unprocessable_entity(format!("Patched client is invalid: {}", err)))
+}
+
+// Parse JSON Patch operations one by one so a malformed one can be pointed at
+fn operations(patch: Value) -> Result<Vec<PatchOperation>, AppError> {
+    let Value::Array(operations) = patch else {
+        return Err(AppError::bad_request(
+            "A JSON Patch document must be an array of operations",
+        ));
+    };
+
+    operations
+        .into_iter()
+        .enumerate()
+        .map(|(index, operation)| {
+            serde_json::from_value(operation).map_err(|err| {
+                AppError::bad_request(format!("Patch operation {} is invalid: {}", index, err))
+                    .with_details(json!({ "operation": index }))
+            })
+        })
+        .collect()
+}
+
+// Reject patches that touch read-only fields or add unknown ones
+fn check_fields(original: &Value, patched: &Value) -> Result<(), AppError> {
+    let Value::Object(fields) = patched else {
+        return Err(AppError::unprocessable_entity("Patched client must be an object"));
+    };
+
+    if let Some(field) = READ_ONLY_FIELDS
+        .into_iter()
+        .find(|field| original.get(field) != patched.get(field))
+    {
+        return Err(AppError::unprocessable_entity(format!("{} is read-only", field))
+            .with_details(json!({ "field": field })));
+    }
+
+    if let Some(field) = fields
+        .keys()
+        .find(|key| !READ_ONLY_FIELDS.contains(&key.as_str()) && !WRITABLE_FIELDS.contains(&key.as_str()))
+    {
+        return Err(AppError::unprocessable_entity(format!("Unknown field {}", field))
+            .with_details(json!({ "field": field })));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use axum::http::StatusCode;
+    use chrono::Utc;
+
+    fn client() -> Client {
+        Client {
+            id: "1".to_string(),
+            name: "Acme".to_string(),
+            created_at: Utc::now(),
+            version: 3,
+            deleted_at: None,
+            metadata: [
+                ("industry".to_string(), "Retail".to_string()),
+                ("tier".to_string(), "gold".to_string()),
+            ]
+            .into(),
+            tags: BTreeSet::new(),
+            custom_fields: BTreeMap::new(),
+        }
+    }
+
+    fn patch(format: PatchFormat, body: Value) -> Result<Client, AppError> {
+        apply(&client(), format, body.to_string().as_bytes())
+    }
+
+    #[test]
+    fn test_from_content_type() {
+        assert_eq!(
+            PatchFormat::from_content_type("application/merge-patch+json; charset=utf-8"),
+            Some(PatchFormat::Merge)
+        );
+        assert_eq!(
+            PatchFormat::from_content_type(PatchFormat::Json.content_type()),
+            Some(PatchFormat::Json)
+        );
+        assert_eq!(PatchFormat::from_content_type("application/json"), None);
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        let patched = patch(
+            PatchFormat::Merge,
+            json!({ "name": "Acme Corp", "metadata": { "tier": null, "region": "EU" } }),
+        )
+        .unwrap();
+
+        assert_eq!(patched.name, "Acme Corp");
+        assert_eq!(patched.metadata.get("industry").unwrap(), "Retail");
+        assert_eq!(patched.metadata.get("region").unwrap(), "EU");
+        assert!(!patched.metadata.contains_key("tier"));
+    }
+
+    #[test]
+    fn test_json_patch() {
+        let patched = patch(
+            PatchFormat::Json,
+            json!([
+                { "op": "test", "path": "/name", "value": "Acme" },
+                { "op": "replace", "path": "/name", "value": "Acme Corp" },
+                { "op": "remove", "path": "/metadata/tier" }
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(patched.name, "Acme Corp");
+        assert!(!patched.metadata.contains_key("tier"));
+    }
+
+    #[test]
+    fn test_failing_operation_is_reported() {
+        let err = patch(
+            PatchFormat::Json,
+            json!([
+                { "op": "replace", "path": "/name", "value": "Acme Corp" },
+                { "op": "test", "path": "/metadata/tier", "value": "silver" }
+            ]),
+        )
+        .unwrap_err();
+
+        assert_eq!(err.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(err.details.unwrap()["operation"], 1);
+
+        let err = patch(PatchFormat::Json, json!([{ "op": "move", "path": "/name" }])).unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+        assert_eq!(err.details.unwrap()["operation"], 0);
+    }
+
+    #[test]
+    fn test_rejects_invalid_results() {
+        let read_only = patch(PatchFormat::Merge, json!({ "version": 9 })).unwrap_err();
+        assert_eq!(read_only.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(read_only.details.unwrap()["field"], "version");
+
+        let unknown = patch(
+            PatchFormat::Json,
+            json!([{ "op": "add", "path": "/owner", "value": "me" }]),
+        )
+        .unwrap_err();
+        assert_eq!(unknown.details.unwrap()["field"], "owner");
+
+        let wrong_type = patch(PatchFormat::Merge, json!({ "metadata": { "tier": 1 } })).unwrap_err();
+        assert_eq!(wrong_type.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let not_an_array = patch(PatchFormat::Json, json!({ "name": "Acme" })).unwrap_err();
+        assert_eq!(not_an_array.status, StatusCode::BAD_REQUEST);
+    }
+}
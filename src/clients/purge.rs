@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::task::JoinHandle;
+
+use super::{handlers::CLIENT_ENTITY, models::Client};
+use crate::{
+    audit::{self, models::AuditAction},
+    auth::Principal,
+    state::AppState,
+};
+
+// Actor recorded for purges, which no request asked for
+const SYSTEM_ACTOR: &str = "system";
+
+/// Start the background job that hard-deletes clients once their retention period is over
+pub fn spawn(state: AppState) -> JoinHandle<()> {
+    let config = &state.config.clients;
+    // Validated at startup; keep deleted clients rather than panic if it wasn't
+    let retention = config.retention();
+    let period = Duration::from_secs(config.purge_interval_seconds.max(1));
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+
+            let cutoff = retention.and_then(|retention| Utc::now().checked_sub_signed(retention));
+            let Some(cutoff) = cutoff else {
+                tracing::error!("Retention period is too long; not purging deleted clients");
+                continue;
+            };
+
+            match state.clients.purge_deleted_before(cutoff) {
+                Ok(purged) if purged.is_empty() => {}
+                Ok(purged) => {
+                    tracing::info!(purged = purged.len(), "Purged deleted clients");
+                    record_purged(&state, &purged);
+                }
+                Err(err) => tracing::error!(error = %err, "Failed to purge deleted clients"),
+            }
+        }
+    })
+}
+
+fn record_purged(state: &AppState, purged: &[Client]) {
+    let actor = Principal {
+        subject: SYSTEM_ACTOR.to_string(),
+    };
+    for client in purged {
+        audit::record_committed(
+            state.audit.as_ref(),
+            &actor,
+            AuditAction::Purge,
+            CLIENT_ENTITY,
+            &client.id,
+            Some(client),
+            None,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        audit::store::AuditFilter,
+        config::{AppConfig, ClientsConfig},
+    };
+
+    #[tokio::test(start_paused = true)]
+    async fn test_purges_on_each_interval() {
+        let state = AppState::new(AppConfig {
+            clients: ClientsConfig {
+                deleted_retention_days: 0,
+                purge_interval_seconds: 60,
+                ..ClientsConfig::default()
+            },
+            ..AppConfig::default()
+        });
+
+        let job = spawn(state.clone());
+
+        // Let the first, immediate purge run before anything is deleted
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        state.clients.delete("1").unwrap();
+        assert!(state.clients.get("1", true).is_ok());
+
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert!(state.clients.get("1", true).is_err());
+
+        let events = state
+            .audit
+            .query(&AuditFilter {
+                entity_id: Some("1".to_string()),
+                limit: 10,
+                ..AuditFilter::default()
+            })
+            .unwrap()
+            .events;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action, AuditAction::Purge);
+        assert_eq!(events[0].actor, SYSTEM_ACTOR);
+
+        job.abort();
+    }
+}
@@ -0,0 +1,1300 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt,
+    str::FromStr,
+    sync::RwLock,
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    models::{Address, Client, Contact},
+    search::{search_client, SearchHit, SearchQuery},
+};
+use crate::errors::AppError;
+
+/// Errors returned by a [`ClientRepository`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum ClientStoreError {
+    /// The query could not be run, e.g. an unknown sort field or a malformed cursor
+    InvalidQuery(String),
+    /// No client with this id
+    NotFound(String),
+    /// A client with this id already exists, possibly deleted
+    AlreadyExists(String),
+    /// The client changed since the version the caller based its update on
+    VersionConflict { id: String, current: u64 },
+    /// Restore was requested for a client that is not deleted
+    NotDeleted(String),
+    /// No contact or address with this id under the client
+    ChildNotFound { resource: &'static str, id: String },
+    /// The contact or address changed since the version the caller based its update on
+    ChildVersionConflict {
+        resource: &'static str,
+        id: String,
+        current: u64,
+    },
+}
+
+impl fmt::Display for ClientStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidQuery(message) => write!(f, "{}", message),
+            Self::NotFound(id) => write!(f, "Client {} not found", id),
+            Self::AlreadyExists(id) => write!(f, "Client {} already exists", id),
+            Self::VersionConflict { id, current } => write!(
+                f,
+                "Client {} has been modified (current version {})",
+                id, current
+            ),
+            Self::NotDeleted(id) => write!(f, "Client {} is not deleted", id),
+            Self::ChildNotFound { resource, id } => write!(f, "{} {} not found", resource, id),
+            Self::ChildVersionConflict {
+                resource,
+                id,
+                current,
+            } => write!(
+                f,
+                "{} {} has been modified (current version {})",
+                resource, id, current
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ClientStoreError {}
+
+impl From<ClientStoreError> for AppError {
+    fn from(err: ClientStoreError) -> Self {
+        match &err {
+            ClientStoreError::InvalidQuery(message) => AppError::bad_request(message.clone()),
+            ClientStoreError::NotFound(_) | ClientStoreError::ChildNotFound { .. } => {
+                AppError::not_found(err.to_string())
+            }
+            ClientStoreError::VersionConflict { .. }
+            | ClientStoreError::ChildVersionConflict { .. } => {
+                AppError::precondition_failed(err.to_string())
+            }
+            ClientStoreError::NotDeleted(_) | ClientStoreError::AlreadyExists(_) => {
+                AppError::conflict(err.to_string())
+            }
+        }
+    }
+}
+
+/// Field the client list can be sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortField {
+    Name,
+    CreatedAt,
+}
+
+/// Sort order for the client list, written as `name` or `-created_at`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sort {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+impl Default for Sort {
+    fn default() -> Self {
+        Self {
+            field: SortField::CreatedAt,
+            descending: false,
+        }
+    }
+}
+
+impl FromStr for Sort {
+    type Err = ClientStoreError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (descending, name) = match value.strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, value),
+        };
+
+        let field = match name {
+            "name" => SortField::Name,
+            "created_at" => SortField::CreatedAt,
+            _ => {
+                return Err(ClientStoreError::InvalidQuery(format!(
+                    "Unknown sort field: {}",
+                    name
+                )))
+            }
+        };
+
+        Ok(Self { field, descending })
+    }
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.field {
+            SortField::Name => "name",
+            SortField::CreatedAt => "created_at",
+        };
+        if self.descending {
+            write!(f, "-{}", name)
+        } else {
+            write!(f, "{}", name)
+        }
+    }
+}
+
+impl Sort {
+    // Keys compare the same way as the field they represent
+    fn key(&self, client: &Client) -> String {
+        match self.field {
+            SortField::Name => client.name.to_lowercase(),
+            SortField::CreatedAt => client
+                .created_at
+                .to_rfc3339_opts(SecondsFormat::Nanos, true),
+        }
+    }
+}
+
+/// Opaque keyset cursor pointing just past the last client of a page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cursor {
+    sort: String,
+    key: String,
+    id: String,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("cursor serializes");
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub fn decode(value: &str) -> Result<Self, ClientStoreError> {
+        let invalid = || ClientStoreError::InvalidQuery("Invalid cursor".to_string());
+        let json = URL_SAFE_NO_PAD.decode(value).map_err(|_| invalid())?;
+        serde_json::from_slice(&json).map_err(|_| invalid())
+    }
+}
+
+/// Filters, ordering and paging for listing clients
+#[derive(Debug, Clone, Default)]
+pub struct ListQuery {
+    pub limit: usize,
+    pub cursor: Option<String>,
+    pub sort: Sort,
+    /// Case-insensitive substring match on the name
+    pub name_contains: Option<String>,
+    /// Case-insensitive prefix match on the name
+    pub name_prefix: Option<String>,
+    pub include_total: bool,
+    /// Also return soft-deleted clients
+    pub include_deleted: bool,
+    /// Only clients with every one of these tags
+    pub tags: Vec<String>,
+    /// Only clients matching every one of these custom field conditions
+    pub custom_fields: Vec<CustomFieldFilter>,
+}
+
+/// A condition on a custom field value
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomFieldFilter {
+    pub key: String,
+    /// Value to match; `None` matches any client that has the field set
+    pub value: Option<Value>,
+}
+
+impl CustomFieldFilter {
+    fn matches(&self, client: &Client) -> bool {
+        let Some(actual) = client.custom_fields.get(&self.key) else {
+            return false;
+        };
+        match (&self.value, actual) {
+            (None, _) => true,
+            // `5` and `5.0` are the same number
+            (Some(Value::Number(expected)), Value::Number(actual)) => expected.as_f64() == actual.as_f64(),
+            (Some(expected), actual) => expected == actual,
+        }
+    }
+}
+
+impl ListQuery {
+    fn matches(&self, client: &Client) -> bool {
+        if client.deleted_at.is_some() && !self.include_deleted {
+            return false;
+        }
+
+        let name = client.name.to_lowercase();
+
+        let contains = self
+            .name_contains
+            .as_ref()
+            .is_none_or(|text| name.contains(&text.to_lowercase()));
+        let prefix = self
+            .name_prefix
+            .as_ref()
+            .is_none_or(|text| name.starts_with(&text.to_lowercase()));
+
+        contains
+            && prefix
+            && self.tags.iter().all(|tag| client.tags.contains(tag))
+            && self.custom_fields.iter().all(|filter| filter.matches(client))
+    }
+}
+
+/// One page of results
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub total: Option<usize>,
+}
+
+// Sort key for relevance: higher scores sort first in ascending key order
+fn relevance_key(score: u32) -> String {
+    format!("{:010}", u32::MAX - score)
+}
+
+// Take one page from `entries`, which are already ordered by (key, id)
+fn paginate<T>(
+    entries: Vec<(String, T)>,
+    id: impl Fn(&T) -> &String,
+    sort_name: &str,
+    descending: bool,
+    cursor: Option<&str>,
+    limit: usize,
+    include_total: bool,
+) -> Result<Page<T>, ClientStoreError> {
+    let total = include_total.then_some(entries.len());
+
+    let start = match cursor {
+        Some(cursor) => {
+            let cursor = Cursor::decode(cursor)?;
+            if cursor.sort != sort_name {
+                return Err(ClientStoreError::InvalidQuery(
+                    "Cursor does not match the requested sort".to_string(),
+                ));
+            }
+
+            entries
+                .iter()
+                .position(|(key, item)| {
+                    let position = (key, id(item)).cmp(&(&cursor.key, &cursor.id));
+                    if descending {
+                        position.is_lt()
+                    } else {
+                        position.is_gt()
+                    }
+                })
+                .unwrap_or(entries.len())
+        }
+        None => 0,
+    };
+
+    let remaining = entries.len() - start;
+    let items: Vec<(String, T)> = entries.into_iter().skip(start).take(limit).collect();
+
+    let next_cursor = match items.last() {
+        Some((key, item)) if remaining > items.len() => Some(
+            Cursor {
+                sort: sort_name.to_string(),
+                key: key.clone(),
+                id: id(item).clone(),
+            }
+            .encode(),
+        ),
+        _ => None,
+    };
+
+    Ok(Page {
+        items: items.into_iter().map(|(_, item)| item).collect(),
+        next_cursor,
+        total,
+    })
+}
+
+/// Storage for clients
+pub trait ClientRepository: Send + Sync {
+    fn list(&self, query: &ListQuery) -> Result<Page<Client>, ClientStoreError>;
+
+    /// Ranked full-text search over names and metadata
+    fn search(&self, query: &SearchQuery) -> Result<Page<SearchHit>, ClientStoreError>;
+
+    /// Look up a client; soft-deleted clients count as not found unless `include_deleted`
+    fn get(&self, id: &str, include_deleted: bool) -> Result<Client, ClientStoreError>;
+
+    /// Add a client; fails if its id is taken, even by a deleted client
+    fn insert(&self, client: Client) -> Result<Client, ClientStoreError>;
+
+    /// Add all of `clients` or, if any id is taken, none of them
+    fn insert_all(&self, clients: Vec<Client>) -> Result<Vec<Client>, ClientStoreError>;
+
+    /// Replace a client's name and metadata, bumping its version.
+    ///
+    /// With `expected_version`, the update only applies if the stored client is
+    /// still at that version. Like the other changes to a client, returns it as
+    /// it was right before and after, read under the same lock as the write.
+    fn update(&self, client: Client, expected_version: Option<u64>) -> Result<Change<Client>, ClientStoreError>;
+
+    /// Soft-delete a client so it can be restored until it is purged
+    fn delete(&self, id: &str) -> Result<Change<Client>, ClientStoreError>;
+
+    /// Undo a soft delete
+    fn restore(&self, id: &str) -> Result<Change<Client>, ClientStoreError>;
+
+    /// Permanently remove clients deleted at or before `cutoff`, returning the removed clients
+    fn purge_deleted_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<Client>, ClientStoreError>;
+
+    /// Contacts of a client that is not deleted, primary contact first
+    fn list_contacts(&self, client_id: &str) -> Result<Vec<Contact>, ClientStoreError>;
+
+    fn get_contact(&self, client_id: &str, id: &str) -> Result<Contact, ClientStoreError>;
+
+    /// Add a contact to a client, returning it with the contacts it demoted.
+    ///
+    /// The first contact of a client always becomes its primary contact. A new
+    /// primary contact takes the designation over from the previous one.
+    fn insert_contact(&self, contact: Contact) -> Result<(Contact, Vec<Change<Contact>>), ClientStoreError>;
+
+    /// Replace a contact, bumping its version; fails if `expected_version` is
+    /// given and no longer current. Returns the contacts it demoted.
+    ///
+    /// A primary contact stays primary until another contact takes the
+    /// designation over.
+    fn update_contact(
+        &self,
+        contact: Contact,
+        expected_version: Option<u64>,
+    ) -> Result<(Contact, Vec<Change<Contact>>), ClientStoreError>;
+
+    /// Soft-delete a contact, bumping its version
+    fn delete_contact(&self, client_id: &str, id: &str) -> Result<Contact, ClientStoreError>;
+
+    /// Addresses of a client that is not deleted
+    fn list_addresses(&self, client_id: &str) -> Result<Vec<Address>, ClientStoreError>;
+
+    fn get_address(&self, client_id: &str, id: &str) -> Result<Address, ClientStoreError>;
+
+    fn insert_address(&self, address: Address) -> Result<Address, ClientStoreError>;
+
+    /// Replace an address, bumping its version; fails if `expected_version` is
+    /// given and no longer current
+    fn update_address(&self, address: Address, expected_version: Option<u64>) -> Result<Address, ClientStoreError>;
+
+    /// Soft-delete an address, bumping its version
+    fn delete_address(&self, client_id: &str, id: &str) -> Result<Address, ClientStoreError>;
+}
+
+// A client that exists and is not soft-deleted
+fn live_mut<'a>(
+    clients: &'a mut BTreeMap<String, Client>,
+    id: &str,
+) -> Result<&'a mut Client, ClientStoreError> {
+    clients
+        .get_mut(id)
+        .filter(|client| client.deleted_at.is_none())
+        .ok_or_else(|| ClientStoreError::NotFound(id.to_string()))
+}
+
+fn ensure_live(clients: &BTreeMap<String, Client>, id: &str) -> Result<(), ClientStoreError> {
+    match clients.get(id) {
+        Some(client) if client.deleted_at.is_none() => Ok(()),
+        _ => Err(ClientStoreError::NotFound(id.to_string())),
+    }
+}
+
+// Contacts and addresses, which belong to a client and are deleted along with it
+trait Child: Clone {
+    const RESOURCE: &'static str;
+
+    fn id(&self) -> &str;
+    fn client_id(&self) -> &str;
+    fn version(&self) -> u64;
+    fn deleted_at(&self) -> Option<DateTime<Utc>>;
+    fn set_state(&mut self, version: u64, deleted_at: Option<DateTime<Utc>>);
+}
+
+impl Child for Contact {
+    const RESOURCE: &'static str = "Contact";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+    fn client_id(&self) -> &str {
+        &self.client_id
+    }
+    fn version(&self) -> u64 {
+        self.version
+    }
+    fn deleted_at(&self) -> Option<DateTime<Utc>> {
+        self.deleted_at
+    }
+    fn set_state(&mut self, version: u64, deleted_at: Option<DateTime<Utc>>) {
+        self.version = version;
+        self.deleted_at = deleted_at;
+    }
+}
+
+impl Child for Address {
+    const RESOURCE: &'static str = "Address";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+    fn client_id(&self) -> &str {
+        &self.client_id
+    }
+    fn version(&self) -> u64 {
+        self.version
+    }
+    fn deleted_at(&self) -> Option<DateTime<Utc>> {
+        self.deleted_at
+    }
+    fn set_state(&mut self, version: u64, deleted_at: Option<DateTime<Utc>>) {
+        self.version = version;
+        self.deleted_at = deleted_at;
+    }
+}
+
+// Children keyed by (client id, child id) so a client's children are one range
+type Children<T> = BTreeMap<(String, String), T>;
+
+fn child_key<T: Child>(child: &T) -> (String, String) {
+    (child.client_id().to_string(), child.id().to_string())
+}
+
+fn children_of<'a, T: Child>(children: &'a Children<T>, client_id: &'a str) -> impl Iterator<Item = &'a T> {
+    children
+        .range((client_id.to_string(), String::new())..)
+        .take_while(move |((owner, _), _)| owner == client_id)
+        .map(|(_, child)| child)
+}
+
+fn children_of_mut<'a, T: Child>(
+    children: &'a mut Children<T>,
+    client_id: &'a str,
+) -> impl Iterator<Item = &'a mut T> {
+    children
+        .range_mut((client_id.to_string(), String::new())..)
+        .take_while(move |((owner, _), _)| owner == client_id)
+        .map(|(_, child)| child)
+}
+
+fn live_children<T: Child>(children: &Children<T>, client_id: &str) -> Vec<T> {
+    children_of(children, client_id)
+        .filter(|child| child.deleted_at().is_none())
+        .cloned()
+        .collect()
+}
+
+fn child_not_found<T: Child>(id: &str) -> ClientStoreError {
+    ClientStoreError::ChildNotFound {
+        resource: T::RESOURCE,
+        id: id.to_string(),
+    }
+}
+
+fn live_child<'a, T: Child>(children: &'a Children<T>, client_id: &str, id: &str) -> Result<&'a T, ClientStoreError> {
+    children
+        .get(&(client_id.to_string(), id.to_string()))
+        .filter(|child| child.deleted_at().is_none())
+        .ok_or_else(|| child_not_found::<T>(id))
+}
+
+fn live_child_mut<'a, T: Child>(
+    children: &'a mut Children<T>,
+    client_id: &str,
+    id: &str,
+) -> Result<&'a mut T, ClientStoreError> {
+    children
+        .get_mut(&(client_id.to_string(), id.to_string()))
+        .filter(|child| child.deleted_at().is_none())
+        .ok_or_else(|| child_not_found::<T>(id))
+}
+
+fn update_child<T: Child>(
+    children: &mut Children<T>,
+    mut child: T,
+    expected_version: Option<u64>,
+) -> Result<T, ClientStoreError> {
+    let stored = live_child_mut(children, child.client_id(), child.id())?;
+
+    if let Some(expected) = expected_version
+        && expected != stored.version()
+    {
+        return Err(ClientStoreError::ChildVersionConflict {
+            resource: T::RESOURCE,
+            id: child.id().to_string(),
+            current: stored.version(),
+        });
+    }
+
+    child.set_state(stored.version() + 1, None);
+    *stored = child.clone();
+    Ok(child)
+}
+
+fn delete_child<T: Child>(children: &mut Children<T>, client_id: &str, id: &str) -> Result<T, ClientStoreError> {
+    let stored = live_child_mut(children, client_id, id)?;
+    stored.set_state(stored.version() + 1, Some(Utc::now()));
+    Ok(stored.clone())
+}
+
+// Move a client's children deleted at `from` (`None` for live ones) to `to`.
+// Children deleted on their own keep their own timestamp, so restoring the
+// client only brings back the ones deleted along with it.
+fn cascade<T: Child>(
+    children: &mut Children<T>,
+    client_id: &str,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) {
+    for child in children_of_mut(children, client_id).filter(|child| child.deleted_at() == from) {
+        child.set_state(child.version() + 1, to);
+    }
+}
+
+// Drop children of purged clients, and children deleted on their own before `cutoff`
+fn purge_children<T: Child>(
+    children: &mut Children<T>,
+    clients: &BTreeMap<String, Client>,
+    cutoff: DateTime<Utc>,
+) {
+    children.retain(|(client_id, _), child| {
+        clients.contains_key(client_id) && child.deleted_at().is_none_or(|deleted_at| deleted_at > cutoff)
+    });
+}
+
+// Leave `id` as the only primary contact of its client, returning the demoted ones
+fn demote_other_contacts(contacts: &mut Children<Contact>, client_id: &str, id: &str) -> Vec<Change<Contact>> {
+    children_of_mut(contacts, client_id)
+        .filter(|contact| contact.is_primary && contact.id != id && contact.deleted_at.is_none())
+        .map(|contact| {
+            let before = contact.clone();
+            contact.is_primary = false;
+            contact.version += 1;
+            Change {
+                before,
+                after: contact.clone(),
+            }
+        })
+        .collect()
+}
+
+/// A record before and after a change
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change<T> {
+    pub before: T,
+    pub after: T,
+}
+
+/// A [`ClientRepository`] that keeps everything in memory
+#[derive(Default)]
+pub struct InMemoryClientRepository {
+    clients: RwLock<BTreeMap<String, Client>>,
+    // Always locked after `clients`, and contacts before addresses
+    contacts: RwLock<Children<Contact>>,
+    addresses: RwLock<Children<Address>>,
+}
+
+impl InMemoryClientRepository {
+    /// A repository holding the example client served by the starter
+    pub fn with_example_data() -> Self {
+        let repository = Self::default();
+        repository
+            .insert(Client {
+                id: "1".to_string(),
+                name: "Example Client".to_string(),
+                created_at: Utc::now(),
+                version: 1,
+                deleted_at: None,
+                metadata: BTreeMap::from([(
+                    "industry".to_string(),
+                    "Software".to_string(),
+                )]),
+                tags: BTreeSet::new(),
+                custom_fields: BTreeMap::new(),
+            })
+            .expect("example client inserts");
+        repository
+    }
+}
+
+impl ClientRepository for InMemoryClientRepository {
+    fn list(&self, query: &ListQuery) -> Result<Page<Client>, ClientStoreError> {
+        let sort = query.sort;
+
+        let clients = self.clients.read().unwrap();
+        let mut matching: Vec<(String, Client)> = clients
+            .values()
+            .filter(|client| query.matches(client))
+            .map(|client| (sort.key(client), client.clone()))
+            .collect();
+        drop(clients);
+
+        // Ties on the sort key are broken by id so the order is stable
+        matching.sort_by(|(a_key, a), (b_key, b)| (a_key, &a.id).cmp(&(b_key, &b.id)));
+        if sort.descending {
+            matching.reverse();
+        }
+
+        paginate(
+            matching,
+            |client| &client.id,
+            &sort.to_string(),
+            sort.descending,
+            query.cursor.as_deref(),
+            query.limit,
+            query.include_total,
+        )
+    }
+
+    fn search(&self, query: &SearchQuery) -> Result<Page<SearchHit>, ClientStoreError> {
+        let terms = query.terms();
+        if terms.is_empty() {
+            return Err(ClientStoreError::InvalidQuery(
+                "Search query must contain at least one word".to_string(),
+            ));
+        }
+
+        let clients = self.clients.read().unwrap();
+        let mut hits: Vec<(String, SearchHit)> = clients
+            .values()
+            .filter(|client| query.include_deleted || client.deleted_at.is_none())
+            .filter_map(|client| search_client(client, &terms))
+            .map(|hit| (relevance_key(hit.score), hit))
+            .collect();
+        drop(clients);
+
+        hits.sort_by(|(a_key, a), (b_key, b)| {
+            (a_key, &a.client.id).cmp(&(b_key, &b.client.id))
+        });
+
+        // Cursors are only valid for the search they came from
+        paginate(
+            hits,
+            |hit| &hit.client.id,
+            &format!("relevance:{}", terms.join(" ")),
+            false,
+            query.cursor.as_deref(),
+            query.limit,
+            query.include_total,
+        )
+    }
+
+    fn get(&self, id: &str, include_deleted: bool) -> Result<Client, ClientStoreError> {
+        self.clients
+            .read()
+            .unwrap()
+            .get(id)
+            .filter(|client| include_deleted || client.deleted_at.is_none())
+            .cloned()
+            .ok_or_else(|| ClientStoreError::NotFound(id.to_string()))
+    }
+
+    fn insert(&self, client: Client) -> Result<Client, ClientStoreError> {
+        let mut clients = self.clients.write().unwrap();
+        if clients.contains_key(&client.id) {
+            return Err(ClientStoreError::AlreadyExists(client.id));
+        }
+        clients.insert(client.id.clone(), client.clone());
+        Ok(client)
+    }
+
+    fn insert_all(&self, new_clients: Vec<Client>) -> Result<Vec<Client>, ClientStoreError> {
+        // Check every id under the same lock as the writes, so nothing can
+        // claim one in between
+        let mut clients = self.clients.write().unwrap();
+        let mut ids = HashSet::new();
+        if let Some(taken) = new_clients
+            .iter()
+            .find(|client| clients.contains_key(&client.id) || !ids.insert(client.id.as_str()))
+        {
+            return Err(ClientStoreError::AlreadyExists(taken.id.clone()));
+        }
+        for client in &new_clients {
+            clients.insert(client.id.clone(), client.clone());
+        }
+        Ok(new_clients)
+    }
+
+    fn update(&self, client: Client, expected_version: Option<u64>) -> Result<Change<Client>, ClientStoreError> {
+        let mut clients = self.clients.write().unwrap();
+        let stored = live_mut(&mut clients, &client.id)?;
+        let before = stored.clone();
+
+        if let Some(expected) = expected_version
+            && expected != stored.version
+        {
+            return Err(ClientStoreError::VersionConflict {
+                id: client.id,
+                current: stored.version,
+            });
+        }
+
+        stored.name = client.name;
+        stored.metadata = client.metadata;
+        stored.tags = client.tags;
+        stored.custom_fields = client.custom_fields;
+        stored.version += 1;
+        Ok(Change {
+            before,
+            after: stored.clone(),
+        })
+    }
+
+    fn delete(&self, id: &str) -> Result<Change<Client>, ClientStoreError> {
+        let mut clients = self.clients.write().unwrap();
+        let stored = live_mut(&mut clients, id)?;
+        let before = stored.clone();
+        let deleted_at = Some(Utc::now());
+
+        stored.deleted_at = deleted_at;
+        stored.version += 1;
+        cascade(&mut self.contacts.write().unwrap(), id, None, deleted_at);
+        cascade(&mut self.addresses.write().unwrap(), id, None, deleted_at);
+        Ok(Change {
+            before,
+            after: stored.clone(),
+        })
+    }
+
+    fn restore(&self, id: &str) -> Result<Change<Client>, ClientStoreError> {
+        let mut clients = self.clients.write().unwrap();
+        let stored = clients
+            .get_mut(id)
+            .ok_or_else(|| ClientStoreError::NotFound(id.to_string()))?;
+        let Some(deleted_at) = stored.deleted_at else {
+            return Err(ClientStoreError::NotDeleted(id.to_string()));
+        };
+        let before = stored.clone();
+
+        stored.deleted_at = None;
+        stored.version += 1;
+        cascade(&mut self.contacts.write().unwrap(), id, Some(deleted_at), None);
+        cascade(&mut self.addresses.write().unwrap(), id, Some(deleted_at), None);
+        Ok(Change {
+            before,
+            after: stored.clone(),
+        })
+    }
+
+    fn purge_deleted_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<Client>, ClientStoreError> {
+        let mut clients = self.clients.write().unwrap();
+        let expired: Vec<String> = clients
+            .values()
+            .filter(|client| client.deleted_at.is_some_and(|deleted_at| deleted_at <= cutoff))
+            .map(|client| client.id.clone())
+            .collect();
+        let purged = expired.iter().filter_map(|id| clients.remove(id)).collect();
+        purge_children(&mut self.contacts.write().unwrap(), &clients, cutoff);
+        purge_children(&mut self.addresses.write().unwrap(), &clients, cutoff);
+        Ok(purged)
+    }
+
+    fn list_contacts(&self, client_id: &str) -> Result<Vec<Contact>, ClientStoreError> {
+        ensure_live(&self.clients.read().unwrap(), client_id)?;
+
+        let mut contacts = live_children(&self.contacts.read().unwrap(), client_id);
+        contacts.sort_by_key(|contact| !contact.is_primary);
+        Ok(contacts)
+    }
+
+    fn get_contact(&self, client_id: &str, id: &str) -> Result<Contact, ClientStoreError> {
+        ensure_live(&self.clients.read().unwrap(), client_id)?;
+        Ok(live_child(&self.contacts.read().unwrap(), client_id, id)?.clone())
+    }
+
+    fn insert_contact(&self, mut contact: Contact) -> Result<(Contact, Vec<Change<Contact>>), ClientStoreError> {
+        let clients = self.clients.read().unwrap();
+        ensure_live(&clients, &contact.client_id)?;
+
+        let mut contacts = self.contacts.write().unwrap();
+        let has_primary = children_of(&contacts, &contact.client_id)
+            .any(|other| other.is_primary && other.deleted_at.is_none());
+        contact.is_primary |= !has_primary;
+        let demoted = if contact.is_primary {
+            demote_other_contacts(&mut contacts, &contact.client_id, &contact.id)
+        } else {
+            Vec::new()
+        };
+
+        contacts.insert(child_key(&contact), contact.clone());
+        Ok((contact, demoted))
+    }
+
+    fn update_contact(
+        &self,
+        mut contact: Contact,
+        expected_version: Option<u64>,
+    ) -> Result<(Contact, Vec<Change<Contact>>), ClientStoreError> {
+        let clients = self.clients.read().unwrap();
+        ensure_live(&clients, &contact.client_id)?;
+
+        let mut contacts = self.contacts.write().unwrap();
+        contact.is_primary |= live_child(&contacts, &contact.client_id, &contact.id)?.is_primary;
+        let contact = update_child(&mut contacts, contact, expected_version)?;
+        let demoted = if contact.is_primary {
+            demote_other_contacts(&mut contacts, &contact.client_id, &contact.id)
+        } else {
+            Vec::new()
+        };
+        Ok((contact, demoted))
+    }
+
+    fn delete_contact(&self, client_id: &str, id: &str) -> Result<Contact, ClientStoreError> {
+        let clients = self.clients.read().unwrap();
+        ensure_live(&clients, client_id)?;
+        delete_child(&mut self.contacts.write().unwrap(), client_id, id)
+    }
+
+    fn list_addresses(&self, client_id: &str) -> Result<Vec<Address>, ClientStoreError> {
+        ensure_live(&self.clients.read().unwrap(), client_id)?;
+        Ok(live_children(&self.addresses.read().unwrap(), client_id))
+    }
+
+    fn get_address(&self, client_id: &str, id: &str) -> Result<Address, ClientStoreError> {
+        ensure_live(&self.clients.read().unwrap(), client_id)?;
+        Ok(live_child(&self.addresses.read().unwrap(), client_id, id)?.clone())
+    }
+
+    fn insert_address(&self, address: Address) -> Result<Address, ClientStoreError> {
+        let clients = self.clients.read().unwrap();
+        ensure_live(&clients, &address.client_id)?;
+
+        self.addresses
+            .write()
+            .unwrap()
+            .insert(child_key(&address), address.clone());
+        Ok(address)
+    }
+
+    fn update_address(&self, address: Address, expected_version: Option<u64>) -> Result<Address, ClientStoreError> {
+        let clients = self.clients.read().unwrap();
+        ensure_live(&clients, &address.client_id)?;
+        update_child(&mut self.addresses.write().unwrap(), address, expected_version)
+    }
+
+    fn delete_address(&self, client_id: &str, id: &str) -> Result<Address, ClientStoreError> {
+        let clients = self.clients.read().unwrap();
+        ensure_live(&clients, client_id)?;
+        delete_child(&mut self.addresses.write().unwrap(), client_id, id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use crate::clients::models::AddressKind;
+
+    fn repository() -> InMemoryClientRepository {
+        let repository = InMemoryClientRepository::default();
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+
+        for (index, name) in ["Acme", "Globex", "Initech", "Umbrella", "Acme Labs"].iter().enumerate() {
+            repository
+                .insert(Client {
+                    id: format!("{}", index + 1),
+                    name: name.to_string(),
+                    created_at: start + Duration::days(index as i64),
+                    version: 1,
+                    deleted_at: None,
+                    metadata: BTreeMap::new(),
+                    tags: BTreeSet::new(),
+                    custom_fields: BTreeMap::new(),
+                })
+                .unwrap();
+        }
+
+        repository
+    }
+
+    fn names(page: &Page<Client>) -> Vec<&str> {
+        page.items.iter().map(|client| client.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sort_parse() {
+        assert_eq!(
+            "-created_at".parse::<Sort>().unwrap(),
+            Sort { field: SortField::CreatedAt, descending: true }
+        );
+        assert_eq!("name".parse::<Sort>().unwrap().to_string(), "name");
+        assert!("email".parse::<Sort>().is_err());
+    }
+
+    #[test]
+    fn test_keyset_pagination() {
+        let repository = repository();
+        let mut query = ListQuery {
+            limit: 2,
+            sort: "name".parse().unwrap(),
+            include_total: true,
+            ..ListQuery::default()
+        };
+
+        let first = repository.list(&query).unwrap();
+        assert_eq!(names(&first), vec!["Acme", "Acme Labs"]);
+        assert_eq!(first.total, Some(5));
+
+        query.cursor = first.next_cursor;
+        let second = repository.list(&query).unwrap();
+        assert_eq!(names(&second), vec!["Globex", "Initech"]);
+
+        query.cursor = second.next_cursor;
+        let third = repository.list(&query).unwrap();
+        assert_eq!(names(&third), vec!["Umbrella"]);
+        assert_eq!(third.next_cursor, None);
+    }
+
+    #[test]
+    fn test_descending_sort() {
+        let repository = repository();
+        let mut query = ListQuery {
+            limit: 3,
+            sort: "-created_at".parse().unwrap(),
+            ..ListQuery::default()
+        };
+
+        let first = repository.list(&query).unwrap();
+        assert_eq!(names(&first), vec!["Acme Labs", "Umbrella", "Initech"]);
+        assert_eq!(first.total, None);
+
+        query.cursor = first.next_cursor;
+        let second = repository.list(&query).unwrap();
+        assert_eq!(names(&second), vec!["Globex", "Acme"]);
+    }
+
+    #[test]
+    fn test_name_filters() {
+        let repository = repository();
+        let query = ListQuery {
+            limit: 10,
+            name_prefix: Some("acme".to_string()),
+            ..ListQuery::default()
+        };
+        assert_eq!(names(&repository.list(&query).unwrap()), vec!["Acme", "Acme Labs"]);
+
+        let query = ListQuery {
+            limit: 10,
+            name_contains: Some("E".to_string()),
+            ..ListQuery::default()
+        };
+        assert_eq!(
+            names(&repository.list(&query).unwrap()),
+            vec!["Acme", "Globex", "Initech", "Umbrella", "Acme Labs"]
+        );
+    }
+
+    #[test]
+    fn test_search_ranks_and_paginates() {
+        let repository = repository();
+        let mut query = SearchQuery {
+            q: "acme".to_string(),
+            limit: 1,
+            include_total: true,
+            ..SearchQuery::default()
+        };
+
+        let first = repository.search(&query).unwrap();
+        assert_eq!(first.total, Some(2));
+        assert_eq!(first.items[0].client.name, "Acme");
+
+        query.cursor = first.next_cursor.clone();
+        let second = repository.search(&query).unwrap();
+        assert_eq!(second.items[0].client.name, "Acme Labs");
+        assert_eq!(second.next_cursor, None);
+
+        // A cursor from one search can't be used for another
+        query.q = "globex".to_string();
+        query.cursor = first.next_cursor;
+        assert!(repository.search(&query).is_err());
+
+        query.q = " ".to_string();
+        query.cursor = None;
+        assert!(repository.search(&query).is_err());
+    }
+
+    #[test]
+    fn test_insert_refuses_taken_ids() {
+        let repository = repository();
+        let client = |id: &str| Client {
+            id: id.to_string(),
+            name: format!("Client {}", id),
+            created_at: Utc::now(),
+            version: 1,
+            deleted_at: None,
+            metadata: BTreeMap::new(),
+            tags: BTreeSet::new(),
+            custom_fields: BTreeMap::new(),
+        };
+
+        assert_eq!(
+            repository.insert(client("1")).unwrap_err(),
+            ClientStoreError::AlreadyExists("1".to_string())
+        );
+        assert_eq!(repository.get("1", false).unwrap().name, "Acme");
+
+        // Deleted clients keep their id until they are purged
+        repository.delete("2").unwrap();
+        assert!(repository.insert(client("2")).is_err());
+
+        assert_eq!(
+            repository.insert_all(vec![client("6"), client("3")]).unwrap_err(),
+            ClientStoreError::AlreadyExists("3".to_string())
+        );
+        assert_eq!(
+            repository.insert_all(vec![client("6"), client("6")]).unwrap_err(),
+            ClientStoreError::AlreadyExists("6".to_string())
+        );
+        assert!(repository.get("6", true).is_err());
+
+        assert_eq!(repository.insert_all(vec![client("6"), client("7")]).unwrap().len(), 2);
+        assert_eq!(repository.get("7", false).unwrap().name, "Client 7");
+    }
+
+    #[test]
+    fn test_update_checks_version() {
+        let repository = repository();
+        let mut client = repository.get("2", false).unwrap();
+        client.name = "Globex Corporation".to_string();
+
+        let updated = repository.update(client.clone(), Some(1)).unwrap();
+        assert_eq!(updated.before.name, "Globex");
+        assert_eq!(updated.after.version, 2);
+        assert_eq!(repository.get("2", false).unwrap().name, "Globex Corporation");
+
+        assert_eq!(
+            repository.update(client.clone(), Some(1)).unwrap_err(),
+            ClientStoreError::VersionConflict { id: "2".to_string(), current: 2 }
+        );
+        assert_eq!(repository.update(client.clone(), None).unwrap().after.version, 3);
+
+        client.id = "missing".to_string();
+        assert_eq!(
+            repository.update(client, None).unwrap_err(),
+            ClientStoreError::NotFound("missing".to_string())
+        );
+    }
+
+    #[test]
+    fn test_soft_delete_and_restore() {
+        let repository = repository();
+
+        let deleted = repository.delete("2").unwrap();
+        assert_eq!(deleted.before.deleted_at, None);
+        assert!(deleted.after.deleted_at.is_some());
+        assert_eq!(deleted.after.version, 2);
+        let deleted = deleted.after;
+
+        // Deleted clients are hidden unless asked for
+        assert_eq!(
+            repository.get("2", false).unwrap_err(),
+            ClientStoreError::NotFound("2".to_string())
+        );
+        assert!(repository.get("2", true).is_ok());
+        let query = ListQuery { limit: 10, ..ListQuery::default() };
+        assert!(!names(&repository.list(&query).unwrap()).contains(&"Globex"));
+        let query = ListQuery { limit: 10, include_deleted: true, ..ListQuery::default() };
+        assert!(names(&repository.list(&query).unwrap()).contains(&"Globex"));
+        let search = SearchQuery { q: "globex".to_string(), limit: 10, ..SearchQuery::default() };
+        assert!(repository.search(&search).unwrap().items.is_empty());
+
+        // Deleted clients can't be changed until restored
+        assert!(repository.update(deleted.clone(), None).is_err());
+        assert!(repository.delete("2").is_err());
+
+        let restored = repository.restore("2").unwrap();
+        assert_eq!(restored.before, deleted);
+        assert_eq!(restored.after.deleted_at, None);
+        assert_eq!(restored.after.version, 3);
+        assert_eq!(
+            repository.restore("2").unwrap_err(),
+            ClientStoreError::NotDeleted("2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_purge_deleted_before() {
+        let repository = repository();
+        repository.delete("1").unwrap();
+        repository.delete("2").unwrap();
+
+        assert!(repository.purge_deleted_before(Utc::now() - Duration::hours(1)).unwrap().is_empty());
+        let purged = repository.purge_deleted_before(Utc::now() + Duration::seconds(1)).unwrap();
+        let ids: Vec<&str> = purged.iter().map(|client| client.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "2"]);
+
+        assert!(repository.get("1", true).is_err());
+        assert!(repository.restore("2").is_err());
+        assert!(repository.get("3", false).is_ok());
+    }
+
+    fn contact(client_id: &str, id: &str, is_primary: bool) -> Contact {
+        Contact {
+            id: id.to_string(),
+            client_id: client_id.to_string(),
+            name: format!("Contact {}", id),
+            email: None,
+            phone: None,
+            role: None,
+            is_primary,
+            created_at: Utc::now(),
+            version: 1,
+            deleted_at: None,
+        }
+    }
+
+    fn address(client_id: &str, id: &str) -> Address {
+        Address {
+            id: id.to_string(),
+            client_id: client_id.to_string(),
+            kind: AddressKind::Billing,
+            line1: "1 Main Street".to_string(),
+            line2: None,
+            city: "Berlin".to_string(),
+            region: None,
+            postal_code: None,
+            country: "DE".to_string(),
+            created_at: Utc::now(),
+            version: 1,
+            deleted_at: None,
+        }
+    }
+
+    fn primary_ids(repository: &InMemoryClientRepository, client_id: &str) -> Vec<String> {
+        repository
+            .list_contacts(client_id)
+            .unwrap()
+            .into_iter()
+            .filter(|contact| contact.is_primary)
+            .map(|contact| contact.id)
+            .collect()
+    }
+
+    #[test]
+    fn test_primary_contact() {
+        let repository = repository();
+
+        // The first contact becomes primary even when not asked to
+        assert!(repository.insert_contact(contact("1", "a", false)).unwrap().0.is_primary);
+        assert!(!repository.insert_contact(contact("1", "b", false)).unwrap().0.is_primary);
+        assert_eq!(primary_ids(&repository, "1"), vec!["a"]);
+
+        // The demoted contact is returned so the change can be audited
+        let (_, demoted) = repository.insert_contact(contact("1", "c", true)).unwrap();
+        assert_eq!(primary_ids(&repository, "1"), vec!["c"]);
+        assert_eq!(repository.get_contact("1", "a").unwrap().version, 2);
+        assert_eq!(demoted.len(), 1);
+        assert!(demoted[0].before.is_primary);
+        assert_eq!(demoted[0].after, repository.get_contact("1", "a").unwrap());
+
+        let b = repository.get_contact("1", "b").unwrap();
+        let (_, demoted) = repository
+            .update_contact(Contact { is_primary: true, ..b }, Some(1))
+            .unwrap();
+        assert_eq!(primary_ids(&repository, "1"), vec!["b"]);
+        assert_eq!(demoted[0].after.id, "c");
+
+        // Updating the primary contact without the flag keeps it primary
+        let b = repository.get_contact("1", "b").unwrap();
+        let (b, demoted) = repository.update_contact(Contact { is_primary: false, ..b }, None).unwrap();
+        assert!(b.is_primary);
+        assert!(demoted.is_empty());
+
+        // Primary first, then in id order
+        let ids: Vec<String> = repository
+            .list_contacts("1")
+            .unwrap()
+            .into_iter()
+            .map(|contact| contact.id)
+            .collect();
+        assert_eq!(ids, vec!["b", "a", "c"]);
+
+        // Contacts of other clients are unaffected
+        assert!(repository.insert_contact(contact("2", "d", false)).unwrap().0.is_primary);
+        assert_eq!(primary_ids(&repository, "1"), vec!["b"]);
+    }
+
+    #[test]
+    fn test_child_errors() {
+        let repository = repository();
+        repository.insert_address(address("1", "a")).unwrap();
+
+        assert_eq!(
+            repository.insert_address(address("missing", "b")).unwrap_err(),
+            ClientStoreError::NotFound("missing".to_string())
+        );
+        assert_eq!(
+            repository.get_address("2", "a").unwrap_err(),
+            ClientStoreError::ChildNotFound { resource: "Address", id: "a".to_string() }
+        );
+        assert_eq!(
+            repository.update_address(address("1", "a"), Some(5)).unwrap_err(),
+            ClientStoreError::ChildVersionConflict { resource: "Address", id: "a".to_string(), current: 1 }
+        );
+
+        repository.delete_address("1", "a").unwrap();
+        assert!(repository.get_address("1", "a").is_err());
+        assert!(repository.delete_address("1", "a").is_err());
+    }
+
+    #[test]
+    fn test_children_follow_client_delete_and_restore() {
+        let repository = repository();
+        repository.insert_contact(contact("1", "a", true)).unwrap();
+        repository.insert_contact(contact("1", "b", false)).unwrap();
+        repository.insert_address(address("1", "a")).unwrap();
+        repository.delete_contact("1", "b").unwrap();
+
+        repository.delete("1").unwrap();
+        assert_eq!(
+            repository.list_contacts("1").unwrap_err(),
+            ClientStoreError::NotFound("1".to_string())
+        );
+        assert!(repository.insert_contact(contact("1", "c", false)).is_err());
+
+        // Only the children deleted along with the client come back
+        repository.restore("1").unwrap();
+        let contacts = repository.list_contacts("1").unwrap();
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].id, "a");
+        assert_eq!(contacts[0].version, 3);
+        assert_eq!(repository.list_addresses("1").unwrap().len(), 1);
+
+        repository.delete("1").unwrap();
+        repository.purge_deleted_before(Utc::now() + Duration::seconds(1)).unwrap();
+        assert!(repository.contacts.read().unwrap().is_empty());
+        assert!(repository.addresses.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_cursor() {
+        let repository = repository();
+
+        let query = ListQuery {
+            limit: 10,
+            cursor: Some("not-a-cursor".to_string()),
+            ..ListQuery::default()
+        };
+        assert_eq!(
+            repository.list(&query).unwrap_err(),
+            ClientStoreError::InvalidQuery("Invalid cursor".to_string())
+        );
+
+        // A cursor is only valid for the sort it was issued with
+        let first = repository
+            .list(&ListQuery { limit: 1, ..ListQuery::default() })
+            .unwrap();
+        let query = ListQuery {
+            limit: 1,
+            cursor: first.next_cursor,
+            sort: "name".parse().unwrap(),
+            ..ListQuery::default()
+        };
+        assert!(repository.list(&query).is_err());
+    }
+}
@@ -1,66 +1,892 @@
 use axum::{
-    routing::get,
+    routing::{get, post},
     Router,
 };
 
-use super::handlers;
+use super::{addresses, contacts, custom_fields, handlers, stream};
+use crate::state::AppState;
 
-pub fn routes() -> Router {
+pub fn routes(state: AppState) -> Router {
     Router::new()
         .route("/clients", get(handlers::get_clients))
+        .with_state(state)
 }
 
-pub fn api_routes() -> Router {
+pub fn api_routes(state: AppState) -> Router {
     // This router doesn't include authentication yet - 
     // Authentication will be added in main.rs
     Router::new()
-        .route("/", get(handlers::get_secured_clients))
+        .route("/", get(handlers::list_clients).post(handlers::create_client))
+        .route("/search", get(handlers::search_clients))
+        .route("/import", post(handlers::import_clients))
+        .route("/export", get(handlers::export_clients))
+        .route("/events", get(stream::stream_events))
+        .route("/custom-fields", get(custom_fields::list_custom_fields))
+        .route(
+            "/custom-fields/:key",
+            get(custom_fields::get_custom_field)
+                .put(custom_fields::put_custom_field)
+                .delete(custom_fields::delete_custom_field),
+        )
+        .route(
+            "/:id",
+            get(handlers::get_client)
+                .put(handlers::update_client)
+                .patch(handlers::patch_client)
+                .delete(handlers::delete_client),
+        )
+        .route("/:id/restore", post(handlers::restore_client))
+        .route("/:id/history", get(handlers::get_client_history))
+        .route(
+            "/:id/contacts",
+            get(contacts::list_contacts).post(contacts::create_contact),
+        )
+        .route(
+            "/:id/contacts/:contact_id",
+            get(contacts::get_contact)
+                .put(contacts::update_contact)
+                .delete(contacts::delete_contact),
+        )
+        .route(
+            "/:id/addresses",
+            get(addresses::list_addresses).post(addresses::create_address),
+        )
+        .route(
+            "/:id/addresses/:address_id",
+            get(addresses::get_address)
+                .put(addresses::update_address)
+                .delete(addresses::delete_address),
+        )
+        .with_state(state)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::{BTreeMap, BTreeSet};
     use axum::{
         body::Body,
-        http::{Request, StatusCode},
+        http::{header, HeaderMap, Request, StatusCode},
     };
+    use serde_json::{json, Value};
+    use crate::{
+        audit::{models::AuditAction, store::AuditFilter},
+        auth,
+        config::AppConfig,
+    };
+    use axum::middleware;
+    use chrono::Utc;
+    use std::sync::Arc;
     use tower::util::ServiceExt;
-    use super::handlers::Client;
+    use super::handlers::{ClientListResponse, SearchResponse};
+    use crate::clients::{
+        models::Client,
+        repository::{ClientRepository, InMemoryClientRepository},
+    };
     use axum::body::to_bytes;
 
+    fn state_with_clients(count: usize) -> AppState {
+        let repository = InMemoryClientRepository::default();
+        for index in 0..count {
+            repository
+                .insert(Client {
+                    id: format!("{:02}", index),
+                    name: format!("Client {:02}", index),
+                    created_at: Utc::now(),
+                    version: 1,
+                    deleted_at: None,
+                    metadata: Default::default(),
+                    tags: BTreeSet::new(),
+                    custom_fields: BTreeMap::new(),
+                })
+                .unwrap();
+        }
+        AppState::default().with_clients(Arc::new(repository))
+    }
+
+    async fn get_page(app: Router, uri: &str) -> (StatusCode, Option<String>, Option<ClientListResponse>) {
+        let response = app
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let link = response
+            .headers()
+            .get(header::LINK)
+            .map(|value| value.to_str().unwrap().to_string());
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        (status, link, serde_json::from_slice(&body).ok())
+    }
+
     #[tokio::test]
     async fn test_clients_route() {
-        let app = routes();
+        // Only the fixed example, whatever is stored
+        let app = routes(state_with_clients(3));
 
         let response = app
             .oneshot(Request::builder().uri("/clients").body(Body::empty()).unwrap())
             .await
             .unwrap();
-
         assert_eq!(response.status(), StatusCode::OK);
 
         let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
-        let clients: Vec<Client> = serde_json::from_slice(&body).unwrap();
-        
+        let clients: Vec<handlers::ClientSummary> = serde_json::from_slice(&body).unwrap();
         assert_eq!(clients.len(), 1);
         assert_eq!(clients[0].id, "1");
         assert_eq!(clients[0].name, "Example Client");
     }
-    
+
     #[tokio::test]
     async fn test_api_clients_route() {
-        let app = api_routes();
+        let app = api_routes(AppState::default());
+
+        let (status, link, page) = get_page(app, "/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(link, None);
+
+        let clients = page.unwrap().data;
+        assert_eq!(clients.len(), 1);
+        assert_eq!(clients[0].id, "1");
+        assert_eq!(clients[0].name, "Example Client");
+    }
+
+    #[tokio::test]
+    async fn test_api_clients_route_follows_link_header() {
+        let app = api_routes(state_with_clients(5));
+
+        let (status, link, page) =
+            get_page(app.clone(), "/?limit=2&sort=-name&include_total=true").await;
+        assert_eq!(status, StatusCode::OK);
+        let page = page.unwrap();
+        assert_eq!(page.total, Some(5));
+        assert_eq!(page.data[0].name, "Client 04");
+
+        // The Link header keeps the original parameters and adds the cursor
+        let link = link.expect("Link header");
+        assert!(link.ends_with("; rel=\"next\""));
+        let next = link.trim_start_matches('<').split('>').next().unwrap();
+        assert!(next.starts_with("/?limit=2&cursor="));
+        assert!(next.ends_with("&sort=-name&include_total=true"));
+
+        let (_, _, second) = get_page(app, next).await;
+        let names: Vec<String> = second.unwrap().data.into_iter().map(|client| client.name).collect();
+        assert_eq!(names, vec!["Client 02", "Client 01"]);
+    }
+
+    #[tokio::test]
+    async fn test_api_clients_route_rejects_bad_cursor() {
+        let app = api_routes(AppState::default());
+
+        let (status, _, _) = get_page(app, "/?cursor=garbage").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_search_route() {
+        let app = api_routes(state_with_clients(3));
 
         let response = app
-            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/search?q=clent%2001")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
             .await
             .unwrap();
-
         assert_eq!(response.status(), StatusCode::OK);
 
         let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
-        let response_data: handlers::ClientResponse = serde_json::from_slice(&body).unwrap();
-        
-        assert_eq!(response_data.message, "Clients endpoint");
+        let results: SearchResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(results.data.len(), 1);
+        assert_eq!(results.data[0].client.name, "Client 01");
+        assert_eq!(results.data[0].highlights["name"], "<mark>Client</mark> <mark>01</mark>");
+        assert_eq!(results.next_cursor, None);
+
+        let response = app
+            .oneshot(Request::builder().uri("/search?q=").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    // The client API behind the auth middleware, as mounted in main.rs
+    fn secured(state: AppState) -> Router {
+        api_routes(state.clone()).layer(middleware::from_fn_with_state(state, auth::auth_middleware))
     }
-}
\ No newline at end of file
+
+    async fn send(app: &Router, mut request: Request<Body>) -> (StatusCode, HeaderMap, Value) {
+        request
+            .headers_mut()
+            .insert(header::AUTHORIZATION, auth::DEV_TOKEN.parse().unwrap());
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        (status, headers, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    fn put(uri: &str, if_match: Option<&str>, name: &str) -> Request<Body> {
+        let mut request = Request::builder()
+            .method("PUT")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(if_match) = if_match {
+            request = request.header(header::IF_MATCH, if_match);
+        }
+        request
+            .body(Body::from(json!({ "name": name }).to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_client_etags() {
+        let app = secured(AppState::default());
+
+        let (status, headers, _) = send(
+            &app,
+            Request::builder()
+                .method("POST")
+                .uri("/")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"name":"Acme"}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(headers[header::ETAG], "\"1\"");
+        let location = headers[header::LOCATION].to_str().unwrap();
+        let id = location.trim_start_matches("/api/clients/");
+        let uri = format!("/{}", id);
+
+        // Unchanged since the ETag we have
+        let (status, _, _) = send(
+            &app,
+            Request::builder()
+                .uri(&uri)
+                .header(header::IF_NONE_MATCH, "\"1\"")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+        let (status, _, _) = send(&app, put(&uri, None, "Acme Corp")).await;
+        assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+
+        let (status, headers, body) = send(&app, put(&uri, Some("\"1\""), "Acme Corp")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[header::ETAG], "\"2\"");
+        assert_eq!(body["name"], "Acme Corp");
+
+        // A second writer still holding version 1 must not overwrite the change
+        let (status, _, _) = send(&app, put(&uri, Some("\"1\""), "Acme Inc")).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+        let (status, headers, body) = send(
+            &app,
+            Request::builder()
+                .uri(&uri)
+                .header(header::IF_NONE_MATCH, "\"1\"")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[header::ETAG], "\"2\"");
+        assert_eq!(body["version"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_if_match_can_be_optional() {
+        let mut config = AppConfig::default();
+        config.clients.require_if_match = false;
+        let app = secured(AppState::new(config));
+
+        let (status, headers, _) = send(&app, put("/1", None, "Renamed")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[header::ETAG], "\"2\"");
+
+        let (status, _, _) = send(&app, put("/missing", None, "Renamed")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    fn patch(uri: &str, content_type: &str, body: Value) -> Request<Body> {
+        Request::builder()
+            .method("PATCH")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::IF_MATCH, "\"1\"")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_patch_client() {
+        let app = secured(AppState::default());
+
+        let (status, _, _) = send(&app, patch("/1", "application/json", json!({ "name": "Acme" }))).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let (status, _, body) = send(
+            &app,
+            patch(
+                "/1",
+                "application/json-patch+json",
+                json!([
+                    { "op": "add", "path": "/metadata/tier", "value": "gold" },
+                    { "op": "test", "path": "/name", "value": "Someone Else" }
+                ]),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["error"]["details"]["operation"], 1);
+
+        let (status, _, body) = send(
+            &app,
+            patch("/1", "application/merge-patch+json", json!({ "name": " " })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["message"], "name must not be empty");
+
+        let (status, headers, body) = send(
+            &app,
+            patch(
+                "/1",
+                "application/merge-patch+json",
+                json!({ "name": "Acme", "metadata": { "tier": "gold" } }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[header::ETAG], "\"2\"");
+        assert_eq!(body["name"], "Acme");
+        assert_eq!(body["metadata"], json!({ "industry": "Software", "tier": "gold" }));
+
+        // Still based on version 1
+        let (status, _, _) = send(
+            &app,
+            patch("/1", "application/merge-patch+json", json!({ "name": "Stale" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+    }
+
+    fn post_json(uri: &str, body: Value) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_contacts() {
+        let app = secured(AppState::default());
+
+        let (status, headers, first) = send(
+            &app,
+            post_json("/1/contacts", json!({ "name": "Ada", "email": "ada@example.com" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(first["is_primary"], true);
+        let location = headers[header::LOCATION].to_str().unwrap();
+        assert_eq!(location, format!("/api/clients/1/contacts/{}", first["id"].as_str().unwrap()));
+
+        let (status, _, body) = send(
+            &app,
+            post_json("/1/contacts", json!({ "name": "Bob", "email": "not-an-email" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["details"]["field"], "email");
+
+        let (_, _, second) = send(
+            &app,
+            post_json("/1/contacts", json!({ "name": "Bob", "phone": "+1 555 0100", "is_primary": true })),
+        )
+        .await;
+        assert_eq!(second["is_primary"], true);
+
+        let (status, _, body) = send(&app, request("GET", "/1/contacts")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["name"], "Bob");
+        assert_eq!(body["data"][1]["is_primary"], false);
+
+        let uri = format!("/1/contacts/{}", first["id"].as_str().unwrap());
+        let (status, headers, body) = send(
+            &app,
+            Request::builder()
+                .method("PUT")
+                .uri(&uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::IF_MATCH, "\"2\"")
+                .body(Body::from(json!({ "name": "Ada Lovelace", "role": "CTO" }).to_string()))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[header::ETAG], "\"3\"");
+        assert_eq!(body["role"], "CTO");
+
+        let (status, _, _) = send(&app, request("DELETE", &uri)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _, body) = send(&app, request("GET", &uri)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body["error"]["message"].as_str().unwrap().starts_with("Contact "));
+
+        let (status, _, _) = send(&app, request("GET", "/missing/contacts")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_primary_contact_changes() {
+        let state = AppState::default();
+        let app = secured(state.clone());
+
+        let (_, _, first) = send(&app, post_json("/1/contacts", json!({ "name": "Ada" }))).await;
+        let (_, _, second) = send(&app, post_json("/1/contacts", json!({ "name": "Bob", "is_primary": true }))).await;
+        let first_id = first["id"].as_str().unwrap();
+
+        // Losing the designation is recorded as an update of the demoted contact
+        let demotions = state
+            .audit
+            .query(&AuditFilter {
+                entity_id: Some(first_id.to_string()),
+                action: Some(AuditAction::Update),
+                limit: 10,
+                ..AuditFilter::default()
+            })
+            .unwrap()
+            .events;
+        assert_eq!(demotions.len(), 1);
+        assert_eq!(demotions[0].entity_type, "contact");
+        assert_eq!(demotions[0].changes[0].path, "/is_primary");
+
+        // Leaving the flag out keeps the primary contact primary
+        let put = |version: &str, body: Value| {
+            Request::builder()
+                .method("PUT")
+                .uri(format!("/1/contacts/{}", second["id"].as_str().unwrap()))
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::IF_MATCH, format!("\"{}\"", version))
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let (status, _, body) = send(&app, put("1", json!({ "name": "Bob Smith" }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["is_primary"], true);
+
+        let (status, _, _) = send(&app, put("2", json!({ "name": "Bob", "is_primary": false }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_addresses_follow_client_deletion() {
+        let app = secured(AppState::default());
+
+        let (status, _, body) = send(
+            &app,
+            post_json("/1/addresses", json!({ "line1": "1 Main St", "city": "Berlin", "country": "Germany" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["details"]["field"], "country");
+
+        let (status, _, address) = send(
+            &app,
+            post_json(
+                "/1/addresses",
+                json!({ "kind": "billing", "line1": "1 Main St", "city": "Berlin", "country": "de" }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(address["country"], "DE");
+        let uri = format!("/1/addresses/{}", address["id"].as_str().unwrap());
+
+        send(&app, request("DELETE", "/1")).await;
+        let (status, _, _) = send(&app, request("GET", &uri)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        send(&app, request("POST", "/1/restore")).await;
+        let (status, _, body) = send(&app, request("GET", &uri)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["kind"], "billing");
+        assert!(body.get("deleted_at").is_none());
+    }
+
+    fn put_json(uri: &str, body: Value) -> Request<Body> {
+        Request::builder()
+            .method("PUT")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_tags_and_custom_fields() {
+        let state = AppState::default();
+        let app = secured(state.clone());
+
+        let (status, _, _) = send(
+            &app,
+            put_json("/custom-fields/tier", json!({ "type": "enum", "options": ["gold", "silver"] })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, _, body) = send(
+            &app,
+            post_json("/", json!({ "name": "Acme", "custom_fields": { "tier": "bronze" } })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["details"]["field"], "custom_fields.tier");
+
+        let (status, _, client) = send(
+            &app,
+            post_json(
+                "/",
+                json!({ "name": "Acme", "tags": ["VIP", "region:emea"], "custom_fields": { "tier": "gold" } }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(client["tags"], json!(["region:emea", "vip"]));
+
+        let (status, _, page) = get_page(api_routes(state), "/?tag=vip&custom_field=tier:gold").await;
+        assert_eq!(status, StatusCode::OK);
+        let page = page.unwrap();
+        assert_eq!(page.data.len(), 1);
+        assert_eq!(page.data[0].id, client["id"].as_str().unwrap());
+
+        // Definitions with values can't be removed or narrowed
+        let (status, _, _) = send(&app, request("DELETE", "/custom-fields/tier")).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (status, _, _) = send(
+            &app,
+            put_json("/custom-fields/tier", json!({ "type": "enum", "options": ["silver"] })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, _, body) = send(&app, request("GET", "/custom-fields")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["key"], "tier");
+    }
+
+    fn request(method: &str, uri: &str) -> Request<Body> {
+        Request::builder().method(method).uri(uri).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_delete_and_restore() {
+        let app = secured(AppState::default());
+
+        let (status, _, _) = send(&app, request("DELETE", "/1")).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (status, _, _) = send(&app, request("GET", "/1")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _, body) = send(&app, request("GET", "/1?include_deleted=true")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["deleted_at"].is_string());
+
+        let (_, _, page) = send(&app, request("GET", "/")).await;
+        assert_eq!(page["data"], json!([]));
+        let (_, _, page) = send(&app, request("GET", "/?include_deleted=true")).await;
+        assert_eq!(page["data"].as_array().unwrap().len(), 1);
+
+        let (status, headers, body) = send(&app, request("POST", "/1/restore")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[header::ETAG], "\"3\"");
+        assert!(body.get("deleted_at").is_none());
+
+        let (status, _, _) = send(&app, request("POST", "/1/restore")).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (_, _, page) = send(&app, request("GET", "/")).await;
+        assert_eq!(page["data"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_deleted_clients_hidden_from_public_route() {
+        let state = AppState::default();
+        let app = secured(state.clone());
+        let public = routes(state);
+
+        let (_, _, client) = send(&app, post_json("/", json!({ "name": "Deleted Corp" }))).await;
+        let id = client["id"].as_str().unwrap().to_string();
+        let (status, _, _) = send(&app, request("DELETE", &format!("/{}", id))).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        // Only authenticated callers can ask for deleted clients
+        let (_, _, page) = send(&app, request("GET", "/?include_deleted=true")).await;
+        assert!(page["data"].as_array().unwrap().iter().any(|client| client["id"] == id.as_str()));
+
+        for uri in ["/clients", "/clients?include_deleted=true"] {
+            let response = public.clone().oneshot(request("GET", uri)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let clients: Vec<handlers::ClientSummary> = serde_json::from_slice(&body).unwrap();
+            assert!(clients.iter().all(|client| client.id != id && client.name != "Deleted Corp"), "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_changes_are_audited() {
+        // Events carry the ID of the request that made the change
+        let app = secured(AppState::default()).layer(middleware::from_fn(crate::request_id::request_id_middleware));
+
+        let mut rename = put("/1", Some("\"1\""), "Renamed Client");
+        rename.headers_mut().insert("x-request-id", "rename-1".parse().unwrap());
+        let (status, _, _) = send(&app, rename).await;
+        assert_eq!(status, StatusCode::OK);
+        send(&app, request("DELETE", "/1")).await;
+
+        let (status, _, body) = send(
+            &app,
+            Request::builder()
+                .uri("/1/history")
+                .header("x-request-id", "history-test")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let events = body["data"].as_array().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["action"], "update");
+        assert_eq!(events[0]["actor"], auth::DEV_SUBJECT);
+        assert_eq!(events[0]["request_id"], "rename-1");
+        assert_eq!(events[0]["entity_type"], "client");
+        assert!(events[0]["changes"]
+            .as_array()
+            .unwrap()
+            .contains(&json!({ "path": "/name", "before": "Example Client", "after": "Renamed Client" })));
+        assert_eq!(events[1]["action"], "delete");
+        // Generated when the request didn't send one
+        assert!(events[1]["request_id"].as_str().is_some_and(|id| !id.is_empty()));
+        assert!(events[1]["changes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|change| change["path"] == "/deleted_at" && change["before"].is_null()));
+
+        let (status, _, _) = send(&app, request("GET", "/unknown/history")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    // An audit log that is down
+    struct FailingAuditStore;
+
+    impl crate::audit::store::AuditStore for FailingAuditStore {
+        fn append(
+            &self,
+            _event: crate::audit::models::AuditEvent,
+        ) -> anyhow::Result<crate::audit::models::AuditEvent> {
+            anyhow::bail!("audit log unavailable")
+        }
+
+        fn query(&self, _filter: &AuditFilter) -> anyhow::Result<crate::audit::store::AuditPage> {
+            anyhow::bail!("audit log unavailable")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stored_changes_succeed_when_audit_fails() {
+        let state = AppState {
+            audit: std::sync::Arc::new(FailingAuditStore),
+            ..AppState::default()
+        };
+        let app = secured(state.clone());
+        let mut published = state.events.subscribe(None).receiver;
+
+        // A 500 here would have a retry create the client a second time
+        let (status, _, created) = send(&app, post_json("/", json!({ "name": "Acme" }))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let id = created["id"].as_str().unwrap();
+        assert_eq!(published.try_recv().unwrap().event.data.id, id);
+
+        let (status, _, _) = send(&app, request("DELETE", &format!("/{}", id))).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+
+    fn import(query: &str, content_type: &str, body: &'static str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/import{}", query))
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_import_and_export() {
+        let app = secured(AppState::default());
+
+        let csv = "id,name,metadata\n10,Acme,\"{\"\"tier\"\":\"\"gold\"\"}\"\n11,Globex,\n";
+        let (status, _, report) = send(&app, import("?dry_run=true", "text/csv", csv)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["rows"][0]["status"], "valid");
+        assert_eq!(report["committed"], false);
+
+        let (status, _, report) = send(&app, import("", "text/csv", csv)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["succeeded"], 2);
+        assert_eq!(report["rows"][1]["status"], "created");
+
+        // Importing the same ids again fails every row, so nothing is written
+        let (status, _, report) = send(&app, import("", "text/csv", csv)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(report["failed"], 2);
+
+        let ndjson = "{\"name\":\"Initech\"}\n{\"name\":\"\"}\n";
+        let (status, _, report) =
+            send(&app, import("?mode=best_effort", "application/x-ndjson", ndjson)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["rows"][0]["status"], "created");
+        assert_eq!(report["rows"][1]["error"], "name must not be empty");
+
+        let mut request = request("GET", "/export?format=ndjson");
+        request
+            .headers_mut()
+            .insert(header::AUTHORIZATION, auth::DEV_TOKEN.parse().unwrap());
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let names: Vec<String> = body
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice::<Client>(line).unwrap().name)
+            .collect();
+        assert_eq!(names, vec!["Example Client", "Acme", "Globex", "Initech"]);
+    }
+
+    #[tokio::test]
+    async fn test_import_requires_known_format() {
+        let app = secured(AppState::default());
+
+        let (status, _, _) = send(&app, import("", "application/json", "[]")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _, _) = send(&app, import("", "text/csv", "id,title\n1,Acme\n")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_imported_rows_are_audited_and_published() {
+        let state = AppState::default();
+        let app = secured(state.clone());
+        let mut published = state.events.subscribe(None).receiver;
+
+        // The body breaks after the first row, which best effort has already written
+        let chunks: Vec<Result<&'static str, std::io::Error>> = vec![
+            Ok("{\"id\":\"20\",\"name\":\"Acme\"}\n"),
+            Err(std::io::Error::other("connection reset")),
+        ];
+        let upload = Request::builder()
+            .method("POST")
+            .uri("/import?mode=best_effort")
+            .header(header::CONTENT_TYPE, "application/x-ndjson")
+            .body(Body::from_stream(futures_util::stream::iter(chunks)))
+            .unwrap();
+        let (status, _, _) = send(&app, upload).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (_, _, history) = send(&app, request("GET", "/20/history")).await;
+        assert_eq!(history["data"][0]["action"], "create");
+
+        let created = published.try_recv().unwrap();
+        assert_eq!(created.event.event_type, crate::events::ClientEventType::Created);
+        assert_eq!(created.event.data.id, "20");
+        assert!(published.try_recv().is_err());
+    }
+
+    // Read SSE frames until `count` have arrived, skipping keep-alive comments
+    async fn sse_frames(body: &mut axum::body::BodyDataStream, count: usize) -> Vec<String> {
+        use futures_util::StreamExt;
+
+        let mut text = String::new();
+        loop {
+            let frames: Vec<String> = text
+                .split("\n\n")
+                .filter(|frame| !frame.is_empty() && !frame.starts_with(':'))
+                .map(str::to_string)
+                .collect();
+            if text.ends_with("\n\n") && frames.len() >= count {
+                return frames;
+            }
+            let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), body.next())
+                .await
+                .expect("SSE frame")
+                .unwrap()
+                .unwrap();
+            text.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+
+    fn events_request(uri: &str, last_event_id: Option<&str>) -> Request<Body> {
+        let mut request = Request::builder()
+            .uri(uri)
+            .header(header::AUTHORIZATION, auth::DEV_TOKEN);
+        if let Some(last_event_id) = last_event_id {
+            request = request.header("last-event-id", last_event_id);
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_event_stream() {
+        let app = secured(AppState::default());
+
+        let response = app
+            .clone()
+            .oneshot(events_request("/events?types=client.deleted", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/event-stream");
+        let mut live = response.into_body().into_data_stream();
+
+        send(&app, post_json("/", json!({ "name": "Acme" }))).await;
+        send(&app, request("DELETE", "/1")).await;
+
+        // Only the subscribed type arrives, numbered after the create
+        let frames = sse_frames(&mut live, 1).await;
+        let id = frames[0].lines().next().unwrap().strip_prefix("id: ").unwrap();
+        let epoch = id.strip_suffix("-2").unwrap();
+        assert!(frames[0].contains("event: client.deleted\n"));
+        assert!(frames[0].contains("\"id\":\"1\""));
+
+        // Resuming after the first event replays the rest
+        let first = format!("{}-1", epoch);
+        let response = app.clone().oneshot(events_request("/events", Some(&first))).await.unwrap();
+        let frames = sse_frames(&mut response.into_body().into_data_stream(), 1).await;
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].starts_with(&format!("id: {}\n", id)));
+
+        // Ids this stream never issued, or issued before a restart, can't be resumed from
+        for unknown in [format!("{}-99", epoch), "0192abc-1".to_string(), "1".to_string()] {
+            let response = app.clone().oneshot(events_request("/events", Some(&unknown))).await.unwrap();
+            let frames = sse_frames(&mut response.into_body().into_data_stream(), 1).await;
+            assert!(frames[0].starts_with("event: resync\n"));
+        }
+
+        let response = app.clone().oneshot(events_request("/events?types=client.renamed", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = app.oneshot(events_request("/events", Some("abc"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::models::Client;
+
+const MARK_START: &str = "<mark>";
+const MARK_END: &str = "</mark>";
+
+// Weights for how well a query term matches a word
+const EXACT_SCORE: u32 = 10;
+const PREFIX_SCORE: u32 = 6;
+const FUZZY_SCORE: u32 = 3;
+// Matches in the name count for more than matches in metadata
+const NAME_WEIGHT: u32 = 2;
+
+/// Full-text search over client names and metadata values
+#[derive(Debug, Clone, Default)]
+pub struct SearchQuery {
+    pub q: String,
+    pub limit: usize,
+    pub cursor: Option<String>,
+    pub include_total: bool,
+    /// Also return soft-deleted clients
+    pub include_deleted: bool,
+}
+
+impl SearchQuery {
+    /// Lowercased words of the query, in order and without duplicates
+    pub fn terms(&self) -> Vec<String> {
+        let mut terms: Vec<String> = Vec::new();
+        for (_, word) in words(&self.q) {
+            let word = word.to_lowercase();
+            if !terms.contains(&word) {
+                terms.push(word);
+            }
+        }
+        terms
+    }
+}
+
+/// A client matching a search, with highlighted snippets
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct SearchHit {
+    pub client: Client,
+    /// Relevance, higher is better
+    pub score: u32,
+    /// Matching fields (`name` or `metadata.<key>`) with matches wrapped in `<mark>`
+    pub highlights: BTreeMap<String, String>,
+}
+
+/// Score a client against the query terms; every term has to match somewhere
+pub fn search_client(client: &Client, terms: &[String]) -> Option<SearchHit> {
+    if terms.is_empty() {
+        return None;
+    }
+
+    let mut fields = vec![("name".to_string(), client.name.as_str(), NAME_WEIGHT)];
+    fields.extend(
+        client
+            .metadata
+            .iter()
+            .map(|(key, value)| (format!("metadata.{}", key), value.as_str(), 1)),
+    );
+
+    let mut score = 0;
+    let mut matched_terms = vec![false; terms.len()];
+    let mut highlights = BTreeMap::new();
+
+    for (field, text, weight) in fields {
+        let mut spans = Vec::new();
+
+        for (offset, original) in words(text) {
+            let word = original.to_lowercase();
+            let best = terms
+                .iter()
+                .enumerate()
+                .filter_map(|(index, term)| term_score(term, &word).map(|score| (index, score)))
+                .max_by_key(|(_, score)| *score);
+
+            if let Some((index, term_score)) = best {
+                matched_terms[index] = true;
+                score += term_score * weight;
+                spans.push((offset, offset + original.len()));
+            }
+        }
+
+        if !spans.is_empty() {
+            highlights.insert(field, highlight(text, &spans));
+        }
+    }
+
+    if !matched_terms.iter().all(|matched| *matched) {
+        return None;
+    }
+
+    Some(SearchHit {
+        client: client.clone(),
+        score,
+        highlights,
+    })
+}
+
+// How well a query term matches a word, if at all
+fn term_score(term: &str, word: &str) -> Option<u32> {
+    if word == term {
+        return Some(EXACT_SCORE);
+    }
+    if word.starts_with(term) {
+        return Some(PREFIX_SCORE);
+    }
+
+    // Allow one typo in short words and two in longer ones
+    let allowed = match term.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    };
+    if allowed > 0 && edit_distance(term, word) <= allowed {
+        return Some(FUZZY_SCORE);
+    }
+
+    None
+}
+
+// Levenshtein distance between two words
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+// Alphanumeric words of `text` with their byte offsets
+fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(move |word| (word.as_ptr() as usize - text.as_ptr() as usize, word))
+}
+
+// Wrap the given byte ranges in `<mark>` tags, escaping everything else as HTML
+fn highlight(text: &str, spans: &[(usize, usize)]) -> String {
+    let mut snippet = String::with_capacity(text.len() + spans.len() * 13);
+    let mut position = 0;
+
+    for (start, end) in spans {
+        snippet.push_str(&escape_html(&text[position..*start]));
+        snippet.push_str(MARK_START);
+        snippet.push_str(&escape_html(&text[*start..*end]));
+        snippet.push_str(MARK_END);
+        position = *end;
+    }
+    snippet.push_str(&escape_html(&text[position..]));
+
+    snippet
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use chrono::Utc;
+
+    fn client(name: &str, metadata: &[(&str, &str)]) -> Client {
+        Client {
+            id: "1".to_string(),
+            name: name.to_string(),
+            created_at: Utc::now(),
+            version: 1,
+            deleted_at: None,
+            metadata: metadata
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            tags: BTreeSet::new(),
+            custom_fields: BTreeMap::new(),
+        }
+    }
+
+    fn terms(q: &str) -> Vec<String> {
+        SearchQuery {
+            q: q.to_string(),
+            ..SearchQuery::default()
+        }
+        .terms()
+    }
+
+    #[test]
+    fn test_terms() {
+        assert_eq!(terms("Acme, acme  LABS"), vec!["acme", "labs"]);
+        assert!(terms(" -- ").is_empty());
+    }
+
+    #[test]
+    fn test_highlights_matches() {
+        let hit = search_client(
+            &client("Acme Labs", &[("industry", "Lab equipment")]),
+            &terms("labs"),
+        )
+        .unwrap();
+
+        assert_eq!(hit.highlights["name"], "Acme <mark>Labs</mark>");
+        assert_eq!(hit.highlights["metadata.industry"], "<mark>Lab</mark> equipment");
+        assert_eq!(hit.score, EXACT_SCORE * NAME_WEIGHT + FUZZY_SCORE);
+    }
+
+    #[test]
+    fn test_fuzzy_and_prefix_matches() {
+        let acme = client("Acme Corporation", &[]);
+
+        assert!(search_client(&acme, &terms("corp")).is_some());
+        assert!(search_client(&acme, &terms("corporatoin")).is_some());
+        assert!(search_client(&acme, &terms("acne")).is_some());
+        assert!(search_client(&acme, &terms("umbrella")).is_none());
+    }
+
+    #[test]
+    fn test_all_terms_must_match() {
+        let acme = client("Acme", &[("city", "Berlin")]);
+
+        assert!(search_client(&acme, &terms("acme berlin")).is_some());
+        assert!(search_client(&acme, &terms("acme paris")).is_none());
+    }
+
+    #[test]
+    fn test_snippets_are_escaped() {
+        let hit = search_client(&client("<b>Acme</b> & Co", &[]), &terms("acme")).unwrap();
+
+        assert_eq!(hit.highlights["name"], "&lt;b&gt;<mark>Acme</mark>&lt;/b&gt; &amp; Co");
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("acme", "acme"), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -0,0 +1,125 @@
+use std::{collections::BTreeSet, convert::Infallible, time::Duration};
+
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::{stream, Stream, StreamExt};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use utoipa::IntoParams;
+
+use crate::{
+    errors::AppError,
+    events::{self, ClientEventType, EventId, Sequenced, Subscription},
+    state::AppState,
+};
+
+const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+// Sent when the stream can't account for every event since the subscriber's last one
+const RESYNC_EVENT: &str = "resync";
+
+/// Query parameters for the client event stream
+#[derive(Deserialize, Debug, Default, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventStreamParams {
+    /// Comma-separated event types to receive, e.g. `client.created,client.deleted`;
+    /// all types by default
+    pub types: Option<String>,
+}
+
+/// Stream client changes
+///
+/// A Server-Sent Events stream of `client.created`, `client.updated` and
+/// `client.deleted` events. Each event's `id` can be sent back as
+/// `Last-Event-ID` when reconnecting to replay what was missed. If that isn't
+/// possible, for example because the server restarted since, a `resync` event
+/// tells the subscriber to reload its data.
+#[utoipa::path(
+    get,
+    path = "/api/clients/events",
+    tag = "clients",
+    params(
+        EventStreamParams,
+        ("Last-Event-ID" = Option<String>, Header, description = "Id of the last event received, to resume after it")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Stream of client events", body = String, content_type = "text/event-stream"),
+        (status = 400, description = "Unknown event type or invalid `Last-Event-ID`"),
+        (status = 401, description = "Unauthorized - Missing or invalid token")
+    )
+)]
+pub async fn stream_events(
+    State(state): State<AppState>,
+    Query(params): Query<EventStreamParams>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let types = events::parse_types(params.types.as_deref().unwrap_or_default())
+        .map_err(AppError::bad_request)?;
+    let last_event_id = headers
+        .get(LAST_EVENT_ID_HEADER)
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse::<EventId>().ok())
+                .ok_or_else(|| AppError::bad_request("Last-Event-ID must be an event id from this stream"))
+        })
+        .transpose()?;
+
+    let Subscription {
+        replay,
+        truncated,
+        receiver,
+    } = state.events.subscribe(last_event_id.as_ref());
+
+    let replayed = truncated
+        .then(|| resync("replay_unavailable"))
+        .into_iter()
+        .chain(
+            replay
+                .into_iter()
+                .filter(|event| wanted(&types, event))
+                .map(|event| to_sse(&event)),
+        )
+        .map(Ok)
+        .collect::<Vec<_>>();
+
+    let live = stream::unfold((receiver, types), |(mut receiver, types)| async move {
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) if wanted(&types, &event) => to_sse(&event),
+                Ok(_) => continue,
+                // This subscriber fell too far behind and missed events
+                Err(RecvError::Lagged(_)) => resync("subscriber_lagged"),
+                Err(RecvError::Closed) => return None,
+            };
+            return Some((Ok(event), (receiver, types)));
+        }
+    });
+
+    let keep_alive = Duration::from_secs(state.config.events.keep_alive_seconds.max(1));
+    Ok(Sse::new(stream::iter(replayed).chain(live)).keep_alive(KeepAlive::new().interval(keep_alive)))
+}
+
+fn wanted(types: &BTreeSet<ClientEventType>, event: &Sequenced) -> bool {
+    types.is_empty() || types.contains(&event.event.event_type)
+}
+
+fn to_sse(event: &Sequenced) -> Event {
+    Event::default()
+        .id(event.id.as_str())
+        .event(event.event.event_type.as_str())
+        .data(serde_json::to_string(&event.event).unwrap_or_default())
+}
+
+fn resync(reason: &str) -> Event {
+    Event::default()
+        .event(RESYNC_EVENT)
+        .data(json!({ "reason": reason }).to_string())
+}
@@ -0,0 +1,170 @@
+use std::collections::BTreeSet;
+
+use serde_json::json;
+
+use crate::errors::AppError;
+
+// Longest free-text field we accept
+const MAX_TEXT_LEN: usize = 200;
+const MAX_EMAIL_LEN: usize = 254;
+const MIN_PHONE_DIGITS: usize = 5;
+const MAX_PHONE_DIGITS: usize = 20;
+const MAX_TAGS: usize = 20;
+const MAX_TAG_LEN: usize = 50;
+
+/// 400 naming the offending field in `error.details.field`
+pub fn invalid(field: &str, message: impl Into<String>) -> AppError {
+    AppError::bad_request(message).with_details(json!({ "field": field }))
+}
+
+/// Trimmed value of a required text field
+pub fn required(field: &str, value: &str) -> Result<String, AppError> {
+    optional(field, Some(value))?.ok_or_else(|| invalid(field, format!("{} must not be empty", field)))
+}
+
+/// Trimmed value of an optional text field, `None` when blank
+pub fn optional(field: &str, value: Option<&str>) -> Result<Option<String>, AppError> {
+    let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) else {
+        return Ok(None);
+    };
+    if value.chars().count() > MAX_TEXT_LEN {
+        return Err(invalid(
+            field,
+            format!("{} must be at most {} characters", field, MAX_TEXT_LEN),
+        ));
+    }
+    Ok(Some(value.to_string()))
+}
+
+/// An optional email address, checked for the `local@domain.tld` shape
+pub fn email(field: &str, value: Option<&str>) -> Result<Option<String>, AppError> {
+    let Some(value) = optional(field, value)? else {
+        return Ok(None);
+    };
+
+    let valid = value.len() <= MAX_EMAIL_LEN
+        && !value.chars().any(char::is_whitespace)
+        && value.split_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.split('.').count() > 1
+                && domain.split('.').all(|label| !label.is_empty())
+        });
+    if !valid {
+        return Err(invalid(field, format!("{} must be a valid email address", field)));
+    }
+    Ok(Some(value))
+}
+
+/// An optional phone number: digits with an optional leading `+` and common separators
+pub fn phone(field: &str, value: Option<&str>) -> Result<Option<String>, AppError> {
+    let Some(value) = optional(field, value)? else {
+        return Ok(None);
+    };
+
+    let digits = value.chars().filter(char::is_ascii_digit).count();
+    let valid = (MIN_PHONE_DIGITS..=MAX_PHONE_DIGITS).contains(&digits)
+        && value
+            .trim_start_matches('+')
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, ' ' | '-' | '.' | '(' | ')'));
+    if !valid {
+        return Err(invalid(field, format!("{} must be a valid phone number", field)));
+    }
+    Ok(Some(value))
+}
+
+/// An ISO 3166-1 alpha-2 country code, uppercased
+pub fn country_code(field: &str, value: &str) -> Result<String, AppError> {
+    let value = required(field, value)?;
+    if value.len() != 2 || !value.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(invalid(
+            field,
+            format!("{} must be a two-letter ISO 3166-1 country code", field),
+        ));
+    }
+    Ok(value.to_ascii_uppercase())
+}
+
+/// Tags trimmed and lowercased; letters, digits, `-`, `_` and `:` only
+pub fn tags(field: &str, tags: &BTreeSet<String>) -> Result<BTreeSet<String>, AppError> {
+    let tags: BTreeSet<String> = tags.iter().map(|tag| tag.trim().to_lowercase()).collect();
+
+    if tags.len() > MAX_TAGS {
+        return Err(invalid(field, format!("At most {} tags are allowed", MAX_TAGS)));
+    }
+    if let Some(tag) = tags.iter().find(|tag| {
+        tag.is_empty()
+            || tag.chars().count() > MAX_TAG_LEN
+            || !tag.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ':'))
+    }) {
+        return Err(invalid(
+            field,
+            format!(
+                "Invalid tag {:?}: use 1 to {} letters, digits, '-', '_' or ':'",
+                tag, MAX_TAG_LEN
+            ),
+        ));
+    }
+
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    #[test]
+    fn test_text_fields() {
+        assert_eq!(required("name", "  Acme ").unwrap(), "Acme");
+        assert_eq!(optional("role", Some(" ")).unwrap(), None);
+
+        let err = required("name", " ").unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+        assert_eq!(err.message, "name must not be empty");
+        assert_eq!(err.details.unwrap()["field"], "name");
+
+        assert!(optional("role", Some(&"x".repeat(MAX_TEXT_LEN + 1))).is_err());
+    }
+
+    #[test]
+    fn test_email() {
+        assert_eq!(email("email", Some("ada@example.com")).unwrap().unwrap(), "ada@example.com");
+        assert_eq!(email("email", None).unwrap(), None);
+
+        for bad in ["ada", "ada@example", "@example.com", "ada@@example.com", "ada @example.com", "ada@example."] {
+            assert!(email("email", Some(bad)).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_phone() {
+        assert!(phone("phone", Some("+49 (30) 123-456")).is_ok());
+
+        for bad in ["123", "call me", "49+30123456", &"1".repeat(21)] {
+            assert!(phone("phone", Some(bad)).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_tags() {
+        let input = BTreeSet::from([" VIP ".to_string(), "vip".to_string(), "region:emea".to_string()]);
+        assert_eq!(
+            tags("tags", &input).unwrap(),
+            BTreeSet::from(["vip".to_string(), "region:emea".to_string()])
+        );
+
+        assert!(tags("tags", &BTreeSet::from(["two words".to_string()])).is_err());
+        assert!(tags("tags", &BTreeSet::from([" ".to_string()])).is_err());
+        let many: BTreeSet<String> = (0..=MAX_TAGS).map(|index| index.to_string()).collect();
+        assert!(tags("tags", &many).is_err());
+    }
+
+    #[test]
+    fn test_country_code() {
+        assert_eq!(country_code("country", "de").unwrap(), "DE");
+        assert!(country_code("country", "DEU").is_err());
+        assert!(country_code("country", "D1").is_err());
+    }
+}
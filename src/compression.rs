@@ -0,0 +1,233 @@
+use std::sync::Arc;
+
+use axum::{
+    http::{header, Extensions, HeaderMap, StatusCode, Version},
+    Router,
+};
+use tower_http::{
+    compression::{predicate::SizeAbove, CompressionLayer, Predicate},
+    decompression::RequestDecompressionLayer,
+};
+
+use crate::config::CompressionConfig;
+
+// Streams flush event by event, which compression would hold back
+const EVENT_STREAM: &str = "text/event-stream";
+
+/// Compress responses from `router` and decompress request bodies sent to it,
+/// as configured.
+///
+/// Request bodies with a `Content-Encoding` that isn't enabled get 415.
+/// Body size limits apply to the decompressed body.
+pub fn apply(mut router: Router, config: &CompressionConfig) -> Router {
+    if config.decompress_requests {
+        router = router.layer(
+            RequestDecompressionLayer::new()
+                .gzip(config.gzip)
+                .br(config.brotli)
+                .zstd(config.zstd),
+        );
+    }
+
+    if config.enabled {
+        let content_types: Arc<[String]> = config
+            .content_types
+            .iter()
+            .map(|content_type| content_type.trim().to_ascii_lowercase())
+            .collect();
+        let allowed = move |_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions| {
+            compressible(&content_types, headers)
+        };
+
+        router = router.layer(
+            CompressionLayer::new()
+                .gzip(config.gzip)
+                .br(config.brotli)
+                .zstd(config.zstd)
+                .compress_when(SizeAbove::new(config.min_size_bytes).and(allowed)),
+        );
+    }
+
+    router
+}
+
+// Whether the response's media type is in the allowlist
+fn compressible(content_types: &[String], headers: &HeaderMap) -> bool {
+    let Some(media_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase())
+    else {
+        return false;
+    };
+    if media_type == EVENT_STREAM {
+        return false;
+    }
+
+    content_types.iter().any(|allowed| match allowed.strip_suffix("/*") {
+        Some(top_level) => media_type
+            .split_once('/')
+            .is_some_and(|(media_top_level, _)| media_top_level == top_level),
+        None => media_type == *allowed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    use axum::{
+        body::{to_bytes, Body},
+        http::Request,
+        response::{sse::Event, IntoResponse, Response, Sse},
+        routing::{get, post},
+    };
+    use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+    use futures_util::stream;
+    use tower::ServiceExt;
+
+    fn large_json() -> String {
+        format!("[{}]", vec!["{\"name\":\"Example Client\"}"; 200].join(","))
+    }
+
+    fn app(config: &CompressionConfig) -> Router {
+        let router = Router::new()
+            .route("/json", get(|| async { ([(header::CONTENT_TYPE, "application/json")], large_json()) }))
+            .route("/small", get(|| async { ([(header::CONTENT_TYPE, "application/json")], "{}") }))
+            .route("/png", get(|| async { ([(header::CONTENT_TYPE, "image/png")], large_json()) }))
+            .route(
+                "/events",
+                get(|| async {
+                    let events = stream::iter(
+                        (0..100).map(|_| Ok::<_, std::convert::Infallible>(Event::default().data(large_json()))),
+                    );
+                    Sse::new(events)
+                }),
+            )
+            .route("/echo", post(|body: String| async move { body }));
+        apply(router, config)
+    }
+
+    async fn get_with(app: Router, uri: &str, accept_encoding: &str) -> Response {
+        app.oneshot(
+            Request::builder()
+                .uri(uri)
+                .header(header::ACCEPT_ENCODING, accept_encoding)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+    }
+
+    fn encoding(response: &Response) -> Option<&str> {
+        response
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .map(|value| value.to_str().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_negotiates_encoding() {
+        let config = CompressionConfig::default();
+
+        let response = get_with(app(&config), "/json", "gzip").await;
+        assert_eq!(encoding(&response), Some("gzip"));
+        assert!(response.headers()[header::VARY].to_str().unwrap().contains("accept-encoding"));
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mut decoded = String::new();
+        GzDecoder::new(&body[..]).read_to_string(&mut decoded).unwrap();
+        assert_eq!(decoded, large_json());
+
+        let response = get_with(app(&config), "/json", "br;q=1.0, gzip;q=0.5").await;
+        assert_eq!(encoding(&response), Some("br"));
+        let response = get_with(app(&config), "/json", "zstd").await;
+        assert_eq!(encoding(&response), Some("zstd"));
+
+        let response = get_with(app(&config), "/json", "identity").await;
+        assert_eq!(encoding(&response), None);
+
+        let config = CompressionConfig {
+            brotli: false,
+            ..CompressionConfig::default()
+        };
+        let response = get_with(app(&config), "/json", "br, gzip").await;
+        assert_eq!(encoding(&response), Some("gzip"));
+    }
+
+    #[tokio::test]
+    async fn test_skips_small_unlisted_and_event_stream_responses() {
+        let config = CompressionConfig::default();
+
+        for uri in ["/small", "/png"] {
+            let response = get_with(app(&config), uri, "gzip").await;
+            assert_eq!(encoding(&response), None, "{}", uri);
+        }
+
+        // Even when event streams are allowlisted
+        let config = CompressionConfig {
+            content_types: vec!["text/*".to_string()],
+            ..CompressionConfig::default()
+        };
+        let response = get_with(app(&config), "/events", "gzip").await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], EVENT_STREAM);
+        assert_eq!(encoding(&response), None);
+
+        let config = CompressionConfig {
+            enabled: false,
+            ..CompressionConfig::default()
+        };
+        let response = get_with(app(&config), "/json", "gzip").await;
+        assert_eq!(encoding(&response), None);
+    }
+
+    #[test]
+    fn test_compressible_content_types() {
+        let allowed = vec!["application/json".to_string(), "text/*".to_string()];
+        let headers = |content_type: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
+            headers
+        };
+
+        assert!(compressible(&allowed, &headers("application/json")));
+        assert!(compressible(&allowed, &headers("Text/CSV; charset=utf-8")));
+        assert!(!compressible(&allowed, &headers("application/jsonp")));
+        assert!(!compressible(&allowed, &headers("text/event-stream")));
+        assert!(!compressible(&allowed, &HeaderMap::new()));
+    }
+
+    #[tokio::test]
+    async fn test_decompresses_request_bodies() {
+        let config = CompressionConfig::default();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"{\"name\":\"Compressed\"}").unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let post = |encoding: &str, body: Vec<u8>| {
+            Request::builder()
+                .method("POST")
+                .uri("/echo")
+                .header(header::CONTENT_ENCODING, encoding)
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let response = app(&config).oneshot(post("gzip", gzipped.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_response().into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"{\"name\":\"Compressed\"}");
+
+        let response = app(&config).oneshot(post("compress", gzipped.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let config = CompressionConfig {
+            gzip: false,
+            ..CompressionConfig::default()
+        };
+        let response = app(&config).oneshot(post("gzip", gzipped)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
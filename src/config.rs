@@ -1,12 +1,744 @@
 use config::{Config, ConfigError, Environment, File};
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::env;
 use std::path::Path;
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Deserialize, PartialEq)]
 pub struct AppConfig {
     pub run_mode: String,
     pub some_other_setting: String,
+    #[serde(default)]
+    pub http: HttpConfig,
+    #[serde(default)]
+    pub access_log: AccessLogConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub otel: OtelConfig,
+    #[serde(default)]
+    pub clients: ClientsConfig,
+    #[serde(default)]
+    pub idempotency: IdempotencyConfig,
+    #[serde(default)]
+    pub webhooks: WebhooksConfig,
+    #[serde(default)]
+    pub events: EventsConfig,
+    #[serde(default)]
+    pub websocket: WebSocketConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    #[serde(default)]
+    pub auth_lockout: AuthLockoutConfig,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    #[serde(default)]
+    pub security_headers: SecurityHeadersConfig,
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            run_mode: "local".to_string(),
+            some_other_setting: "placeholder".to_string(),
+            http: HttpConfig::default(),
+            access_log: AccessLogConfig::default(),
+            logging: LoggingConfig::default(),
+            metrics: MetricsConfig::default(),
+            otel: OtelConfig::default(),
+            clients: ClientsConfig::default(),
+            idempotency: IdempotencyConfig::default(),
+            webhooks: WebhooksConfig::default(),
+            events: EventsConfig::default(),
+            websocket: WebSocketConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            auth_lockout: AuthLockoutConfig::default(),
+            cors: CorsConfig::default(),
+            security_headers: SecurityHeadersConfig::default(),
+            compression: CompressionConfig::default(),
+            limits: LimitsConfig::default(),
+        }
+    }
+}
+
+impl AppConfig {
+    /// Check values that deserialize fine but would break the app at runtime
+    pub fn validate(&self) -> Result<(), String> {
+        self.metrics.validate()?;
+        self.clients.validate()?;
+        self.idempotency.validate()?;
+        self.auth_lockout.validate()?;
+        self.rate_limit.validate()
+    }
+}
+
+/// General HTTP server settings
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Trust the `X-Forwarded-For` header when determining the client IP.
+    /// Only enable this when running behind a proxy that sets it.
+    pub trust_forwarded_for: bool,
+}
+
+/// Access log settings
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct AccessLogConfig {
+    pub enabled: bool,
+    /// Route templates (e.g. `/api/health`) that never produce access log events
+    pub exclude_routes: Vec<String>,
+}
+
+/// Prometheus metrics settings
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    pub path: String,
+    /// Serve metrics on a separate admin address (e.g. `127.0.0.1:9000`)
+    /// instead of the main port
+    pub listen_addr: Option<String>,
+}
+
+impl MetricsConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.enabled && !self.path.starts_with('/') {
+            return Err(format!("metrics.path must start with '/': {:?}", self.path));
+        }
+        Ok(())
+    }
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: "/metrics".to_string(),
+            listen_addr: None,
+        }
+    }
+}
+
+/// OTLP wire protocol used to export traces
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OtlpProtocol {
+    /// HTTP with protobuf bodies
+    Protobuf,
+    /// HTTP with JSON bodies
+    Json,
+}
+
+/// OpenTelemetry trace export settings
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct OtelConfig {
+    pub enabled: bool,
+    /// OTLP/HTTP traces endpoint
+    pub endpoint: String,
+    pub protocol: OtlpProtocol,
+    pub service_name: String,
+    /// Fraction of new traces to sample, from 0.0 to 1.0.
+    /// Requests with a sampled incoming `traceparent` are always sampled.
+    pub sampling_ratio: f64,
+    /// Extra resource attributes in `OTEL_RESOURCE_ATTRIBUTES` format,
+    /// e.g. `deployment.environment=staging,team=platform`
+    pub resource_attributes: String,
+}
+
+impl Default for OtelConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "http://localhost:4318/v1/traces".to_string(),
+            protocol: OtlpProtocol::Protobuf,
+            service_name: "axum-starter".to_string(),
+            sampling_ratio: 1.0,
+            resource_attributes: String::new(),
+        }
+    }
+}
+
+/// Client API settings
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ClientsConfig {
+    /// Reject updates without an `If-Match` header with 428 Precondition Required
+    pub require_if_match: bool,
+    /// Days a deleted client can still be restored before it is purged
+    pub deleted_retention_days: u64,
+    /// How often the purge job looks for expired deleted clients
+    pub purge_interval_seconds: u64,
+    /// Longest CSV or NDJSON record accepted by an import
+    pub max_import_record_bytes: usize,
+}
+
+impl ClientsConfig {
+    /// How long deleted clients are kept, or `None` when `deleted_retention_days`
+    /// is too large to subtract from the current time
+    pub fn retention(&self) -> Option<chrono::Duration> {
+        let days = i64::try_from(self.deleted_retention_days).ok()?;
+        let retention = chrono::Duration::try_days(days)?;
+        chrono::Utc::now().checked_sub_signed(retention)?;
+        Some(retention)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self.retention() {
+            Some(_) => Ok(()),
+            None => Err(format!(
+                "clients.deleted_retention_days is too large: {}",
+                self.deleted_retention_days
+            )),
+        }
+    }
+}
+
+impl Default for ClientsConfig {
+    fn default() -> Self {
+        Self {
+            require_if_match: true,
+            deleted_retention_days: 30,
+            purge_interval_seconds: 3600,
+            max_import_record_bytes: 64 * 1024,
+        }
+    }
+}
+
+/// `Idempotency-Key` handling for POST and PATCH requests
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct IdempotencyConfig {
+    pub enabled: bool,
+    /// How long a stored response is replayed for retries with the same key
+    pub ttl_seconds: u64,
+    /// Largest request body accepted with an `Idempotency-Key`
+    pub max_body_bytes: usize,
+}
+
+impl IdempotencyConfig {
+    fn validate(&self) -> Result<(), String> {
+        if Instant::now().checked_add(Duration::from_secs(self.ttl_seconds)).is_none() {
+            return Err("idempotency.ttl_seconds is too large".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl_seconds: 86400,
+            max_body_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// Delivery of client events to registered webhook endpoints
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct WebhooksConfig {
+    pub enabled: bool,
+    /// Attempts before a delivery is dead-lettered
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled after every further failure
+    pub retry_base_seconds: u64,
+    /// Upper bound for the delay between retries
+    pub retry_max_seconds: u64,
+    /// How long to wait for an endpoint to respond
+    pub timeout_seconds: u64,
+    /// How often the delivery worker looks for due deliveries
+    pub poll_interval_ms: u64,
+    /// Hosts (names or IP addresses) webhooks may be sent to even though they
+    /// are loopback, private or link-local, e.g. `127.0.0.1` for local testing
+    pub allowed_private_hosts: Vec<String>,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_attempts: 8,
+            retry_base_seconds: 10,
+            retry_max_seconds: 3600,
+            timeout_seconds: 10,
+            poll_interval_ms: 1000,
+            allowed_private_hosts: Vec::new(),
+        }
+    }
+}
+
+/// Live client event streams
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EventsConfig {
+    /// Recent events kept for subscribers resuming with `Last-Event-ID`
+    pub replay_buffer_size: usize,
+    /// Events a slow subscriber can fall behind before it misses some
+    pub channel_capacity: usize,
+    /// Interval of keep-alive comments on idle streams
+    pub keep_alive_seconds: u64,
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        Self {
+            replay_buffer_size: 1000,
+            channel_capacity: 256,
+            keep_alive_seconds: 15,
+        }
+    }
+}
+
+/// WebSocket sessions under `/api/ws`
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct WebSocketConfig {
+    /// Interval of server pings
+    pub heartbeat_seconds: u64,
+    /// Close connections that sent nothing, not even a pong, for this long
+    pub idle_timeout_seconds: u64,
+    /// Outgoing messages buffered per connection; a subscriber that falls
+    /// further behind is disconnected
+    pub send_queue_size: usize,
+    /// Sustained rate of incoming messages per connection
+    pub messages_per_second: u32,
+    /// Incoming messages a connection may send at once above the sustained rate
+    pub message_burst: u32,
+    /// Largest incoming message
+    pub max_message_bytes: usize,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_seconds: 30,
+            idle_timeout_seconds: 75,
+            send_queue_size: 64,
+            messages_per_second: 10,
+            message_burst: 20,
+            max_message_bytes: 64 * 1024,
+        }
+    }
+}
+
+/// Token-bucket rate limits, applied separately to each route group
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Header carrying an API key to limit callers without a principal by
+    /// instead of their IP. Keys that don't authenticate fall back to the IP.
+    pub api_key_header: Option<String>,
+    /// Limit for groups without their own entry in `groups`
+    pub default: RateLimitRule,
+    /// Limits by route group: `public`, `clients`, `admin`, `audit`,
+    /// `webhooks` or `ws`
+    pub groups: BTreeMap<String, RateLimitRule>,
+}
+
+impl RateLimitConfig {
+    /// The limit for a route group
+    pub fn rule(&self, group: &str) -> &RateLimitRule {
+        self.groups.get(group).unwrap_or(&self.default)
+    }
+
+    // A rate of 0 would never refill, so callers would be told to retry never
+    fn validate(&self) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let rules = std::iter::once(("default", &self.default))
+            .chain(self.groups.iter().map(|(group, rule)| (group.as_str(), rule)));
+        for (group, rule) in rules {
+            if rule.requests_per_minute == 0 {
+                return Err(format!(
+                    "rate_limit.{}.requests_per_minute must be at least 1; disable rate limiting instead",
+                    group
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            api_key_header: None,
+            default: RateLimitRule {
+                requests_per_minute: 600,
+                burst: 100,
+            },
+            groups: BTreeMap::from([
+                (
+                    "public".to_string(),
+                    RateLimitRule {
+                        requests_per_minute: 120,
+                        burst: 30,
+                    },
+                ),
+                (
+                    "admin".to_string(),
+                    RateLimitRule {
+                        requests_per_minute: 60,
+                        burst: 10,
+                    },
+                ),
+            ]),
+        }
+    }
+}
+
+/// A sustained request rate with room for bursts above it
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct RateLimitRule {
+    pub requests_per_minute: u32,
+    /// Requests a caller may make at once; also the most it can save up
+    pub burst: u32,
+}
+
+/// Throttling of repeated authentication failures, tracked per client IP and
+/// per credential prefix
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct AuthLockoutConfig {
+    pub enabled: bool,
+    /// Failures within `window_seconds` that lock an IP or credential out
+    pub max_failures: u32,
+    pub window_seconds: u64,
+    pub lockout_seconds: u64,
+    /// Failures before further failures start a cool-down
+    pub delay_after_failures: u32,
+    /// First cool-down, doubled with each further failure up to `max_delay_ms`
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// Leading characters of a token that identify the credential being guessed
+    pub credential_prefix_len: usize,
+}
+
+impl AuthLockoutConfig {
+    fn validate(&self) -> Result<(), String> {
+        let durations = [
+            ("window_seconds", Duration::from_secs(self.window_seconds)),
+            ("lockout_seconds", Duration::from_secs(self.lockout_seconds)),
+            ("max_delay_ms", Duration::from_millis(self.max_delay_ms)),
+        ];
+        let now = Instant::now();
+        for (name, duration) in durations {
+            if now.checked_add(duration).is_none() {
+                return Err(format!("auth_lockout.{} is too large", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for AuthLockoutConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_failures: 10,
+            window_seconds: 600,
+            lockout_seconds: 900,
+            delay_after_failures: 3,
+            base_delay_ms: 250,
+            max_delay_ms: 4000,
+            credential_prefix_len: 8,
+        }
+    }
+}
+
+/// Cross-origin access for browser apps served from other origins
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct CorsConfig {
+    pub enabled: bool,
+    /// Exact origins such as `https://app.example.com`, or wildcard subdomains
+    /// such as `https://*.example.com`. Defaults to localhost dev servers for
+    /// local runs and to no origins otherwise.
+    pub allowed_origins: Option<Vec<String>>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    /// Response headers that browser scripts may read
+    pub exposed_headers: Vec<String>,
+    /// Allow cookies and `Authorization` on cross-origin requests
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight response
+    pub max_age_seconds: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        let strings = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+        Self {
+            enabled: true,
+            allowed_origins: None,
+            allowed_methods: strings(&["GET", "POST", "PUT", "PATCH", "DELETE"]),
+            allowed_headers: strings(&[
+                "authorization",
+                "content-type",
+                "if-match",
+                "if-none-match",
+                "idempotency-key",
+                "last-event-id",
+                "x-request-id",
+            ]),
+            exposed_headers: strings(&[
+                "etag",
+                "link",
+                "location",
+                "retry-after",
+                "ratelimit-limit",
+                "ratelimit-remaining",
+                "ratelimit-reset",
+                "idempotent-replayed",
+                "x-request-id",
+            ]),
+            allow_credentials: false,
+            max_age_seconds: 600,
+        }
+    }
+}
+
+/// Hardening headers added to every response. An empty value leaves that
+/// header out.
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SecurityHeadersConfig {
+    pub enabled: bool,
+    /// `Strict-Transport-Security` max-age; 0 leaves the header out
+    pub hsts_max_age_seconds: u64,
+    pub hsts_include_subdomains: bool,
+    pub hsts_preload: bool,
+    pub frame_options: String,
+    pub referrer_policy: String,
+    pub permissions_policy: String,
+    pub content_security_policy: String,
+    /// Policy for the Swagger UI, which needs its own scripts, inline styles
+    /// and data URI images
+    pub docs_content_security_policy: String,
+}
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            hsts_max_age_seconds: 31_536_000,
+            hsts_include_subdomains: true,
+            hsts_preload: false,
+            frame_options: "DENY".to_string(),
+            referrer_policy: "no-referrer".to_string(),
+            permissions_policy: "camera=(), microphone=(), geolocation=(), payment=(), usb=()".to_string(),
+            content_security_policy: "default-src 'none'; frame-ancestors 'none'; base-uri 'none'; form-action 'none'"
+                .to_string(),
+            docs_content_security_policy: "default-src 'self'; script-src 'self'; style-src 'self' 'unsafe-inline'; \
+                img-src 'self' data:; connect-src 'self'; frame-ancestors 'none'; base-uri 'self'; form-action 'self'"
+                .to_string(),
+        }
+    }
+}
+
+/// Compression of responses and decompression of request bodies
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct CompressionConfig {
+    /// Compress responses for clients that send a matching `Accept-Encoding`
+    pub enabled: bool,
+    pub gzip: bool,
+    pub brotli: bool,
+    pub zstd: bool,
+    /// Smaller responses are sent as they are
+    pub min_size_bytes: u16,
+    /// Media types to compress, exact or with a wildcard subtype such as `text/*`.
+    /// `text/event-stream` is never compressed.
+    pub content_types: Vec<String>,
+    /// Accept request bodies sent with a `Content-Encoding` of the enabled algorithms
+    pub decompress_requests: bool,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            gzip: true,
+            brotli: true,
+            zstd: true,
+            min_size_bytes: 1024,
+            content_types: [
+                "application/json",
+                "application/x-ndjson",
+                "application/javascript",
+                "image/svg+xml",
+                "text/*",
+            ]
+            .iter()
+            .map(|content_type| content_type.to_string())
+            .collect(),
+            decompress_requests: true,
+        }
+    }
+}
+
+/// Timeouts, body size limits and load shedding
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Requests handled at once across the server; 0 for no cap
+    pub max_concurrent_requests: usize,
+    /// How long a request waits for a free slot before it's shed with 503;
+    /// 0 sheds it straight away
+    pub queue_timeout_ms: u64,
+    /// `Retry-After` sent with shed requests
+    pub retry_after_seconds: u64,
+    /// Limits for groups without their own entry in `groups`
+    pub default: RouteLimits,
+    /// Limits by route group: `public`, `clients`, `admin`, `audit`,
+    /// `webhooks` or `ws`
+    pub groups: BTreeMap<String, RouteLimits>,
+}
+
+impl LimitsConfig {
+    /// The limits for a route group
+    pub fn route(&self, group: &str) -> &RouteLimits {
+        self.groups.get(group).unwrap_or(&self.default)
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_requests: 512,
+            queue_timeout_ms: 100,
+            retry_after_seconds: 1,
+            default: RouteLimits::default(),
+            // Bulk imports stream large bodies and take longer
+            groups: BTreeMap::from([(
+                "clients".to_string(),
+                RouteLimits {
+                    timeout_seconds: 120,
+                    body_timeout_seconds: 60,
+                    max_body_bytes: 16 * 1024 * 1024,
+                },
+            )]),
+        }
+    }
+}
+
+/// Limits for the requests to one route group; 0 turns a limit off
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RouteLimits {
+    /// Time to produce a response before 504. Streamed responses such as event
+    /// streams and WebSocket sessions only need to start within it.
+    pub timeout_seconds: u64,
+    /// Time for the whole request body to arrive before 408
+    pub body_timeout_seconds: u64,
+    /// Largest request body before 413, counted after decompression
+    pub max_body_bytes: usize,
+}
+
+impl Default for RouteLimits {
+    fn default() -> Self {
+        Self {
+            timeout_seconds: 30,
+            body_timeout_seconds: 10,
+            max_body_bytes: 2 * 1024 * 1024,
+        }
+    }
+}
+
+/// Log output format
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Json,
+    Pretty,
+    Compact,
+    Logfmt,
+}
+
+/// Log output settings
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Output format; defaults to `pretty` for local runs and `json` otherwise
+    pub format: Option<LogFormat>,
+    /// `EnvFilter` directives, e.g. `axum_starter=debug,tower_http=info`.
+    /// `RUST_LOG` takes precedence when set.
+    pub level: String,
+    pub timestamps: bool,
+    /// Write to stdout in addition to any file output
+    pub stdout: bool,
+    pub file: Option<LogFileConfig>,
+    /// Longest `ttl_seconds` accepted for a runtime log level change
+    pub max_level_ttl_seconds: u64,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            format: None,
+            level: "axum_starter=debug,tower_http=debug".to_string(),
+            timestamps: true,
+            stdout: true,
+            file: None,
+            max_level_ttl_seconds: 86400,
+        }
+    }
+}
+
+/// How log files are rotated
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Never,
+    Daily,
+    Size,
+}
+
+/// Log file output settings
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct LogFileConfig {
+    pub directory: String,
+    pub file_name: String,
+    pub rotation: LogRotation,
+    /// Size at which the file is rotated when `rotation = "size"`
+    pub max_size_bytes: u64,
+    /// Number of rotated files to keep when `rotation = "size"`
+    pub max_files: usize,
+}
+
+impl Default for LogFileConfig {
+    fn default() -> Self {
+        Self {
+            directory: "logs".to_string(),
+            file_name: "axum-starter.log".to_string(),
+            rotation: LogRotation::Daily,
+            max_size_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+impl Default for AccessLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            exclude_routes: vec!["/health".to_string(), "/api/health".to_string()],
+        }
+    }
 }
 
 pub fn load_config() -> Result<AppConfig, ConfigError> {
@@ -22,7 +754,9 @@ pub fn load_config() -> Result<AppConfig, ConfigError> {
         .build()?;
     
     // Try to deserialize the configuration into our AppConfig struct
-    config.try_deserialize()
+    let config: AppConfig = config.try_deserialize()?;
+    config.validate().map_err(ConfigError::Message)?;
+    Ok(config)
 }
 
 #[cfg(test)]
@@ -47,8 +781,416 @@ mod tests {
         
         assert_eq!(app_config.run_mode, "test");
         assert_eq!(app_config.some_other_setting, "value");
+
+        // Sections that are not present fall back to their defaults
+        assert_eq!(app_config.access_log, AccessLogConfig::default());
+        assert!(!app_config.http.trust_forwarded_for);
+    }
+
+    #[test]
+    fn test_access_log_config_deserialize() {
+        let config_str = r#"
+        run_mode = "test"
+        some_other_setting = "value"
+
+        [access_log]
+        exclude_routes = ["/metrics"]
+        "#;
+
+        let config = Config::builder()
+            .add_source(config::File::from_str(config_str, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+
+        let app_config: AppConfig = config.try_deserialize().unwrap();
+
+        assert!(app_config.access_log.enabled);
+        assert_eq!(app_config.access_log.exclude_routes, vec!["/metrics"]);
     }
     
+    #[test]
+    fn test_logging_config_deserialize() {
+        let config_str = r#"
+        run_mode = "production"
+        some_other_setting = "value"
+
+        [logging]
+        format = "logfmt"
+        level = "info"
+        timestamps = false
+
+        [logging.file]
+        directory = "/var/log/app"
+        rotation = "size"
+        max_size_bytes = 1024
+        "#;
+
+        let config = Config::builder()
+            .add_source(config::File::from_str(config_str, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+
+        let app_config: AppConfig = config.try_deserialize().unwrap();
+        let logging = app_config.logging;
+
+        assert_eq!(logging.format, Some(LogFormat::Logfmt));
+        assert_eq!(logging.level, "info");
+        assert!(!logging.timestamps);
+        assert!(logging.stdout);
+        assert_eq!(logging.max_level_ttl_seconds, 86400);
+
+        let file = logging.file.unwrap();
+        assert_eq!(file.directory, "/var/log/app");
+        assert_eq!(file.file_name, "axum-starter.log");
+        assert_eq!(file.rotation, LogRotation::Size);
+        assert_eq!(file.max_size_bytes, 1024);
+    }
+
+    #[test]
+    fn test_otel_config_deserialize() {
+        let config_str = r#"
+        run_mode = "test"
+        some_other_setting = "value"
+
+        [otel]
+        enabled = true
+        protocol = "json"
+        sampling_ratio = 0.25
+        resource_attributes = "deployment.environment=staging"
+        "#;
+
+        let config = Config::builder()
+            .add_source(config::File::from_str(config_str, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+
+        let app_config: AppConfig = config.try_deserialize().unwrap();
+        let otel = app_config.otel;
+
+        assert!(otel.enabled);
+        assert_eq!(otel.protocol, OtlpProtocol::Json);
+        assert_eq!(otel.sampling_ratio, 0.25);
+        assert_eq!(otel.service_name, "axum-starter");
+        assert_eq!(otel.resource_attributes, "deployment.environment=staging");
+    }
+
+    #[test]
+    fn test_clients_config_deserialize() {
+        let config_str = r#"
+        run_mode = "test"
+        some_other_setting = "value"
+
+        [clients]
+        require_if_match = false
+        deleted_retention_days = 7
+        "#;
+
+        let config = Config::builder()
+            .add_source(config::File::from_str(config_str, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+
+        let app_config: AppConfig = config.try_deserialize().unwrap();
+
+        assert!(!app_config.clients.require_if_match);
+        assert_eq!(app_config.clients.deleted_retention_days, 7);
+        assert_eq!(app_config.clients.purge_interval_seconds, 3600);
+        assert_eq!(app_config.clients.max_import_record_bytes, 64 * 1024);
+        assert!(ClientsConfig::default().require_if_match);
+    }
+
+    #[test]
+    fn test_idempotency_config_deserialize() {
+        let config_str = r#"
+        run_mode = "test"
+        some_other_setting = "value"
+
+        [idempotency]
+        ttl_seconds = 600
+        "#;
+
+        let config = Config::builder()
+            .add_source(config::File::from_str(config_str, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+
+        let app_config: AppConfig = config.try_deserialize().unwrap();
+
+        assert!(app_config.idempotency.enabled);
+        assert_eq!(app_config.idempotency.ttl_seconds, 600);
+        assert_eq!(app_config.idempotency.max_body_bytes, 1024 * 1024);
+    }
+
+    #[test]
+    fn test_webhooks_config_deserialize() {
+        let config_str = r#"
+        run_mode = "test"
+        some_other_setting = "value"
+
+        [webhooks]
+        max_attempts = 3
+        retry_base_seconds = 1
+        allowed_private_hosts = ["127.0.0.1"]
+        "#;
+
+        let config = Config::builder()
+            .add_source(config::File::from_str(config_str, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+
+        let app_config: AppConfig = config.try_deserialize().unwrap();
+
+        assert!(app_config.webhooks.enabled);
+        assert_eq!(app_config.webhooks.max_attempts, 3);
+        assert_eq!(app_config.webhooks.retry_base_seconds, 1);
+        assert_eq!(app_config.webhooks.retry_max_seconds, 3600);
+        assert_eq!(app_config.webhooks.allowed_private_hosts, vec!["127.0.0.1".to_string()]);
+    }
+
+    #[test]
+    fn test_websocket_config_deserialize() {
+        let config_str = r#"
+        run_mode = "test"
+        some_other_setting = "value"
+
+        [websocket]
+        heartbeat_seconds = 5
+        message_burst = 2
+        "#;
+
+        let config = Config::builder()
+            .add_source(config::File::from_str(config_str, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+
+        let app_config: AppConfig = config.try_deserialize().unwrap();
+
+        assert_eq!(app_config.websocket.heartbeat_seconds, 5);
+        assert_eq!(app_config.websocket.message_burst, 2);
+        assert_eq!(app_config.websocket.messages_per_second, 10);
+    }
+
+    #[test]
+    fn test_rate_limit_config_deserialize() {
+        let config_str = r#"
+        run_mode = "test"
+        some_other_setting = "value"
+
+        [rate_limit]
+        api_key_header = "x-api-key"
+        default = { requests_per_minute = 100, burst = 5 }
+
+        [rate_limit.groups]
+        clients = { requests_per_minute = 30, burst = 3 }
+        "#;
+
+        let config = Config::builder()
+            .add_source(config::File::from_str(config_str, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+
+        let app_config: AppConfig = config.try_deserialize().unwrap();
+        let rate_limit = &app_config.rate_limit;
+
+        assert!(rate_limit.enabled);
+        assert_eq!(rate_limit.api_key_header.as_deref(), Some("x-api-key"));
+        assert_eq!(rate_limit.rule("clients").burst, 3);
+        // Groups listed in the file replace the built-in ones
+        assert_eq!(rate_limit.rule("admin").requests_per_minute, 100);
+        assert!(app_config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_zero_rate_limit_is_rejected() {
+        let mut config = AppConfig::default();
+        config.rate_limit.groups.insert(
+            "clients".to_string(),
+            RateLimitRule {
+                requests_per_minute: 0,
+                burst: 10,
+            },
+        );
+        let err = config.validate().unwrap_err();
+        assert!(err.contains("rate_limit.clients.requests_per_minute"));
+
+        config.rate_limit.enabled = false;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_metrics_path_needs_leading_slash() {
+        let mut config = AppConfig::default();
+        config.metrics.path = "metrics".to_string();
+        let err = config.validate().unwrap_err();
+        assert!(err.contains("metrics.path"));
+
+        config.metrics.enabled = false;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_huge_idempotency_ttl_is_rejected() {
+        let mut config = AppConfig::default();
+        config.idempotency.ttl_seconds = u64::MAX;
+        let err = config.validate().unwrap_err();
+        assert!(err.contains("idempotency.ttl_seconds"));
+    }
+
+    #[test]
+    fn test_huge_lockout_is_rejected() {
+        let mut config = AppConfig::default();
+        assert!(config.validate().is_ok());
+
+        config.auth_lockout.lockout_seconds = u64::MAX;
+        let err = config.validate().unwrap_err();
+        assert!(err.contains("auth_lockout.lockout_seconds"));
+    }
+
+    #[test]
+    fn test_huge_retention_is_rejected() {
+        let mut config = AppConfig::default();
+        assert_eq!(config.clients.retention(), Some(chrono::Duration::days(30)));
+
+        config.clients.deleted_retention_days = u64::MAX;
+        assert!(config.clients.retention().is_none());
+        let err = config.validate().unwrap_err();
+        assert!(err.contains("clients.deleted_retention_days"));
+
+        config.clients.deleted_retention_days = 1_000_000_000;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_auth_lockout_config_deserialize() {
+        let config_str = r#"
+        run_mode = "test"
+        some_other_setting = "value"
+
+        [auth_lockout]
+        max_failures = 3
+        lockout_seconds = 60
+        "#;
+
+        let config = Config::builder()
+            .add_source(config::File::from_str(config_str, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+
+        let app_config: AppConfig = config.try_deserialize().unwrap();
+
+        assert!(app_config.auth_lockout.enabled);
+        assert_eq!(app_config.auth_lockout.max_failures, 3);
+        assert_eq!(app_config.auth_lockout.lockout_seconds, 60);
+        assert_eq!(app_config.auth_lockout.window_seconds, 600);
+    }
+
+    #[test]
+    fn test_cors_config_deserialize() {
+        let config_str = r#"
+        run_mode = "test"
+        some_other_setting = "value"
+
+        [cors]
+        allowed_origins = ["https://app.example.com", "https://*.example.com"]
+        allow_credentials = true
+        "#;
+
+        let config = Config::builder()
+            .add_source(config::File::from_str(config_str, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+
+        let app_config: AppConfig = config.try_deserialize().unwrap();
+
+        assert_eq!(app_config.cors.allowed_origins.unwrap().len(), 2);
+        assert!(app_config.cors.allow_credentials);
+        assert_eq!(app_config.cors.max_age_seconds, 600);
+        assert!(app_config.cors.allowed_methods.contains(&"PATCH".to_string()));
+    }
+
+    #[test]
+    fn test_security_headers_config_deserialize() {
+        let config_str = r#"
+        run_mode = "test"
+        some_other_setting = "value"
+
+        [security_headers]
+        hsts_max_age_seconds = 0
+        frame_options = "SAMEORIGIN"
+        "#;
+
+        let config = Config::builder()
+            .add_source(config::File::from_str(config_str, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+
+        let app_config: AppConfig = config.try_deserialize().unwrap();
+        let headers = &app_config.security_headers;
+
+        assert!(headers.enabled);
+        assert_eq!(headers.hsts_max_age_seconds, 0);
+        assert_eq!(headers.frame_options, "SAMEORIGIN");
+        assert_eq!(headers.referrer_policy, "no-referrer");
+    }
+
+    #[test]
+    fn test_compression_config_deserialize() {
+        let config_str = r#"
+        run_mode = "test"
+        some_other_setting = "value"
+
+        [compression]
+        brotli = false
+        min_size_bytes = 256
+        content_types = ["application/json"]
+        "#;
+
+        let config = Config::builder()
+            .add_source(config::File::from_str(config_str, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+
+        let app_config: AppConfig = config.try_deserialize().unwrap();
+        let compression = &app_config.compression;
+
+        assert!(compression.enabled && compression.gzip && !compression.brotli);
+        assert_eq!(compression.min_size_bytes, 256);
+        assert_eq!(compression.content_types, vec!["application/json".to_string()]);
+        assert!(compression.decompress_requests);
+    }
+
+    #[test]
+    fn test_limits_config_deserialize() {
+        let config_str = r#"
+        run_mode = "test"
+        some_other_setting = "value"
+
+        [limits]
+        max_concurrent_requests = 64
+        queue_timeout_ms = 0
+
+        [limits.groups]
+        admin = { timeout_seconds = 5 }
+        "#;
+
+        let config = Config::builder()
+            .add_source(config::File::from_str(config_str, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+
+        let app_config: AppConfig = config.try_deserialize().unwrap();
+        let limits = &app_config.limits;
+
+        assert_eq!(limits.max_concurrent_requests, 64);
+        assert_eq!(limits.queue_timeout_ms, 0);
+        assert_eq!(limits.retry_after_seconds, 1);
+        assert_eq!(limits.route("admin").timeout_seconds, 5);
+        // Fields left out of a group take the built-in defaults
+        assert_eq!(limits.route("admin").max_body_bytes, 2 * 1024 * 1024);
+        // Groups listed in the file replace the built-in ones
+        assert_eq!(limits.route("clients"), &RouteLimits::default());
+    }
+
     // Test that settings file is required for the app to start
     #[test]
     fn test_missing_config_file_fails() {
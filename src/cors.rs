@@ -0,0 +1,270 @@
+use std::time::Duration;
+
+use axum::http::{HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::config::{AppConfig, CorsConfig};
+
+// Origins of common dev servers, allowed by default for local runs
+const LOCAL_ORIGINS: &[&str] = &[
+    "http://localhost:5173",
+    "http://localhost:8080",
+    "http://127.0.0.1:5173",
+    "http://127.0.0.1:8080",
+];
+
+/// An entry of `allowed_origins`
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum OriginPattern {
+    /// `*`: any origin
+    Any,
+    /// e.g. `https://app.example.com`
+    Exact(String),
+    /// e.g. `https://*.example.com`, matching any subdomain but not `example.com` itself
+    Subdomain { scheme: String, suffix: String },
+}
+
+impl OriginPattern {
+    fn parse(pattern: &str) -> Self {
+        let pattern = pattern.trim().trim_end_matches('/').to_ascii_lowercase();
+        if pattern == "*" {
+            return Self::Any;
+        }
+        match pattern.split_once("://*.") {
+            Some((scheme, domain)) => Self::Subdomain {
+                scheme: format!("{}://", scheme),
+                suffix: format!(".{}", domain),
+            },
+            None => Self::Exact(pattern),
+        }
+    }
+
+    fn matches(&self, origin: &str) -> bool {
+        let origin = origin.to_ascii_lowercase();
+        match self {
+            Self::Any => true,
+            Self::Exact(pattern) => origin == *pattern,
+            Self::Subdomain { scheme, suffix } => origin
+                .strip_prefix(scheme.as_str())
+                .and_then(|host| host.strip_suffix(suffix.as_str()))
+                .is_some_and(|subdomain| {
+                    !subdomain.is_empty()
+                        && subdomain
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+                }),
+        }
+    }
+}
+
+/// The configured origins, or the defaults for this run mode
+pub fn allowed_origins(cors: &CorsConfig, run_mode: &str) -> Vec<String> {
+    match &cors.allowed_origins {
+        Some(origins) => origins.clone(),
+        None if run_mode == "local" => LOCAL_ORIGINS.iter().map(|origin| origin.to_string()).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Build the CORS layer, or `None` when no other origin may call the API.
+///
+/// Preflight requests are answered by the layer itself, so it has to sit
+/// outside authentication.
+pub fn layer(config: &AppConfig) -> Option<CorsLayer> {
+    let cors = &config.cors;
+    if !cors.enabled {
+        return None;
+    }
+
+    let mut patterns: Vec<OriginPattern> = allowed_origins(cors, &config.run_mode)
+        .iter()
+        .map(|origin| OriginPattern::parse(origin))
+        .collect();
+    if cors.allow_credentials && patterns.contains(&OriginPattern::Any) {
+        // Any site could then make requests with the user's credentials
+        tracing::warn!("Ignoring CORS origin \"*\", which can't be combined with allow_credentials");
+        patterns.retain(|pattern| *pattern != OriginPattern::Any);
+    }
+    if patterns.is_empty() {
+        return None;
+    }
+
+    let methods: Vec<Method> = parse_all(&cors.allowed_methods, "method", |value| {
+        Method::from_bytes(value.to_ascii_uppercase().as_bytes()).ok()
+    });
+    let allowed_headers: Vec<HeaderName> = parse_all(&cors.allowed_headers, "header", |value| {
+        HeaderName::from_bytes(value.as_bytes()).ok()
+    });
+    let exposed_headers: Vec<HeaderName> = parse_all(&cors.exposed_headers, "header", |value| {
+        HeaderName::from_bytes(value.as_bytes()).ok()
+    });
+
+    Some(
+        CorsLayer::new()
+            .allow_origin(AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+                origin
+                    .to_str()
+                    .is_ok_and(|origin| patterns.iter().any(|pattern| pattern.matches(origin)))
+            }))
+            .allow_methods(methods)
+            .allow_headers(allowed_headers)
+            .expose_headers(exposed_headers)
+            .allow_credentials(cors.allow_credentials)
+            .max_age(Duration::from_secs(cors.max_age_seconds)),
+    )
+}
+
+// Parse configured names, skipping invalid ones with a warning
+fn parse_all<T>(values: &[String], kind: &str, parse: impl Fn(&str) -> Option<T>) -> Vec<T> {
+    values
+        .iter()
+        .filter_map(|value| {
+            let parsed = parse(value.trim());
+            if parsed.is_none() {
+                tracing::warn!(value = %value, "Ignoring invalid CORS {}", kind);
+            }
+            parsed
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+        response::Response,
+        routing::get,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    fn app(cors: CorsConfig) -> Router {
+        let config = AppConfig {
+            run_mode: "production".to_string(),
+            cors,
+            ..AppConfig::default()
+        };
+        Router::new()
+            .route("/things", get(|| async { ([(header::ETAG, "\"1\"")], "ok") }))
+            .layer(layer(&config).expect("CORS enabled"))
+    }
+
+    fn origins(origins: &[&str]) -> CorsConfig {
+        CorsConfig {
+            allowed_origins: Some(origins.iter().map(|origin| origin.to_string()).collect()),
+            ..CorsConfig::default()
+        }
+    }
+
+    async fn preflight(app: Router, origin: &str, method: &str) -> Response {
+        app.oneshot(
+            Request::builder()
+                .method(Method::OPTIONS)
+                .uri("/things")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, method)
+                .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization,if-match")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn test_origin_patterns() {
+        let exact = OriginPattern::parse("https://App.example.com/");
+        assert!(exact.matches("https://app.example.com"));
+        assert!(!exact.matches("http://app.example.com"));
+        assert!(!exact.matches("https://app.example.com.evil.net"));
+
+        let subdomain = OriginPattern::parse("https://*.example.com");
+        assert!(subdomain.matches("https://app.example.com"));
+        assert!(subdomain.matches("https://a.b.example.com"));
+        assert!(!subdomain.matches("https://example.com"));
+        assert!(!subdomain.matches("https://evilexample.com"));
+        assert!(!subdomain.matches("http://app.example.com"));
+        assert!(!subdomain.matches("https://app.example.com:8443"));
+        assert!(!subdomain.matches("https://user@app.example.com"));
+
+        assert!(OriginPattern::parse("*").matches("https://anything.test"));
+    }
+
+    #[test]
+    fn test_default_origins_depend_on_run_mode() {
+        let cors = CorsConfig::default();
+        assert!(allowed_origins(&cors, "local").contains(&"http://localhost:5173".to_string()));
+        assert!(allowed_origins(&cors, "production").is_empty());
+
+        let config = AppConfig {
+            run_mode: "production".to_string(),
+            ..AppConfig::default()
+        };
+        assert!(layer(&config).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_preflight_from_allowed_origin() {
+        let app = app(origins(&["https://app.example.com"]));
+
+        let response = preflight(app, "https://app.example.com", "PATCH").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+        assert!(headers[header::ACCESS_CONTROL_ALLOW_METHODS].to_str().unwrap().contains("PATCH"));
+        let allowed_headers = headers[header::ACCESS_CONTROL_ALLOW_HEADERS].to_str().unwrap();
+        assert!(allowed_headers.contains("authorization") && allowed_headers.contains("if-match"));
+        assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "600");
+        assert!(headers.get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_preflight_from_wildcard_subdomain() {
+        let app = app(CorsConfig {
+            allow_credentials: true,
+            ..origins(&["https://*.example.com"])
+        });
+
+        let response = preflight(app.clone(), "https://admin.example.com", "DELETE").await;
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://admin.example.com");
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+
+        let response = preflight(app, "https://example.org", "DELETE").await;
+        assert!(response.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_simple_request_exposes_headers() {
+        let app = app(origins(&["https://app.example.com"]));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/things")
+                    .header(header::ORIGIN, "https://app.example.com")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+        let exposed = response.headers()[header::ACCESS_CONTROL_EXPOSE_HEADERS].to_str().unwrap();
+        assert!(exposed.contains("etag") && exposed.contains("retry-after"));
+    }
+
+    #[test]
+    fn test_any_origin_not_combined_with_credentials() {
+        let config = AppConfig {
+            cors: CorsConfig {
+                allow_credentials: true,
+                ..origins(&["*"])
+            },
+            ..AppConfig::default()
+        };
+        assert!(layer(&config).is_none());
+    }
+}
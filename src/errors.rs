@@ -1,16 +1,22 @@
 #![allow(dead_code)]
 use axum::{
-    http::StatusCode,
+    http::{HeaderName, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
+use serde_json::{json, Value};
 use std::fmt;
 
+use crate::request_id;
+
 #[derive(Debug)]
 pub struct AppError {
     pub status: StatusCode,
     pub message: String,
+    /// Extra machine-readable context, returned as `error.details`
+    pub details: Option<Value>,
+    /// Extra response headers, e.g. `Retry-After`
+    pub headers: Vec<(HeaderName, HeaderValue)>,
 }
 
 impl AppError {
@@ -19,6 +25,8 @@ impl AppError {
         Self {
             status: StatusCode::INTERNAL_SERVER_ERROR,
             message: message.into(),
+            details: None,
+            headers: Vec::new(),
         }
     }
 
@@ -26,6 +34,8 @@ impl AppError {
         Self {
             status: StatusCode::NOT_FOUND,
             message: message.into(),
+            details: None,
+            headers: Vec::new(),
         }
     }
 
@@ -33,6 +43,8 @@ impl AppError {
         Self {
             status: StatusCode::UNAUTHORIZED,
             message: message.into(),
+            details: None,
+            headers: Vec::new(),
         }
     }
 
@@ -40,6 +52,8 @@ impl AppError {
         Self {
             status: StatusCode::BAD_REQUEST,
             message: message.into(),
+            details: None,
+            headers: Vec::new(),
         }
     }
 
@@ -47,8 +61,112 @@ impl AppError {
         Self {
             status: StatusCode::CONFLICT,
             message: message.into(),
+            details: None,
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn request_timeout(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::REQUEST_TIMEOUT,
+            message: message.into(),
+            details: None,
+            headers: Vec::new(),
         }
     }
+
+    pub fn precondition_failed(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::PRECONDITION_FAILED,
+            message: message.into(),
+            details: None,
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn precondition_required(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::PRECONDITION_REQUIRED,
+            message: message.into(),
+            details: None,
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn payload_too_large(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::PAYLOAD_TOO_LARGE,
+            message: message.into(),
+            details: None,
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn unprocessable_entity(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            message: message.into(),
+            details: None,
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn unsupported_media_type(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            message: message.into(),
+            details: None,
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn service_unavailable(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            message: message.into(),
+            details: None,
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn gateway_timeout(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::GATEWAY_TIMEOUT,
+            message: message.into(),
+            details: None,
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn upgrade_required(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::UPGRADE_REQUIRED,
+            message: message.into(),
+            details: None,
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn too_many_requests(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            message: message.into(),
+            details: None,
+            headers: Vec::new(),
+        }
+    }
+
+    /// Attach machine-readable context to the error
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Add a header to the error response
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.push((name, value));
+        self
+    }
 }
 
 impl fmt::Display for AppError {
@@ -61,17 +179,32 @@ impl std::error::Error for AppError {}
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let body = Json(json!({
-            "error": {
-                "status": self.status.as_u16(),
-                "message": self.message
-            }
-        }));
+        let mut body = error_body(self.status, &self.message);
+        if let Some(details) = self.details {
+            body["error"]["details"] = details;
+        }
+        let body = Json(body);
 
-        (self.status, body).into_response()
+        let mut response = (self.status, body).into_response();
+        response.headers_mut().extend(self.headers);
+        response
     }
 }
 
+// Build the JSON error body, tagging it with the current request ID when known
+fn error_body(status: StatusCode, message: &str) -> Value {
+    let mut error = json!({
+        "status": status.as_u16(),
+        "message": message
+    });
+
+    if let Some(request_id) = request_id::current() {
+        error["request_id"] = Value::String(request_id);
+    }
+
+    json!({ "error": error })
+}
+
 // Helper function to convert any error into an AppError
 pub fn internal_error<E>(err: E) -> AppError
 where
@@ -92,13 +225,12 @@ pub fn handle_panic(err: Box<dyn std::any::Any + Send + 'static>) -> Response {
     };
 
     tracing::error!("Panic occurred: {}", message);
+    crate::metrics::record_panic();
 
-    let body = Json(json!({
-        "error": {
-            "status": StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-            "message": "Internal server error"
-        }
-    }));
+    let body = Json(error_body(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal server error",
+    ));
 
     (StatusCode::INTERNAL_SERVER_ERROR, body).into_response()
 }
@@ -138,5 +270,28 @@ mod tests {
         // Check the error structure
         assert_eq!(body["error"]["status"], 400);
         assert_eq!(body["error"]["message"], "Invalid request");
+        assert!(body["error"].get("details").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_app_error_details() {
+        let response = AppError::unprocessable_entity("Patch failed")
+            .with_details(json!({ "operation": 2 }))
+            .into_response();
+
+        let body_bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body_bytes).unwrap();
+
+        assert_eq!(body["error"]["details"]["operation"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_app_error_headers() {
+        let response = AppError::too_many_requests("Slow down")
+            .with_header(axum::http::header::RETRY_AFTER, HeaderValue::from_static("3"))
+            .into_response();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "3");
     }
 }
\ No newline at end of file
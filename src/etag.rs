@@ -0,0 +1,109 @@
+use axum::http::{header, HeaderMap, HeaderValue};
+
+use crate::errors::AppError;
+
+/// Strong entity tag for a resource version, e.g. `"3"`
+pub fn etag(version: u64) -> String {
+    format!("\"{}\"", version)
+}
+
+/// `ETag` response header for a resource version
+pub fn etag_header(version: u64) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::ETAG,
+        HeaderValue::from_str(&etag(version)).expect("etag is a valid header value"),
+    );
+    headers
+}
+
+// Entity tags listed in a conditional header, `None` when the header is absent
+fn listed_tags(headers: &HeaderMap, name: header::HeaderName) -> Option<Vec<String>> {
+    let values: Vec<&str> = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+
+    Some(
+        values
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect(),
+    )
+}
+
+/// Whether `If-None-Match` matches the current tag, so a GET can answer 304.
+///
+/// Uses weak comparison, as RFC 9110 requires for `If-None-Match`.
+pub fn if_none_match(headers: &HeaderMap, current: &str) -> bool {
+    listed_tags(headers, header::IF_NONE_MATCH).is_some_and(|tags| {
+        tags.iter()
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == current)
+    })
+}
+
+/// Check `If-Match` against the current tag before an update.
+///
+/// Returns 412 when the header does not match and, if `required`, 428 when it
+/// is missing. Weak tags never match since `If-Match` uses strong comparison.
+/// Returns whether the header was present, in which case the update should
+/// also be made conditional on the version in storage.
+pub fn check_if_match(headers: &HeaderMap, current: &str, required: bool) -> Result<bool, AppError> {
+    match listed_tags(headers, header::IF_MATCH) {
+        None if required => Err(AppError::precondition_required(
+            "This request requires an If-Match header",
+        )),
+        None => Ok(false),
+        Some(tags) if tags.iter().any(|tag| tag == "*" || tag == current) => Ok(true),
+        Some(_) => Err(AppError::precondition_failed(
+            "The resource has been modified; fetch it again and retry",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    fn headers(name: header::HeaderName, value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let current = etag(2);
+
+        assert!(if_none_match(&headers(header::IF_NONE_MATCH, "\"2\""), &current));
+        assert!(if_none_match(&headers(header::IF_NONE_MATCH, "\"1\", W/\"2\""), &current));
+        assert!(if_none_match(&headers(header::IF_NONE_MATCH, "*"), &current));
+        assert!(!if_none_match(&headers(header::IF_NONE_MATCH, "\"1\""), &current));
+        assert!(!if_none_match(&HeaderMap::new(), &current));
+    }
+
+    #[test]
+    fn test_check_if_match() {
+        let current = etag(2);
+
+        assert!(check_if_match(&headers(header::IF_MATCH, "\"2\""), &current, true).unwrap());
+        assert!(check_if_match(&headers(header::IF_MATCH, "*"), &current, true).unwrap());
+        assert!(!check_if_match(&HeaderMap::new(), &current, false).unwrap());
+
+        let missing = check_if_match(&HeaderMap::new(), &current, true).unwrap_err();
+        assert_eq!(missing.status, StatusCode::PRECONDITION_REQUIRED);
+
+        let stale = check_if_match(&headers(header::IF_MATCH, "\"1\""), &current, true).unwrap_err();
+        assert_eq!(stale.status, StatusCode::PRECONDITION_FAILED);
+
+        let weak = check_if_match(&headers(header::IF_MATCH, "W/\"2\""), &current, true).unwrap_err();
+        assert_eq!(weak.status, StatusCode::PRECONDITION_FAILED);
+    }
+}
@@ -0,0 +1,359 @@
+use std::{
+    collections::{BTreeSet, VecDeque},
+    str::FromStr,
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    audit::models::AuditAction, clients::models::Client, config::EventsConfig, state::AppState,
+    webhooks,
+};
+
+/// Kind of client lifecycle event
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ToSchema)]
+pub enum ClientEventType {
+    #[serde(rename = "client.created")]
+    Created,
+    #[serde(rename = "client.updated")]
+    Updated,
+    #[serde(rename = "client.deleted")]
+    Deleted,
+}
+
+impl ClientEventType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Created => "client.created",
+            Self::Updated => "client.updated",
+            Self::Deleted => "client.deleted",
+        }
+    }
+}
+
+impl FromStr for ClientEventType {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        [Self::Created, Self::Updated, Self::Deleted]
+            .into_iter()
+            .find(|event_type| event_type.as_str() == value)
+            .ok_or_else(|| format!("Unknown event type {}", value))
+    }
+}
+
+/// Parse a comma-separated list of event types; an empty list means all of them
+pub fn parse_types(value: &str) -> Result<BTreeSet<ClientEventType>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|event_type| !event_type.is_empty())
+        .map(ClientEventType::from_str)
+        .collect()
+}
+
+impl TryFrom<AuditAction> for ClientEventType {
+    type Error = String;
+
+    fn try_from(action: AuditAction) -> Result<Self, Self::Error> {
+        match action {
+            AuditAction::Create => Ok(Self::Created),
+            // A restored client is visible again, which subscribers see as an update
+            AuditAction::Update | AuditAction::Restore => Ok(Self::Updated),
+            AuditAction::Delete | AuditAction::Purge => Ok(Self::Deleted),
+            AuditAction::Lockout => Err(format!("No client event for {:?}", action)),
+        }
+    }
+}
+
+/// A change to a client, with the client as it was after the change
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct ClientEvent {
+    /// Unique per event; receivers can use it to drop duplicate deliveries
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: ClientEventType,
+    pub occurred_at: DateTime<Utc>,
+    pub data: Client,
+}
+
+impl ClientEvent {
+    pub fn new(event_type: ClientEventType, client: &Client) -> Self {
+        Self {
+            id: Uuid::now_v7().to_string(),
+            event_type,
+            occurred_at: Utc::now(),
+            data: client.clone(),
+        }
+    }
+}
+
+/// An event as numbered by the [`EventBus`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sequenced {
+    /// Increases by one per published event, starting at 1 in each process
+    pub sequence: u64,
+    /// The bus epoch and sequence as `<epoch>-<sequence>`; used as the SSE event id
+    pub id: String,
+    pub event: ClientEvent,
+}
+
+/// A `Last-Event-ID` sent by a subscriber resuming the stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventId {
+    pub epoch: String,
+    pub sequence: u64,
+}
+
+impl FromStr for EventId {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        // Ids issued before epochs were added are bare sequences, from no epoch
+        let (epoch, sequence) = value.rsplit_once('-').unwrap_or(("", value));
+        let sequence = sequence
+            .parse()
+            .map_err(|_| format!("Invalid event id {}", value))?;
+        Ok(Self {
+            epoch: epoch.to_string(),
+            sequence,
+        })
+    }
+}
+
+/// What a new subscriber gets: missed events to replay, then live ones
+pub struct Subscription {
+    pub replay: Vec<Sequenced>,
+    /// Set when events after the requested one have already left the replay buffer
+    pub truncated: bool,
+    pub receiver: broadcast::Receiver<Sequenced>,
+}
+
+struct Replay {
+    next_sequence: u64,
+    events: VecDeque<Sequenced>,
+}
+
+/// In-process fan-out of client events to live subscribers, keeping the most
+/// recent ones so reconnecting subscribers can resume
+pub struct EventBus {
+    // Distinguishes this process's sequences from those of earlier runs
+    epoch: String,
+    sender: broadcast::Sender<Sequenced>,
+    // Also serialises publishing, so sequence numbers reach the channel in order
+    replay: Mutex<Replay>,
+    replay_size: usize,
+}
+
+impl EventBus {
+    pub fn new(config: &EventsConfig) -> Self {
+        let (sender, _) = broadcast::channel(config.channel_capacity.max(1));
+        Self {
+            epoch: Uuid::now_v7().simple().to_string(),
+            sender,
+            replay: Mutex::new(Replay {
+                next_sequence: 1,
+                events: VecDeque::with_capacity(config.replay_buffer_size),
+            }),
+            replay_size: config.replay_buffer_size,
+        }
+    }
+
+    /// Number the event, keep it for replay and send it to current subscribers
+    pub fn publish(&self, event: ClientEvent) -> Sequenced {
+        let mut replay = self.replay.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let sequenced = Sequenced {
+            sequence: replay.next_sequence,
+            id: format!("{}-{}", self.epoch, replay.next_sequence),
+            event,
+        };
+        replay.next_sequence += 1;
+        if self.replay_size > 0 {
+            if replay.events.len() == self.replay_size {
+                replay.events.pop_front();
+            }
+            replay.events.push_back(sequenced.clone());
+        }
+
+        // Sending only fails when nobody is subscribed
+        let _ = self.sender.send(sequenced.clone());
+        sequenced
+    }
+
+    /// Subscribe to live events, replaying the buffered ones after `last_event_id`
+    pub fn subscribe(&self, last_event_id: Option<&EventId>) -> Subscription {
+        let replay = self.replay.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Subscribing under the lock means no event is both replayed and received
+        let receiver = self.sender.subscribe();
+
+        let Some(last_event_id) = last_event_id else {
+            return Subscription {
+                replay: Vec::new(),
+                truncated: false,
+                receiver,
+            };
+        };
+        // Sequences restart with each process, so an id from another one says
+        // nothing about which events were missed
+        if last_event_id.epoch != self.epoch {
+            return Subscription {
+                replay: Vec::new(),
+                truncated: true,
+                receiver,
+            };
+        }
+        let last_sequence = last_event_id.sequence;
+
+        let oldest = replay
+            .events
+            .front()
+            .map_or(replay.next_sequence, |event| event.sequence);
+        Subscription {
+            replay: replay
+                .events
+                .iter()
+                .filter(|event| event.sequence > last_sequence)
+                .cloned()
+                .collect(),
+            // This process never issued an id from the future
+            truncated: last_sequence.saturating_add(1) < oldest || last_sequence >= replay.next_sequence,
+            receiver,
+        }
+    }
+}
+
+/// Hand an event to its subscribers.
+///
+/// The change has already been stored, so failing to queue deliveries is
+/// logged rather than failing the request.
+pub fn publish(state: &AppState, event: ClientEvent) {
+    if let Err(err) = webhooks::enqueue(state, &event) {
+        tracing::error!(event_id = %event.id, error = %err, "Failed to queue webhook deliveries");
+    }
+    state.events.publish(event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn event(name: &str) -> ClientEvent {
+        let client = Client {
+            id: "1".to_string(),
+            name: name.to_string(),
+            created_at: Utc::now(),
+            version: 1,
+            deleted_at: None,
+            metadata: BTreeMap::new(),
+            tags: BTreeSet::new(),
+            custom_fields: BTreeMap::new(),
+        };
+        ClientEvent::new(ClientEventType::Updated, &client)
+    }
+
+    fn bus(replay_buffer_size: usize) -> EventBus {
+        EventBus::new(&EventsConfig {
+            replay_buffer_size,
+            ..EventsConfig::default()
+        })
+    }
+
+    fn after(bus: &EventBus, sequence: u64) -> Subscription {
+        bus.subscribe(Some(&EventId {
+            epoch: bus.epoch.clone(),
+            sequence,
+        }))
+    }
+
+    fn sequences(events: &[Sequenced]) -> Vec<u64> {
+        events.iter().map(|event| event.sequence).collect()
+    }
+
+    #[test]
+    fn test_parse_types() {
+        assert_eq!(
+            parse_types("client.created, client.deleted").unwrap(),
+            BTreeSet::from([ClientEventType::Created, ClientEventType::Deleted])
+        );
+        assert!(parse_types("").unwrap().is_empty());
+        assert!(parse_types("client.renamed").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_receive_events_in_order() {
+        let bus = bus(10);
+        let mut subscription = bus.subscribe(None);
+
+        bus.publish(event("a"));
+        bus.publish(event("b"));
+
+        assert_eq!(subscription.receiver.recv().await.unwrap().sequence, 1);
+        let second = subscription.receiver.recv().await.unwrap();
+        assert_eq!(second.sequence, 2);
+        assert_eq!(second.event.data.name, "b");
+    }
+
+    #[test]
+    fn test_replay_after_last_sequence() {
+        let bus = bus(3);
+        for name in ["a", "b", "c", "d"] {
+            bus.publish(event(name));
+        }
+
+        let subscription = after(&bus, 2);
+        assert_eq!(sequences(&subscription.replay), vec![3, 4]);
+        assert_eq!(subscription.replay[0].id, format!("{}-3", bus.epoch));
+        assert!(!subscription.truncated);
+
+        // Event 1 has left the buffer, so resuming after 0 misses it
+        let subscription = after(&bus, 0);
+        assert_eq!(sequences(&subscription.replay), vec![2, 3, 4]);
+        assert!(subscription.truncated);
+
+        let subscription = after(&bus, 4);
+        assert!(subscription.replay.is_empty());
+        assert!(!subscription.truncated);
+
+        assert!(after(&bus, 9).truncated);
+    }
+
+    #[test]
+    fn test_ids_from_another_process_resync() {
+        let bus = bus(10);
+        bus.publish(event("a"));
+        bus.publish(event("b"));
+
+        // A restarted process numbers from 1 again under a new epoch
+        let restarted = EventBus::new(&EventsConfig::default());
+        assert_ne!(restarted.epoch, bus.epoch);
+        let id = restarted.publish(event("c")).id;
+        let subscription = bus.subscribe(Some(&id.parse().unwrap()));
+        assert!(subscription.replay.is_empty());
+        assert!(subscription.truncated);
+
+        let subscription = bus.subscribe(Some(&"1".parse().unwrap()));
+        assert!(subscription.replay.is_empty());
+        assert!(subscription.truncated);
+    }
+
+    #[test]
+    fn test_parse_event_id() {
+        assert_eq!(
+            "0192abc-42".parse::<EventId>().unwrap(),
+            EventId {
+                epoch: "0192abc".to_string(),
+                sequence: 42,
+            }
+        );
+        assert_eq!("7".parse::<EventId>().unwrap().epoch, "");
+        assert!("0192abc-".parse::<EventId>().is_err());
+        assert!("abc".parse::<EventId>().is_err());
+    }
+}
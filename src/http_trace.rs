@@ -0,0 +1,198 @@
+use std::time::Instant;
+
+use axum::{
+    body::HttpBody,
+    extract::{MatchedPath, Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use tracing::{field, Instrument};
+
+use crate::{client_ip::client_ip, state::AppState};
+
+// Route label used when no route matched (e.g. the 404 fallback)
+pub const UNMATCHED_ROUTE: &str = "<unmatched>";
+
+/// Returns the matched route template for a request, e.g. `/api/clients/:id`
+pub fn matched_route(request: &Request) -> String {
+    request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_string())
+}
+
+// Middleware that opens a span per request and emits an access log event on completion
+pub async fn trace_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let start = Instant::now();
+    let config = &state.config;
+
+    let method = request.method().clone();
+    let route = matched_route(&request);
+    let path = request.uri().path().to_string();
+    let client_ip = client_ip(&request, config.http.trust_forwarded_for)
+        .map(|ip| ip.to_string())
+        .unwrap_or_default();
+    let user_agent = request
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let bytes_in = content_length(request.headers())
+        .or_else(|| request.body().size_hint().exact())
+        .unwrap_or(0);
+
+    // Fields left empty here are filled in by inner middleware or on completion
+    let span = tracing::info_span!(
+        "http_request",
+        method = %method,
+        route = %route,
+        path = %path,
+        client_ip = %client_ip,
+        user_agent = %user_agent,
+        request_id = field::Empty,
+        status = field::Empty,
+        latency_ms = field::Empty,
+        bytes_in,
+        bytes_out = field::Empty,
+    );
+
+    // Join the caller's distributed trace when a `traceparent` header is present
+    crate::telemetry::set_parent_from_headers(&span, request.headers());
+
+    let log_access = config.access_log.enabled
+        && !config.access_log.exclude_routes.contains(&route);
+
+    async move {
+        let response = next.run(request).await;
+
+        let status = response.status().as_u16();
+        let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+        let bytes_out = content_length(response.headers())
+            .or_else(|| response.body().size_hint().exact());
+
+        let span = tracing::Span::current();
+        span.record("status", status);
+        span.record("latency_ms", latency_ms);
+        if let Some(bytes_out) = bytes_out {
+            span.record("bytes_out", bytes_out);
+        }
+
+        if log_access {
+            tracing::info!(
+                target: "access_log",
+                method = %method,
+                route = %route,
+                path = %path,
+                status,
+                latency_ms,
+                bytes_in,
+                bytes_out,
+                client_ip = %client_ip,
+                user_agent = %user_agent,
+                "request completed"
+            );
+        }
+
+        response
+    }
+    .instrument(span)
+    .await
+}
+
+fn content_length(headers: &axum::http::HeaderMap) -> Option<u64> {
+    headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::AppConfig, logging::CapturedLogs};
+    use axum::{
+        body::Body,
+        http::StatusCode,
+        middleware,
+        routing::get,
+        Router,
+    };
+    use tower::ServiceExt;
+    use tracing_subscriber::prelude::*;
+
+    async fn item_handler() -> &'static str {
+        "item"
+    }
+
+    fn test_app() -> Router {
+        let state = AppState::new(AppConfig::default());
+        Router::new()
+            .route("/items/:id", get(item_handler))
+            .route("/health", get(item_handler))
+            .layer(middleware::from_fn(crate::request_id::request_id_middleware))
+            .layer(middleware::from_fn_with_state(state, trace_middleware))
+    }
+
+    async fn send(uri: &str) -> (StatusCode, String) {
+        let logs = CapturedLogs::default();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_current_span(true)
+                .with_writer(logs.clone()),
+        );
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let response = test_app()
+            .oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .header(header::USER_AGENT, "test-agent")
+                    .header("x-request-id", "trace-test-id")
+                    .body(Body::empty())
+                    .unwrap()
+            )
+            .await
+            .unwrap();
+
+        (response.status(), logs.contents())
+    }
+
+    #[tokio::test]
+    async fn test_access_log_event() {
+        let (status, logs) = send("/items/42").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let line = logs
+            .lines()
+            .find(|line| line.contains("request completed"))
+            .expect("access log event");
+        let event: serde_json::Value = serde_json::from_str(line).unwrap();
+
+        assert_eq!(event["target"], "access_log");
+        assert_eq!(event["fields"]["route"], "/items/:id");
+        assert_eq!(event["fields"]["path"], "/items/42");
+        assert_eq!(event["fields"]["method"], "GET");
+        assert_eq!(event["fields"]["status"], 200);
+        assert_eq!(event["fields"]["bytes_out"], 4);
+        assert_eq!(event["fields"]["user_agent"], "test-agent");
+        assert_eq!(event["span"]["name"], "http_request");
+        assert_eq!(event["span"]["route"], "/items/:id");
+        assert_eq!(event["span"]["request_id"], "trace-test-id");
+    }
+
+    #[tokio::test]
+    async fn test_excluded_routes_are_not_logged() {
+        let (status, logs) = send("/health").await;
+        assert_eq!(status, StatusCode::OK);
+
+        assert!(!logs.contains("request completed"));
+    }
+}
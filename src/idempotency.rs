@@ -0,0 +1,487 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use tokio::time::Instant;
+
+use crate::{auth::Principal, errors::AppError, state::AppState};
+
+// Header clients send to make a POST or PATCH safe to retry
+pub static IDEMPOTENCY_KEY_HEADER: HeaderName = HeaderName::from_static("idempotency-key");
+
+// Header marking a response as a replay of a stored one
+pub static IDEMPOTENT_REPLAYED_HEADER: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+// Longest idempotency key we accept
+const MAX_KEY_LEN: usize = 255;
+
+/// A response stored so that retries can be answered without running the handler again
+#[derive(Clone, Debug)]
+pub struct StoredResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl StoredResponse {
+    fn replay(self) -> Response {
+        let mut response = (self.status, self.headers, self.body).into_response();
+        response
+            .headers_mut()
+            .insert(IDEMPOTENT_REPLAYED_HEADER.clone(), HeaderValue::from_static("true"));
+        response
+    }
+}
+
+/// What to do with a request carrying an idempotency key
+#[derive(Debug)]
+pub enum Claim {
+    /// The key is new (or expired); the caller runs the request and completes or releases it
+    Acquired,
+    /// A request with the same key is still running
+    InFlight,
+    /// The key was already used for a different request
+    Mismatch,
+    /// The key was used for this exact request; replay its response
+    Completed(StoredResponse),
+}
+
+/// Storage for idempotency keys and the responses they produced
+pub trait IdempotencyStore: Send + Sync {
+    /// Claim `key` for a request with the given fingerprint, keeping it for `ttl`
+    fn claim(&self, key: &str, fingerprint: &str, ttl: Duration) -> Claim;
+
+    /// Store the response for a claimed key
+    fn complete(&self, key: &str, response: StoredResponse);
+
+    /// Forget a claimed key so the request can be retried
+    fn release(&self, key: &str);
+}
+
+#[derive(Debug)]
+enum EntryState {
+    InFlight,
+    Completed(StoredResponse),
+}
+
+#[derive(Debug)]
+struct Entry {
+    fingerprint: String,
+    expires_at: Instant,
+    state: EntryState,
+}
+
+#[derive(Default)]
+struct Entries {
+    by_key: HashMap<String, Entry>,
+    // Keys in the order they expire, since every key gets the same TTL
+    expiry: VecDeque<(Instant, String)>,
+}
+
+impl Entries {
+    fn evict_expired(&mut self, now: Instant) {
+        while let Some((expires_at, _)) = self.expiry.front() {
+            if *expires_at > now {
+                break;
+            }
+            let (expires_at, key) = self.expiry.pop_front().expect("front exists");
+            // The key may have been released and claimed again since
+            if self.by_key.get(&key).is_some_and(|entry| entry.expires_at == expires_at) {
+                self.by_key.remove(&key);
+            }
+        }
+    }
+}
+
+/// Idempotency keys kept in memory; lost on restart and not shared between instances
+#[derive(Default)]
+pub struct InMemoryIdempotencyStore {
+    entries: Mutex<Entries>,
+}
+
+impl IdempotencyStore for InMemoryIdempotencyStore {
+    fn claim(&self, key: &str, fingerprint: &str, ttl: Duration) -> Claim {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().expect("idempotency store lock poisoned");
+        entries.evict_expired(now);
+
+        if let Some(entry) = entries.by_key.get(key) {
+            if entry.fingerprint != fingerprint {
+                return Claim::Mismatch;
+            }
+            return match &entry.state {
+                EntryState::InFlight => Claim::InFlight,
+                EntryState::Completed(response) => Claim::Completed(response.clone()),
+            };
+        }
+
+        // The TTL is validated at startup; panicking here would poison the lock.
+        // Without an entry the request still runs, it just can't be replayed.
+        let Some(expires_at) = now.checked_add(ttl) else {
+            return Claim::Acquired;
+        };
+        entries.by_key.insert(
+            key.to_string(),
+            Entry {
+                fingerprint: fingerprint.to_string(),
+                expires_at,
+                state: EntryState::InFlight,
+            },
+        );
+        entries.expiry.push_back((expires_at, key.to_string()));
+        Claim::Acquired
+    }
+
+    fn complete(&self, key: &str, response: StoredResponse) {
+        let mut entries = self.entries.lock().expect("idempotency store lock poisoned");
+        if let Some(entry) = entries.by_key.get_mut(key) {
+            entry.state = EntryState::Completed(response);
+        }
+    }
+
+    fn release(&self, key: &str) {
+        let mut entries = self.entries.lock().expect("idempotency store lock poisoned");
+        entries.by_key.remove(key);
+    }
+}
+
+// Releases a claimed key unless the request completes, e.g. when the client
+// disconnects and the handler future is dropped
+struct ClaimGuard {
+    store: Arc<dyn IdempotencyStore>,
+    key: String,
+    completed: bool,
+}
+
+impl Drop for ClaimGuard {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.store.release(&self.key);
+        }
+    }
+}
+
+/// Replay stored responses for POST and PATCH requests retried with the same
+/// `Idempotency-Key`.
+///
+/// Keys are scoped to the authenticated caller. A key reused for a different
+/// request is rejected with 422, and a retry arriving while the original is
+/// still running gets 409. Server errors and 429s are not stored, so they can be
+/// retried.
+pub async fn idempotency_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let config = &state.config.idempotency;
+    if !config.enabled || !matches!(*request.method(), Method::POST | Method::PATCH) {
+        return Ok(next.run(request).await);
+    }
+
+    let Some(key) = request.headers().get(&IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(next.run(request).await);
+    };
+    let key = key
+        .to_str()
+        .ok()
+        .filter(|key| is_valid(key))
+        .ok_or_else(|| {
+            AppError::bad_request(format!(
+                "Idempotency-Key must be 1 to {} visible ASCII characters",
+                MAX_KEY_LEN
+            ))
+        })?
+        .to_string();
+
+    let (parts, body) = request.into_parts();
+    let body = to_bytes(body, config.max_body_bytes).await.map_err(|_| {
+        AppError::payload_too_large(format!(
+            "Requests with an Idempotency-Key are limited to {} bytes",
+            config.max_body_bytes
+        ))
+    })?;
+
+    let subject = parts
+        .extensions
+        .get::<Principal>()
+        .map(|principal| principal.subject.as_str())
+        .unwrap_or("");
+    let scoped_key = format!("{}:{}", subject, key);
+    let fingerprint = fingerprint(
+        &parts.method,
+        parts.uri.path_and_query().map(|p| p.as_str()),
+        &parts.headers,
+        &body,
+    );
+
+    let store = state.idempotency.clone();
+    match store.claim(&scoped_key, &fingerprint, Duration::from_secs(config.ttl_seconds)) {
+        Claim::Acquired => {}
+        Claim::InFlight => {
+            return Err(AppError::conflict(
+                "A request with this Idempotency-Key is still being processed",
+            ));
+        }
+        Claim::Mismatch => {
+            return Err(AppError::unprocessable_entity(
+                "This Idempotency-Key was already used for a different request",
+            ));
+        }
+        Claim::Completed(response) => return Ok(response.replay()),
+    }
+
+    let mut guard = ClaimGuard {
+        store,
+        key: scoped_key,
+        completed: false,
+    };
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    // Neither is the request's outcome, so a retry should run it again
+    if response.status().is_server_error() || response.status() == StatusCode::TOO_MANY_REQUESTS {
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, usize::MAX)
+        .await
+        .map_err(|err| AppError::internal_error(format!("Failed to read response body: {}", err)))?;
+
+    guard.store.complete(
+        &guard.key,
+        StoredResponse {
+            status: parts.status,
+            headers: parts.headers.clone(),
+            body: body.clone(),
+        },
+    );
+    guard.completed = true;
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+fn is_valid(key: &str) -> bool {
+    !key.is_empty() && key.len() <= MAX_KEY_LEN && key.bytes().all(|b| b.is_ascii_graphic())
+}
+
+// Hash of everything that makes two requests "the same" for a key. The same
+// bytes mean something else as CSV or NDJSON, or as a merge patch or a JSON Patch.
+fn fingerprint(
+    method: &Method,
+    path_and_query: Option<&str>,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str());
+    hasher.update([0]);
+    hasher.update(path_and_query.unwrap_or(""));
+    hasher.update([0]);
+    for name in [header::CONTENT_TYPE, header::CONTENT_ENCODING] {
+        for value in headers.get_all(name) {
+            hasher.update(value.as_bytes());
+            hasher.update([1]);
+        }
+        hasher.update([0]);
+    }
+    hasher.update(body);
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{middleware, routing::post, Router};
+    use tokio::sync::Notify;
+    use tower::ServiceExt;
+
+    use crate::{auth, config::AppConfig};
+
+    fn app(state: AppState, calls: Arc<AtomicUsize>) -> Router {
+        Router::new()
+            .route(
+                "/things",
+                post(move |body: String| async move {
+                    let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                    (StatusCode::CREATED, format!("{} #{}", body, call))
+                }),
+            )
+            .layer(middleware::from_fn_with_state(state.clone(), idempotency_middleware))
+            .layer(middleware::from_fn_with_state(state, auth::auth_middleware))
+    }
+
+    fn request(key: Option<&str>, body: &'static str) -> Request {
+        let mut builder = Request::builder()
+            .method(Method::POST)
+            .uri("/things")
+            .header(axum::http::header::AUTHORIZATION, auth::DEV_TOKEN);
+        if let Some(key) = key {
+            builder = builder.header(&IDEMPOTENCY_KEY_HEADER, key);
+        }
+        builder.body(Body::from(body)).unwrap()
+    }
+
+    async fn send(app: &Router, request: Request) -> (StatusCode, HeaderMap, String) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, headers, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_replays_identical_retries() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = app(AppState::default(), calls.clone());
+
+        let (status, headers, body) = send(&app, request(Some("abc"), "hello")).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body, "hello #1");
+        assert!(headers.get(&IDEMPOTENT_REPLAYED_HEADER).is_none());
+
+        let (status, headers, body) = send(&app, request(Some("abc"), "hello")).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body, "hello #1");
+        assert_eq!(headers[&IDEMPOTENT_REPLAYED_HEADER], "true");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Requests without a key are never deduplicated
+        send(&app, request(None, "hello")).await;
+        send(&app, request(None, "hello")).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_reused_key_with_different_body() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = app(AppState::default(), calls.clone());
+
+        send(&app, request(Some("abc"), "hello")).await;
+        let (status, _, _) = send(&app, request(Some("abc"), "goodbye")).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_invalid_key() {
+        let app = app(AppState::default(), Arc::new(AtomicUsize::new(0)));
+
+        let (status, _, _) = send(&app, request(Some("has space"), "hello")).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_duplicate_conflicts() {
+        let started = Arc::new(Notify::new());
+        let finish = Arc::new(Notify::new());
+        let (handler_started, handler_finish) = (started.clone(), finish.clone());
+        let app = Router::new()
+            .route(
+                "/things",
+                post(move || async move {
+                    handler_started.notify_one();
+                    handler_finish.notified().await;
+                    StatusCode::CREATED
+                }),
+            )
+            .layer(middleware::from_fn_with_state(AppState::default(), idempotency_middleware))
+            .layer(middleware::from_fn_with_state(AppState::default(), auth::auth_middleware));
+
+        let first = tokio::spawn(app.clone().oneshot(request(Some("abc"), "hello")));
+        started.notified().await;
+
+        let (status, _, _) = send(&app, request(Some("abc"), "hello")).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        finish.notify_one();
+        assert_eq!(first.await.unwrap().unwrap().status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keys_expire_after_ttl() {
+        let mut config = AppConfig::default();
+        config.idempotency.ttl_seconds = 60;
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = app(AppState::new(config), calls.clone());
+
+        send(&app, request(Some("abc"), "hello")).await;
+        tokio::time::advance(Duration::from_secs(30)).await;
+        send(&app, request(Some("abc"), "hello")).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        tokio::time::advance(Duration::from_secs(31)).await;
+        let (_, _, body) = send(&app, request(Some("abc"), "hello")).await;
+        assert_eq!(body, "hello #2");
+    }
+
+    #[test]
+    fn test_release_allows_retry() {
+        let store = InMemoryIdempotencyStore::default();
+        let ttl = Duration::from_secs(60);
+
+        assert!(matches!(store.claim("k", "a", ttl), Claim::Acquired));
+        assert!(matches!(store.claim("k", "a", ttl), Claim::InFlight));
+        assert!(matches!(store.claim("k", "b", ttl), Claim::Mismatch));
+
+        store.release("k");
+        assert!(matches!(store.claim("k", "b", ttl), Claim::Acquired));
+    }
+
+    #[tokio::test]
+    async fn test_content_type_is_part_of_the_request() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = app(AppState::default(), calls.clone());
+
+        let with_type = |content_type: &'static str| {
+            let mut request = request(Some("abc"), "{}");
+            request
+                .headers_mut()
+                .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+            request
+        };
+
+        let (status, _, _) = send(&app, with_type("application/merge-patch+json")).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, _, _) = send(&app, with_type("application/json-patch+json")).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let mut encoded = with_type("application/merge-patch+json");
+        encoded
+            .headers_mut()
+            .insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        let (status, _, _) = send(&app, encoded).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (_, headers, _) = send(&app, with_type("application/merge-patch+json")).await;
+        assert_eq!(headers[&IDEMPOTENT_REPLAYED_HEADER], "true");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_huge_ttl_keeps_store_usable() {
+        let store = InMemoryIdempotencyStore::default();
+
+        assert!(matches!(store.claim("k", "a", Duration::MAX), Claim::Acquired));
+        assert!(matches!(store.claim("k", "a", Duration::from_secs(60)), Claim::Acquired));
+        assert!(matches!(store.claim("k", "a", Duration::from_secs(60)), Claim::InFlight));
+    }
+}
@@ -0,0 +1,431 @@
+use std::{
+    fmt,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::{DefaultBodyLimit, Request, State},
+    http::{header, HeaderValue},
+    middleware::{self, Next},
+    response::Response,
+    BoxError, Router,
+};
+use futures_util::{stream, StreamExt};
+use tokio::{
+    sync::Semaphore,
+    time::{self, Instant},
+};
+
+use crate::{
+    config::{LimitsConfig, RouteLimits},
+    errors::AppError,
+};
+
+/// A limit a request body broke while being read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BodyLimit {
+    Size,
+    Timeout,
+}
+
+impl fmt::Display for BodyLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Size => write!(f, "request body too large"),
+            Self::Timeout => write!(f, "request body timed out"),
+        }
+    }
+}
+
+impl std::error::Error for BodyLimit {}
+
+#[derive(Clone)]
+struct Group {
+    limits: RouteLimits,
+    name: &'static str,
+}
+
+/// Apply the timeouts and body size limit of the route group `group` to the
+/// routes of `router`.
+///
+/// Bodies are checked as they stream in, so handlers that read them in chunks
+/// still see the limits. A handler that gives up on a body that broke a limit
+/// has its response replaced with 413 or 408.
+pub fn limit(router: Router, config: &LimitsConfig, group: &'static str) -> Router {
+    let group = Group {
+        limits: config.route(group).clone(),
+        name: group,
+    };
+    router
+        // Our own limit replaces the extractors' default one
+        .layer(DefaultBodyLimit::disable())
+        .layer(middleware::from_fn_with_state(group, limits_middleware))
+}
+
+async fn limits_middleware(
+    State(group): State<Group>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let limits = &group.limits;
+    if limits.max_body_bytes > 0
+        && let Some(length) = content_length(&request)
+        && length > limits.max_body_bytes as u64
+    {
+        return Err(exceeded(&group, BodyLimit::Size));
+    }
+
+    let broken = Arc::new(OnceLock::new());
+    let request = if request.body().size_hint().exact() == Some(0) {
+        request
+    } else {
+        guard_body(request, limits, broken.clone())
+    };
+
+    let response = if limits.timeout_seconds > 0 {
+        match time::timeout(Duration::from_secs(limits.timeout_seconds), next.run(request)).await {
+            Ok(response) => response,
+            // A stalled body is the client's fault, not ours
+            Err(_) => match broken.get() {
+                Some(limit) => return Err(exceeded(&group, *limit)),
+                None => {
+                    crate::metrics::record_limit_exceeded(group.name, "timeout");
+                    return Err(AppError::gateway_timeout(format!(
+                        "Request took longer than {} seconds",
+                        limits.timeout_seconds
+                    )));
+                }
+            },
+        }
+    } else {
+        next.run(request).await
+    };
+
+    match broken.get() {
+        Some(limit) => Err(exceeded(&group, *limit)),
+        None => Ok(response),
+    }
+}
+
+fn content_length(request: &Request) -> Option<u64> {
+    request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+// Count the body as it's read, failing it once it's too large or too late
+fn guard_body(request: Request, limits: &RouteLimits, broken: Arc<OnceLock<BodyLimit>>) -> Request {
+    let max_bytes = limits.max_body_bytes;
+    let deadline = (limits.body_timeout_seconds > 0)
+        .then(|| Instant::now() + Duration::from_secs(limits.body_timeout_seconds));
+
+    let (parts, body) = request.into_parts();
+    let chunks = stream::unfold(
+        (body.into_data_stream(), 0usize, false),
+        move |(mut data, mut read, done)| {
+            let broken = broken.clone();
+            async move {
+                if done {
+                    return None;
+                }
+                let next = match deadline {
+                    Some(deadline) => time::timeout_at(deadline, data.next()).await.map_err(|_| BodyLimit::Timeout),
+                    None => Ok(data.next().await),
+                };
+
+                let limit = match next {
+                    Ok(Some(Ok(chunk))) => {
+                        read += chunk.len();
+                        if max_bytes == 0 || read <= max_bytes {
+                            return Some((Ok(chunk), (data, read, false)));
+                        }
+                        BodyLimit::Size
+                    }
+                    Ok(Some(Err(err))) => return Some((Err(BoxError::from(err)), (data, read, true))),
+                    Ok(None) => return None,
+                    Err(limit) => limit,
+                };
+                let _ = broken.set(limit);
+                Some((Err(BoxError::from(limit)), (data, read, true)))
+            }
+        },
+    );
+
+    Request::from_parts(parts, Body::from_stream(chunks))
+}
+
+fn exceeded(group: &Group, limit: BodyLimit) -> AppError {
+    match limit {
+        BodyLimit::Size => {
+            crate::metrics::record_limit_exceeded(group.name, "body_size");
+            AppError::payload_too_large(format!(
+                "Request bodies are limited to {} bytes",
+                group.limits.max_body_bytes
+            ))
+        }
+        BodyLimit::Timeout => {
+            crate::metrics::record_limit_exceeded(group.name, "body_timeout");
+            AppError::request_timeout(format!(
+                "Request body wasn't received within {} seconds",
+                group.limits.body_timeout_seconds
+            ))
+        }
+    }
+}
+
+/// Cap how many requests `router` handles at once.
+///
+/// Requests over the cap wait up to `queue_timeout_ms` for a slot, then get
+/// 503 with `Retry-After`. A slot is held until the response starts, so event
+/// streams and WebSocket sessions don't keep one.
+pub fn shed(router: Router, config: &LimitsConfig) -> Router {
+    if config.max_concurrent_requests == 0 {
+        return router;
+    }
+    let shedder = Shedder {
+        slots: Arc::new(Semaphore::new(config.max_concurrent_requests)),
+        queue_timeout: Duration::from_millis(config.queue_timeout_ms),
+        retry_after: HeaderValue::from(config.retry_after_seconds),
+    };
+    router.layer(middleware::from_fn_with_state(shedder, shed_middleware))
+}
+
+#[derive(Clone)]
+struct Shedder {
+    slots: Arc<Semaphore>,
+    queue_timeout: Duration,
+    retry_after: HeaderValue,
+}
+
+async fn shed_middleware(
+    State(shedder): State<Shedder>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let slot = if shedder.queue_timeout.is_zero() {
+        shedder.slots.clone().try_acquire_owned().ok()
+    } else {
+        time::timeout(shedder.queue_timeout, shedder.slots.clone().acquire_owned())
+            .await
+            .ok()
+            .and_then(Result::ok)
+    };
+
+    let Some(_slot) = slot else {
+        crate::metrics::record_shed();
+        return Err(AppError::service_unavailable("Server is busy, retry later")
+            .with_header(header::RETRY_AFTER, shedder.retry_after.clone()));
+    };
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    use axum::{
+        body::{to_bytes, Bytes},
+        http::StatusCode,
+        routing::{get, post},
+    };
+    use serde_json::Value;
+    use tokio::sync::Notify;
+    use tower::ServiceExt;
+
+    fn config(limits: RouteLimits) -> LimitsConfig {
+        LimitsConfig {
+            default: limits,
+            groups: Default::default(),
+            ..LimitsConfig::default()
+        }
+    }
+
+    fn app(limits: RouteLimits) -> Router {
+        let router = Router::new()
+            .route(
+                "/slow",
+                get(|| async {
+                    time::sleep(Duration::from_secs(60)).await;
+                    "done"
+                }),
+            )
+            .route("/echo", post(|body: Bytes| async move { body }))
+            .route(
+                "/count",
+                // Reads the body in chunks, and reports a failure its own way
+                post(|body: Body| async move {
+                    let mut read = 0;
+                    let mut data = body.into_data_stream();
+                    while let Some(chunk) = data.next().await {
+                        match chunk {
+                            Ok(chunk) => read += chunk.len(),
+                            Err(_) => return (StatusCode::BAD_REQUEST, "unreadable".to_string()),
+                        }
+                    }
+                    (StatusCode::OK, read.to_string())
+                }),
+            );
+        limit(router, &config(limits), "things")
+    }
+
+    fn post_to(uri: &str, body: Body) -> Request {
+        Request::builder().method("POST").uri(uri).body(body).unwrap()
+    }
+
+    // A chunked body of `chunks` 1 KiB chunks, `delay` apart
+    fn chunked(chunks: usize, delay: Duration) -> Body {
+        Body::from_stream(stream::iter(0..chunks).then(move |_| async move {
+            time::sleep(delay).await;
+            Ok::<_, Infallible>(Bytes::from(vec![b'x'; 1024]))
+        }))
+    }
+
+    async fn error_message(response: Response) -> String {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        body["error"]["message"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeout() {
+        let limits = RouteLimits {
+            timeout_seconds: 5,
+            ..RouteLimits::default()
+        };
+
+        let response = app(limits)
+            .oneshot(Request::builder().uri("/slow").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert!(error_message(response).await.contains("5 seconds"));
+
+        let limits = RouteLimits {
+            timeout_seconds: 0,
+            ..RouteLimits::default()
+        };
+        let response = app(limits)
+            .oneshot(Request::builder().uri("/slow").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_slow_body() {
+        let limits = RouteLimits {
+            timeout_seconds: 30,
+            body_timeout_seconds: 2,
+            max_body_bytes: 0,
+        };
+
+        for uri in ["/echo", "/count"] {
+            let response = app(limits.clone())
+                .oneshot(post_to(uri, chunked(10, Duration::from_secs(1))))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT, "{}", uri);
+        }
+
+        let response = app(RouteLimits::default())
+            .oneshot(post_to("/count", chunked(5, Duration::from_secs(1))))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_body_size() {
+        let limits = RouteLimits {
+            max_body_bytes: 4096,
+            ..RouteLimits::default()
+        };
+
+        // Rejected up front from Content-Length
+        let request = Request::builder()
+            .method("POST")
+            .uri("/echo")
+            .header(header::CONTENT_LENGTH, "5000")
+            .body(Body::from(vec![b'x'; 5000]))
+            .unwrap();
+        let response = app(limits.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(error_message(response).await.contains("4096 bytes"));
+
+        // Counted as it streams, whatever the handler makes of it
+        for uri in ["/echo", "/count"] {
+            let response = app(limits.clone())
+                .oneshot(post_to(uri, chunked(5, Duration::ZERO)))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE, "{}", uri);
+        }
+
+        let response = app(limits.clone())
+            .oneshot(post_to("/count", chunked(4, Duration::ZERO)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"4096");
+
+        // Larger than the extractors' own 2 MiB default
+        let limits = RouteLimits {
+            max_body_bytes: 4 * 1024 * 1024,
+            ..RouteLimits::default()
+        };
+        let response = app(limits)
+            .oneshot(post_to("/echo", Body::from(vec![b'x'; 3 * 1024 * 1024])))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_shedding() {
+        let release = Arc::new(Notify::new());
+        let held = release.clone();
+        let router = Router::new()
+            .route(
+                "/hold",
+                get(move || {
+                    let held = held.clone();
+                    async move {
+                        held.notified().await;
+                        "done"
+                    }
+                }),
+            )
+            .route("/quick", get(|| async { "ok" }));
+        let config = LimitsConfig {
+            max_concurrent_requests: 1,
+            queue_timeout_ms: 50,
+            retry_after_seconds: 2,
+            ..LimitsConfig::default()
+        };
+        let app = shed(router, &config);
+        let get = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        let holding = tokio::spawn(app.clone().oneshot(get("/hold")));
+        tokio::task::yield_now().await;
+
+        let response = app.clone().oneshot(get("/quick")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "2");
+
+        // A queued request gets the slot once it's free
+        let queued = tokio::spawn(app.clone().oneshot(get("/quick")));
+        tokio::task::yield_now().await;
+        release.notify_one();
+        assert_eq!(holding.await.unwrap().unwrap().status(), StatusCode::OK);
+        assert_eq!(queued.await.unwrap().unwrap().status(), StatusCode::OK);
+
+        let response = app.oneshot(get("/quick")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
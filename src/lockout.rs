@@ -0,0 +1,486 @@
+use std::{collections::HashMap, net::IpAddr, sync::Mutex, time::Duration};
+
+use axum::http::{header, HeaderValue};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::time::Instant;
+
+use crate::{
+    audit::{self, models::AuditAction},
+    auth::Principal,
+    config::AuthLockoutConfig,
+    errors::AppError,
+    state::AppState,
+};
+
+// Audit entity type for lockouts
+pub const LOCKOUT_ENTITY: &str = "auth_lockout";
+
+// Lockouts are imposed by the server rather than by an authenticated caller
+const SYSTEM_ACTOR: &str = "system";
+
+// How many recorded failures between sweeps of stale entries
+const SWEEP_INTERVAL: u64 = 1024;
+
+/// A failed attempt as counted by the store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Failure {
+    /// Failures in the current window, including this one
+    pub failures: u32,
+    /// Set when this failure locked the key out
+    pub locked_for: Option<Duration>,
+}
+
+/// Storage for failed authentication attempts
+pub trait AuthFailureStore: Send + Sync {
+    /// How much longer `key` is locked out or cooling down after a failure, if it is
+    fn retry_after(&self, key: &str) -> Option<Duration>;
+
+    /// Count a failed attempt for `key`: past `delay_after_failures` it has to
+    /// cool down before the next attempt, and once there are too many it is
+    /// locked out
+    fn record_failure(&self, key: &str, config: &AuthLockoutConfig) -> Failure;
+
+    /// Forget the failures for `key`
+    fn reset(&self, key: &str);
+}
+
+#[derive(Debug)]
+struct Attempts {
+    failures: u32,
+    window_started: Instant,
+    locked_until: Option<Instant>,
+    // End of the cool-down earned by the last failure
+    retry_at: Option<Instant>,
+}
+
+impl Attempts {
+    fn new(now: Instant) -> Self {
+        Self {
+            failures: 0,
+            window_started: now,
+            locked_until: None,
+            retry_at: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Entries {
+    by_key: HashMap<String, Attempts>,
+    recorded: u64,
+}
+
+/// Failed attempts kept in memory; each instance counts them on its own
+#[derive(Default)]
+pub struct InMemoryAuthFailureStore {
+    entries: Mutex<Entries>,
+}
+
+impl AuthFailureStore for InMemoryAuthFailureStore {
+    fn retry_after(&self, key: &str) -> Option<Duration> {
+        let now = Instant::now();
+        let entries = self.entries.lock().expect("auth failure store lock poisoned");
+        let attempts = entries.by_key.get(key)?;
+        attempts
+            .locked_until
+            .max(attempts.retry_at)
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+    }
+
+    fn record_failure(&self, key: &str, config: &AuthLockoutConfig) -> Failure {
+        let now = Instant::now();
+        let window = Duration::from_secs(config.window_seconds);
+        let mut entries = self.entries.lock().expect("auth failure store lock poisoned");
+
+        entries.recorded += 1;
+        if entries.recorded.is_multiple_of(SWEEP_INTERVAL) {
+            entries.by_key.retain(|_, attempts| {
+                attempts.locked_until.max(attempts.retry_at).is_some_and(|until| until > now)
+                    || now.duration_since(attempts.window_started) < window
+            });
+        }
+
+        let attempts = entries
+            .by_key
+            .entry(key.to_string())
+            .or_insert_with(|| Attempts::new(now));
+        let lockout_over = attempts.locked_until.is_some_and(|locked_until| locked_until <= now);
+        if lockout_over || now.duration_since(attempts.window_started) >= window {
+            *attempts = Attempts::new(now);
+        }
+
+        attempts.failures = attempts.failures.saturating_add(1);
+        let cool_down = delay(config, attempts.failures);
+        // Durations are validated at startup; panicking here would poison the lock
+        if !cool_down.is_zero() {
+            attempts.retry_at = now.checked_add(cool_down);
+        }
+        let mut locked_for = None;
+        if attempts.locked_until.is_none() && attempts.failures >= config.max_failures.max(1) {
+            let lockout = Duration::from_secs(config.lockout_seconds);
+            attempts.locked_until = now.checked_add(lockout);
+            locked_for = attempts.locked_until.map(|_| lockout);
+        }
+
+        Failure {
+            failures: attempts.failures,
+            locked_for,
+        }
+    }
+
+    fn reset(&self, key: &str) {
+        let mut entries = self.entries.lock().expect("auth failure store lock poisoned");
+        entries.by_key.remove(key);
+    }
+}
+
+/// What the audit log records about a lockout
+#[derive(Serialize)]
+struct Lockout<'a> {
+    key: &'a str,
+    failures: u32,
+    locked_until: DateTime<Utc>,
+}
+
+/// One authentication attempt, tracked by client IP and by the credential it
+/// presented
+pub struct Attempt<'a> {
+    state: &'a AppState,
+    ip_key: Option<String>,
+    credential_key: Option<String>,
+}
+
+impl<'a> Attempt<'a> {
+    pub fn new(state: &'a AppState, ip: Option<IpAddr>, auth_header: Option<&str>) -> Self {
+        let config = &state.config.auth_lockout;
+        let mut attempt = Self {
+            state,
+            ip_key: None,
+            credential_key: None,
+        };
+        if config.enabled {
+            attempt.ip_key = ip.map(|ip| format!("ip:{}", ip));
+            if let Some(auth_header) = auth_header
+                && config.credential_prefix_len > 0
+            {
+                attempt.credential_key = Some(credential_key(auth_header, config.credential_prefix_len));
+            }
+        }
+        attempt
+    }
+
+    /// Reject the attempt with 429 while its IP is locked out or cooling down.
+    ///
+    /// This runs before the credential is checked, so a valid token is refused
+    /// too, e.g. from an IP shared with whoever caused the lockout. Letting
+    /// valid tokens through would tell a guesser which guess was right.
+    pub fn check(&self) -> Result<(), AppError> {
+        match self.ip_key.as_deref().and_then(|key| self.state.auth_failures.retry_after(key)) {
+            None => Ok(()),
+            Some(locked_for) => Err(refuse(locked_for)),
+        }
+    }
+
+    /// Clear the failures counted against the IP and credential
+    pub fn succeeded(&self) {
+        for key in self.keys() {
+            self.state.auth_failures.reset(key);
+        }
+    }
+
+    /// Count the failure. Attempts during the cool-down it earned are refused
+    /// by [`check`](Self::check) rather than held open, so they don't tie up a
+    /// connection or a request slot.
+    ///
+    /// A locked out or cooling down credential only refuses invalid tokens,
+    /// with 429 here. Many tokens share a prefix, so refusing valid ones would
+    /// let anyone lock every holder of a credential out from anywhere.
+    pub fn failed(&self) -> Result<(), AppError> {
+        let credential_locked_for = self
+            .credential_key
+            .as_deref()
+            .and_then(|key| self.state.auth_failures.retry_after(key));
+
+        let config = &self.state.config.auth_lockout;
+        for key in self.keys() {
+            if credential_locked_for.is_some() && Some(key) == self.credential_key.as_deref() {
+                continue;
+            }
+            let failure = self.state.auth_failures.record_failure(key, config);
+            if let Some(locked_for) = failure.locked_for {
+                self.locked_out(key, failure.failures, locked_for);
+            }
+        }
+
+        match credential_locked_for {
+            None => Ok(()),
+            Some(locked_for) => Err(refuse(locked_for)),
+        }
+    }
+
+    fn keys(&self) -> impl Iterator<Item = &str> {
+        self.ip_key.iter().chain(&self.credential_key).map(String::as_str)
+    }
+
+    fn locked_out(&self, key: &str, failures: u32, locked_for: Duration) {
+        tracing::warn!(
+            key,
+            failures,
+            lockout_seconds = locked_for.as_secs(),
+            "Locked out after repeated authentication failures"
+        );
+        let scope = key.split(':').next().unwrap_or_default();
+        crate::metrics::record_auth_lockout(if scope == "ip" { "ip" } else { "credential" });
+
+        let lockout = Lockout {
+            key,
+            failures,
+            locked_until: Utc::now() + chrono::Duration::from_std(locked_for).unwrap_or_default(),
+        };
+        let actor = Principal {
+            subject: SYSTEM_ACTOR.to_string(),
+        };
+        audit::record_committed(
+            self.state.audit.as_ref(),
+            &actor,
+            AuditAction::Lockout,
+            LOCKOUT_ENTITY,
+            key,
+            None,
+            Some(&lockout),
+        );
+    }
+}
+
+// 429 for an attempt refused while its IP or credential is locked out or cooling down
+fn refuse(locked_for: Duration) -> AppError {
+    crate::metrics::record_auth_failure("locked_out");
+    // Whole seconds, rounded up so callers don't retry too early
+    let seconds = locked_for.as_secs() + u64::from(locked_for.subsec_nanos() > 0);
+    AppError::too_many_requests("Too many failed authentication attempts, retry later")
+        .with_header(header::RETRY_AFTER, HeaderValue::from(seconds))
+}
+
+// Identify a credential by a digest of its first characters, so guesses at one
+// token are counted together without keeping any of it in memory
+fn credential_key(auth_header: &str, prefix_len: usize) -> String {
+    let token = auth_header.strip_prefix("Bearer ").unwrap_or(auth_header);
+    let prefix: String = token.chars().take(prefix_len).collect();
+    let digest: String = Sha256::digest(prefix.as_bytes())
+        .iter()
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("credential:{}", digest)
+}
+
+// Cool-down after a failure: none for the first few, then doubling
+fn delay(config: &AuthLockoutConfig, failures: u32) -> Duration {
+    if failures <= config.delay_after_failures {
+        return Duration::ZERO;
+    }
+    let doublings = (failures - config.delay_after_failures - 1).min(20);
+    let delay_ms = config.base_delay_ms.saturating_mul(1 << doublings);
+    Duration::from_millis(delay_ms.min(config.max_delay_ms))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    use axum::{
+        body::Body,
+        extract::{ConnectInfo, Request},
+        http::StatusCode,
+        middleware,
+        routing::get,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use crate::{audit::store::AuditFilter, auth, config::AppConfig};
+
+    fn config() -> AuthLockoutConfig {
+        AuthLockoutConfig {
+            max_failures: 3,
+            delay_after_failures: 1,
+            base_delay_ms: 100,
+            max_delay_ms: 150,
+            ..AuthLockoutConfig::default()
+        }
+    }
+
+    fn state(auth_lockout: AuthLockoutConfig) -> AppState {
+        AppState::new(AppConfig {
+            auth_lockout,
+            ..AppConfig::default()
+        })
+    }
+
+    fn app(state: AppState) -> Router {
+        Router::new()
+            .route("/protected", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(state, auth::auth_middleware))
+    }
+
+    fn request(peer: &str, token: &str) -> Request {
+        let mut request = Request::builder()
+            .uri("/protected")
+            .header(header::AUTHORIZATION, token)
+            .body(Body::empty())
+            .unwrap();
+        let peer: SocketAddr = peer.parse().unwrap();
+        request.extensions_mut().insert(ConnectInfo(peer));
+        request
+    }
+
+    #[test]
+    fn test_delay_doubles_up_to_max() {
+        let config = config();
+        assert_eq!(delay(&config, 1), Duration::ZERO);
+        assert_eq!(delay(&config, 2), Duration::from_millis(100));
+        assert_eq!(delay(&config, 3), Duration::from_millis(150));
+        assert_eq!(delay(&config, u32::MAX), Duration::from_millis(150));
+    }
+
+    #[test]
+    fn test_credential_key_uses_prefix() {
+        assert_eq!(
+            credential_key("Bearer abcdefgh-one", 8),
+            credential_key("Bearer abcdefgh-two", 8)
+        );
+        assert_ne!(credential_key("Bearer abcdefgh", 8), credential_key("Bearer zbcdefgh", 8));
+        assert!(!credential_key("Bearer abcdefgh", 8).contains("abcd"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_store_locks_out_and_expires() {
+        let store = InMemoryAuthFailureStore::default();
+        let config = config();
+
+        assert_eq!(store.record_failure("ip:1", &config).locked_for, None);
+        assert_eq!(store.record_failure("ip:1", &config).locked_for, None);
+        let failure = store.record_failure("ip:1", &config);
+        assert_eq!(failure.failures, 3);
+        assert_eq!(failure.locked_for, Some(Duration::from_secs(900)));
+        assert_eq!(store.retry_after("ip:1"), Some(Duration::from_secs(900)));
+        assert_eq!(store.retry_after("ip:2"), None);
+
+        tokio::time::advance(Duration::from_secs(900)).await;
+        assert_eq!(store.retry_after("ip:1"), None);
+        assert_eq!(store.record_failure("ip:1", &config).failures, 1);
+
+        // Failures outside the window are forgotten
+        tokio::time::advance(Duration::from_secs(600)).await;
+        assert_eq!(store.record_failure("ip:1", &config).failures, 1);
+
+        store.reset("ip:1");
+        assert_eq!(store.record_failure("ip:1", &config).failures, 1);
+    }
+
+    #[tokio::test]
+    async fn test_store_survives_huge_durations() {
+        let store = InMemoryAuthFailureStore::default();
+        let config = AuthLockoutConfig {
+            max_failures: 1,
+            lockout_seconds: u64::MAX,
+            base_delay_ms: u64::MAX,
+            max_delay_ms: u64::MAX,
+            ..config()
+        };
+
+        // Too long to lock out for, but the store keeps working
+        assert_eq!(store.record_failure("ip:1", &config).locked_for, None);
+        assert_eq!(store.record_failure("ip:1", &config).failures, 2);
+        store.reset("ip:1");
+        assert_eq!(store.retry_after("ip:1"), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_repeated_failures_lock_out() {
+        let state = state(config());
+        let app = app(state.clone());
+
+        let started = Instant::now();
+        for attempt in 0..2 {
+            let token = format!("Bearer guess-{}", attempt);
+            let response = app.clone().oneshot(request("10.0.0.1:1000", &token)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        // Failures are answered at once
+        assert_eq!(started.elapsed(), Duration::ZERO);
+
+        // The second failure earned a cool-down, which is refused rather than waited out
+        let response = app.clone().oneshot(request("10.0.0.1:1000", "Bearer guess-2")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "1");
+
+        tokio::time::advance(Duration::from_millis(100)).await;
+        let response = app.clone().oneshot(request("10.0.0.1:1000", "Bearer guess-2")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Locked out, even with the right token
+        let response = app.clone().oneshot(request("10.0.0.1:1000", auth::DEV_TOKEN)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "900");
+
+        // Other IPs can still sign in
+        let response = app.clone().oneshot(request("10.0.0.2:1000", auth::DEV_TOKEN)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let page = state
+            .audit
+            .query(&AuditFilter {
+                entity_type: Some(LOCKOUT_ENTITY.to_string()),
+                limit: 10,
+                ..AuditFilter::default()
+            })
+            .unwrap();
+        assert_eq!(page.events.len(), 1);
+        assert_eq!(page.events[0].entity_id, "ip:10.0.0.1");
+        assert_eq!(page.events[0].action, AuditAction::Lockout);
+        assert_eq!(page.events[0].actor, SYSTEM_ACTOR);
+
+        tokio::time::advance(Duration::from_secs(900)).await;
+        let response = app.oneshot(request("10.0.0.1:1000", auth::DEV_TOKEN)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_credential_lockout_lets_valid_tokens_through() {
+        let app = app(state(config()));
+
+        // The same token prefix from many IPs, waiting out each cool-down
+        for peer in ["10.0.0.1:1000", "10.0.0.2:1000", "10.0.0.3:1000"] {
+            let response = app.clone().oneshot(request(peer, "Bearer dev_toke?")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            tokio::time::advance(Duration::from_millis(150)).await;
+        }
+
+        // Further guesses at the credential are refused
+        let response = app.clone().oneshot(request("10.0.0.4:1000", "Bearer dev_toke!")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "900");
+
+        // But its holders can still sign in from another IP
+        let response = app.oneshot(request("10.0.0.5:1000", auth::DEV_TOKEN)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_disabled() {
+        let app = app(state(AuthLockoutConfig {
+            enabled: false,
+            ..config()
+        }));
+
+        for _ in 0..5 {
+            let response = app.clone().oneshot(request("10.0.0.1:1000", "Bearer wrong")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+}
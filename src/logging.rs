@@ -0,0 +1,651 @@
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tokio::time::Instant;
+use tracing::{field::Field, Event, Subscriber};
+use tracing_appender::{non_blocking::WorkerGuard, rolling};
+use tracing_subscriber::{
+    field::{RecordFields, Visit},
+    fmt::{
+        format::Writer,
+        time::{FormatTime, UtcTime},
+        FmtContext, FormatEvent, FormatFields, FormattedFields, MakeWriter,
+    },
+    prelude::*,
+    layer::Layered,
+    registry::LookupSpan,
+    reload, EnvFilter, Layer, Registry,
+};
+
+use crate::config::{AppConfig, LogFileConfig, LogFormat, LogRotation, LoggingConfig};
+
+type FilteredRegistry = Layered<reload::Layer<EnvFilter, Registry>, Registry>;
+type BoxedLayer = Box<dyn Layer<FilteredRegistry> + Send + Sync>;
+
+/// Handles returned from [`init`] that must outlive the subscriber
+pub struct LoggingHandles {
+    /// Flush file output on drop, so keep them alive for the lifetime of the process
+    pub guards: Vec<WorkerGuard>,
+    pub levels: LogLevelController,
+    /// Present when OpenTelemetry export is enabled; shut it down to flush spans
+    pub tracer_provider: Option<SdkTracerProvider>,
+}
+
+/// Install the global tracing subscriber described by the `[logging]` config
+pub fn init(config: &AppConfig) -> anyhow::Result<LoggingHandles> {
+    let logging = &config.logging;
+    let format = resolve_format(logging, &config.run_mode);
+
+    let mut layers: Vec<BoxedLayer> = Vec::new();
+    let mut guards = Vec::new();
+
+    if logging.stdout {
+        let ansi = io::stdout().is_terminal();
+        layers.push(fmt_layer(format, logging.timestamps, ansi, io::stdout));
+    }
+
+    if let Some(file) = &logging.file {
+        let (writer, guard) = tracing_appender::non_blocking(file_writer(file)?);
+        layers.push(fmt_layer(format, logging.timestamps, false, writer));
+        guards.push(guard);
+    }
+
+    let tracer_provider = crate::telemetry::init(&config.otel)?;
+    if let Some(provider) = &tracer_provider {
+        layers.push(crate::telemetry::layer(provider).boxed());
+    }
+
+    let (subscriber, levels) = subscriber(env_filter(logging)?, layers)?;
+    subscriber.try_init()?;
+
+    Ok(LoggingHandles {
+        guards,
+        levels,
+        tracer_provider,
+    })
+}
+
+// Put the filter in front of every output. Inside the layer vec it would only be
+// one more layer, and wouldn't stop the others from seeing each event.
+fn subscriber(
+    filter: EnvFilter,
+    layers: Vec<BoxedLayer>,
+) -> anyhow::Result<(impl Subscriber + Send + Sync, LogLevelController)> {
+    // Wrap the filter in a reload layer so the level can be changed at runtime
+    let (filter, reload_handle) = reload::Layer::new(filter);
+    let levels = LogLevelController::new(reload_handle)?;
+    let subscriber = tracing_subscriber::registry().with(filter).with(layers);
+    Ok((subscriber, levels))
+}
+
+/// Pick the configured format, defaulting to human-readable output for local runs
+pub fn resolve_format(logging: &LoggingConfig, run_mode: &str) -> LogFormat {
+    match logging.format {
+        Some(format) => format,
+        None if run_mode == "local" => LogFormat::Pretty,
+        None => LogFormat::Json,
+    }
+}
+
+// `RUST_LOG` wins over the configured directives so one-off overrides still work
+fn env_filter(logging: &LoggingConfig) -> anyhow::Result<EnvFilter> {
+    match EnvFilter::try_from_default_env() {
+        Ok(filter) => Ok(filter),
+        Err(_) => Ok(EnvFilter::try_new(&logging.level)?),
+    }
+}
+
+/// Snapshot of the active log filter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogLevelStatus {
+    pub directives: String,
+    /// Directives the filter will revert to once the TTL expires
+    pub revert_to: Option<String>,
+    pub ttl_remaining: Option<Duration>,
+}
+
+/// Changes the global log filter at runtime, optionally reverting after a TTL
+#[derive(Clone)]
+pub struct LogLevelController {
+    handle: reload::Handle<EnvFilter, Registry>,
+    state: Arc<Mutex<LevelState>>,
+}
+
+struct LevelState {
+    // Bumped on every change so stale TTL timers don't revert newer changes
+    generation: u64,
+    revert: Option<(String, Instant)>,
+}
+
+impl LogLevelController {
+    pub fn new(handle: reload::Handle<EnvFilter, Registry>) -> anyhow::Result<Self> {
+        // Fail early if the filter has already been dropped
+        handle.with_current(|_| ())?;
+
+        Ok(Self {
+            handle,
+            state: Arc::new(Mutex::new(LevelState {
+                generation: 0,
+                revert: None,
+            })),
+        })
+    }
+
+    /// Returns the directives currently in effect
+    pub fn status(&self) -> anyhow::Result<LogLevelStatus> {
+        let directives = self.handle.with_current(|filter| filter.to_string())?;
+        let state = self.state.lock().unwrap();
+        let (revert_to, ttl_remaining) = match &state.revert {
+            Some((previous, at)) => (
+                Some(previous.clone()),
+                Some(at.saturating_duration_since(Instant::now())),
+            ),
+            None => (None, None),
+        };
+
+        Ok(LogLevelStatus {
+            directives,
+            revert_to,
+            ttl_remaining,
+        })
+    }
+
+    /// Replace the filter directives, reverting to the previous ones after `ttl` if given.
+    ///
+    /// Must be called from within a Tokio runtime when a TTL is used.
+    pub fn set(&self, directives: &str, ttl: Option<Duration>) -> anyhow::Result<LogLevelStatus> {
+        let filter = EnvFilter::try_new(directives)?;
+        let revert_at = match ttl {
+            Some(ttl) => Some(Instant::now().checked_add(ttl).context("TTL is too long")?),
+            None => None,
+        };
+
+        let generation = {
+            let mut state = self.state.lock().unwrap();
+
+            // Chained temporary changes still revert to the last permanent directives
+            let previous = match state.revert.take() {
+                Some((previous, _)) => previous,
+                None => self.handle.with_current(|filter| filter.to_string())?,
+            };
+
+            self.handle.reload(filter)?;
+            state.generation += 1;
+            state.revert = revert_at.map(|at| (previous, at));
+            state.generation
+        };
+
+        if let Some(ttl) = ttl {
+            let controller = self.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(ttl).await;
+                controller.revert(generation);
+            });
+        }
+
+        tracing::info!(directives, ttl_seconds = ttl.map(|ttl| ttl.as_secs()), "Log level changed");
+
+        self.status()
+    }
+
+    // Restore the directives saved by `set` unless a newer change superseded them
+    fn revert(&self, generation: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.generation != generation {
+            return;
+        }
+
+        if let Some((previous, _)) = state.revert.take() {
+            match EnvFilter::try_new(&previous).map(|filter| self.handle.reload(filter)) {
+                Ok(Ok(())) => tracing::info!(directives = %previous, "Log level reverted"),
+                _ => tracing::error!(directives = %previous, "Failed to revert log level"),
+            }
+        }
+    }
+}
+
+// Build the formatting layer for the chosen format and writer
+fn fmt_layer<W>(format: LogFormat, timestamps: bool, ansi: bool, writer: W) -> BoxedLayer
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let base = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    let timer = UtcTime::rfc_3339();
+
+    match (format, timestamps) {
+        (LogFormat::Json, true) => base
+            .json()
+            .with_timer(timer)
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+        (LogFormat::Json, false) => base
+            .json()
+            .without_time()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+        (LogFormat::Pretty, true) => base.pretty().with_timer(timer).boxed(),
+        (LogFormat::Pretty, false) => base.pretty().without_time().boxed(),
+        (LogFormat::Compact, true) => base.compact().with_timer(timer).boxed(),
+        (LogFormat::Compact, false) => base.compact().without_time().boxed(),
+        (LogFormat::Logfmt, timestamps) => base
+            .event_format(Logfmt { timestamps })
+            .fmt_fields(LogfmtFields)
+            .boxed(),
+    }
+}
+
+// Open the file writer for the configured rotation strategy
+fn file_writer(file: &LogFileConfig) -> anyhow::Result<Box<dyn Write + Send>> {
+    fs::create_dir_all(&file.directory)?;
+
+    let writer: Box<dyn Write + Send> = match file.rotation {
+        LogRotation::Never => Box::new(rolling::never(&file.directory, &file.file_name)),
+        LogRotation::Daily => Box::new(rolling::daily(&file.directory, &file.file_name)),
+        LogRotation::Size => Box::new(SizeRotatingWriter::new(
+            Path::new(&file.directory).join(&file.file_name),
+            file.max_size_bytes,
+            file.max_files,
+        )?),
+    };
+
+    Ok(writer)
+}
+
+/// A file writer that rotates once the file reaches `max_size` bytes.
+///
+/// Rotated files are renamed `<name>.1` (newest) through `<name>.<max_files>` (oldest).
+pub struct SizeRotatingWriter {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    written: u64,
+}
+
+impl SizeRotatingWriter {
+    pub fn new(path: PathBuf, max_size: u64, max_files: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            max_size,
+            max_files,
+            file,
+            written,
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        if self.max_files > 0 {
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.written = 0;
+
+        Ok(())
+    }
+}
+
+impl Write for SizeRotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Formats events as logfmt: `ts=... level=info target=... msg="..." key=value`
+pub struct Logfmt {
+    timestamps: bool,
+}
+
+impl<S, N> FormatEvent<S, N> for Logfmt
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+
+        if self.timestamps {
+            writer.write_str("ts=")?;
+            UtcTime::rfc_3339().format_time(&mut writer)?;
+            writer.write_char(' ')?;
+        }
+
+        write!(
+            writer,
+            "level={} target={} ",
+            metadata.level().as_str().to_ascii_lowercase(),
+            metadata.target()
+        )?;
+        ctx.format_fields(writer.by_ref(), event)?;
+
+        // Span fields were already formatted as logfmt when the span was recorded
+        if let Some(scope) = ctx.event_scope() {
+            for span in scope.from_root() {
+                let extensions = span.extensions();
+                if let Some(fields) = extensions.get::<FormattedFields<N>>()
+                    && !fields.is_empty()
+                {
+                    write!(writer, " {}", fields)?;
+                }
+            }
+        }
+
+        writeln!(writer)
+    }
+}
+
+/// Formats span and event fields as logfmt `key=value` pairs
+pub struct LogfmtFields;
+
+impl<'writer> FormatFields<'writer> for LogfmtFields {
+    fn format_fields<R: RecordFields>(&self, writer: Writer<'writer>, fields: R) -> fmt::Result {
+        let mut visitor = LogfmtVisitor {
+            writer,
+            result: Ok(()),
+            first: true,
+        };
+        fields.record(&mut visitor);
+        visitor.result
+    }
+}
+
+struct LogfmtVisitor<'writer> {
+    writer: Writer<'writer>,
+    result: fmt::Result,
+    first: bool,
+}
+
+impl LogfmtVisitor<'_> {
+    fn write_pair(&mut self, field: &Field, value: &str) {
+        if self.result.is_err() {
+            return;
+        }
+
+        let key = match field.name() {
+            "message" => "msg",
+            name => name,
+        };
+        let separator = if self.first { "" } else { " " };
+        self.first = false;
+
+        // Quote values that would otherwise break the key=value framing
+        let needs_quotes = value.is_empty()
+            || value
+                .chars()
+                .any(|c| c.is_whitespace() || c == '"' || c == '=' || c.is_control());
+
+        self.result = if needs_quotes {
+            write!(self.writer, "{}{}={:?}", separator, key, value)
+        } else {
+            write!(self.writer, "{}{}={}", separator, key, value)
+        };
+    }
+}
+
+impl Visit for LogfmtVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.write_pair(field, value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.write_pair(field, &format!("{:?}", value));
+    }
+}
+
+// Captures formatted log output so tests can inspect it
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct CapturedLogs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl CapturedLogs {
+    pub fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+#[cfg(test)]
+impl Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl<'a> MakeWriter<'a> for CapturedLogs {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+// A subscriber filtered like the real one, writing to captured logs, with the
+// controller for its level. Install it with `tracing::dispatcher::set_default`;
+// the controller stops working once this is dropped.
+#[cfg(test)]
+pub struct TestLogging {
+    pub dispatch: tracing::Dispatch,
+    pub levels: LogLevelController,
+    pub logs: CapturedLogs,
+}
+
+#[cfg(test)]
+impl TestLogging {
+    pub fn new(directives: &str) -> Self {
+        let logs = CapturedLogs::default();
+        let layers = vec![fmt_layer(LogFormat::Logfmt, false, false, logs.clone())];
+        let (subscriber, levels) = subscriber(EnvFilter::new(directives), layers).unwrap();
+        Self {
+            dispatch: tracing::Dispatch::new(subscriber),
+            levels,
+            logs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capture(format: LogFormat, timestamps: bool) -> String {
+        let logs = CapturedLogs::default();
+        let layers = vec![fmt_layer(format, timestamps, false, logs.clone())];
+        let (subscriber, _levels) = subscriber(EnvFilter::new("trace"), layers).unwrap();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", request_id = "abc");
+            let _entered = span.enter();
+            tracing::info!(user = "alice", count = 3, "hello world");
+        });
+
+        logs.contents()
+    }
+
+    #[test]
+    fn test_level_filters_every_output() {
+        let logs = CapturedLogs::default();
+        let other = CapturedLogs::default();
+        let layers = vec![
+            fmt_layer(LogFormat::Logfmt, false, false, logs.clone()),
+            fmt_layer(LogFormat::Logfmt, false, false, other.clone()),
+        ];
+        let (subscriber, _levels) = subscriber(EnvFilter::new("warn"), layers).unwrap();
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("debug event");
+            tracing::info!("info event");
+            tracing::warn!("warn event");
+        });
+
+        for output in [logs.contents(), other.contents()] {
+            assert!(!output.contains("debug event"));
+            assert!(!output.contains("info event"));
+            assert!(output.contains("warn event"));
+        }
+    }
+
+    #[test]
+    fn test_default_format_depends_on_run_mode() {
+        let logging = LoggingConfig::default();
+        assert_eq!(resolve_format(&logging, "local"), LogFormat::Pretty);
+        assert_eq!(resolve_format(&logging, "production"), LogFormat::Json);
+
+        let logging = LoggingConfig {
+            format: Some(LogFormat::Compact),
+            ..LoggingConfig::default()
+        };
+        assert_eq!(resolve_format(&logging, "local"), LogFormat::Compact);
+    }
+
+    #[test]
+    fn test_logfmt_output() {
+        let output = capture(LogFormat::Logfmt, false);
+
+        assert_eq!(
+            output.trim_end(),
+            "level=info target=axum_starter::logging::tests msg=\"hello world\" user=alice count=3 request_id=abc"
+        );
+    }
+
+    #[test]
+    fn test_logfmt_timestamps() {
+        let output = capture(LogFormat::Logfmt, true);
+
+        assert!(output.starts_with("ts="));
+        assert!(output.contains(" level=info "));
+    }
+
+    #[test]
+    fn test_json_output_without_timestamps() {
+        let output = capture(LogFormat::Json, false);
+        let event: serde_json::Value = serde_json::from_str(output.trim_end()).unwrap();
+
+        assert!(event.get("timestamp").is_none());
+        assert_eq!(event["fields"]["message"], "hello world");
+        assert_eq!(event["span"]["request_id"], "abc");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_log_level_controller() {
+        let logging = TestLogging::new("info");
+        let controller = logging.levels.clone();
+
+        assert_eq!(controller.status().unwrap().directives, "info");
+
+        // Permanent change
+        let status = controller.set("warn", None).unwrap();
+        assert_eq!(status.directives, "warn");
+        assert_eq!(status.revert_to, None);
+
+        // Temporary change reverts after the TTL
+        let status = controller
+            .set("axum_starter=trace", Some(Duration::from_secs(60)))
+            .unwrap();
+        assert_eq!(status.directives, "axum_starter=trace");
+        assert_eq!(status.revert_to.as_deref(), Some("warn"));
+        assert_eq!(status.ttl_remaining, Some(Duration::from_secs(60)));
+
+        tokio::time::sleep(Duration::from_secs(61)).await;
+        let status = controller.status().unwrap();
+        assert_eq!(status.directives, "warn");
+        assert_eq!(status.revert_to, None);
+
+        // A TTL past the end of time is refused without changing anything
+        assert!(controller.set("debug", Some(Duration::MAX)).is_err());
+        assert_eq!(controller.status().unwrap().directives, "warn");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_newer_change_cancels_revert() {
+        let logging = TestLogging::new("info");
+        let controller = logging.levels.clone();
+
+        controller.set("debug", Some(Duration::from_secs(10))).unwrap();
+        controller.set("error", None).unwrap();
+
+        tokio::time::sleep(Duration::from_secs(11)).await;
+        assert_eq!(controller.status().unwrap().directives, "error");
+    }
+
+    #[test]
+    fn test_invalid_directives_are_rejected() {
+        let logging = TestLogging::new("info");
+        let controller = logging.levels.clone();
+
+        assert!(controller.set("axum_starter=loud", None).is_err());
+        assert_eq!(controller.status().unwrap().directives, "info");
+    }
+
+    #[test]
+    fn test_size_rotating_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let mut writer = SizeRotatingWriter::new(path.clone(), 10, 2).unwrap();
+
+        writer.write_all(b"first-line\n").unwrap();
+        writer.write_all(b"second-line\n").unwrap();
+        writer.write_all(b"third-line\n").unwrap();
+        writer.write_all(b"fourth-line\n").unwrap();
+        writer.flush().unwrap();
+
+        // Only the newest file plus `max_files` rotated files are kept
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth-line\n");
+        assert_eq!(fs::read_to_string(dir.path().join("app.log.1")).unwrap(), "third-line\n");
+        assert_eq!(fs::read_to_string(dir.path().join("app.log.2")).unwrap(), "second-line\n");
+        assert!(!dir.path().join("app.log.3").exists());
+    }
+}
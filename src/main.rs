@@ -1,9 +1,29 @@
 mod health;
 mod clients;
+mod admin;
+mod audit;
+mod compression;
 mod config;
+mod cors;
 mod errors;
 mod auth;
 mod openapi;
+mod rate_limit;
+mod security_headers;
+mod request_id;
+mod client_ip;
+mod etag;
+mod events;
+mod idempotency;
+mod limits;
+mod lockout;
+mod http_trace;
+mod logging;
+mod metrics;
+mod telemetry;
+mod state;
+mod webhooks;
+mod ws;
 
 use std::{net::SocketAddr, panic::AssertUnwindSafe};
 use axum::{
@@ -13,9 +33,11 @@ use axum::{
     extract::Request,
     http::StatusCode,
 };
-use tracing_subscriber::{fmt, EnvFilter, prelude::*};
 
 use crate::errors::{AppError, handle_panic};
+use crate::request_id::PropagateRequestId;
+use crate::telemetry::PropagateTraceContext;
+use crate::state::AppState;
 
 // Fallback handler for 404 errors
 async fn handle_404() -> impl IntoResponse {
@@ -36,7 +58,8 @@ async fn panic_handler(
     match response {
         Ok(future) => {
             // This runs the future if the closure didn't panic
-            match tokio::task::spawn(future).await {
+            // Keep the request ID in scope inside the spawned task
+            match tokio::task::spawn(request_id::scoped(future)).await {
                 Ok(response) => Ok(response),
                 Err(_) => Ok(handle_panic(Box::new("Task failed"))),
             }
@@ -49,39 +72,91 @@ async fn panic_handler(
 }
 
 // Build the application router
-pub fn app() -> Router {
-    Router::new()
+pub fn app(state: AppState) -> Router {
+    let metrics_config = &state.config.metrics;
+
+    let mut router = Router::new()
         // Original routes
         .merge(health::routes::routes())
-        .merge(clients::routes::routes())
+        .merge(route_group(clients::routes::routes(state.clone()), &state, "public"))
         // API routes with proper nesting
-        .nest("/api", api_routes())
+        .nest("/api", api_routes(state.clone()));
+
+    // Serve metrics here unless they have their own admin port
+    if metrics_config.enabled && metrics_config.listen_addr.is_none() {
+        router = router.merge(crate::metrics::routes(&metrics_config.path));
+    }
+
+    router = router
         // Add 404 fallback
         .fallback(handle_404)
         // Add middleware with panic recovery
-        .layer(middleware::from_fn(panic_handler))
+        .layer(middleware::from_fn(panic_handler));
+    // Inside CORS so shed requests carry CORS headers too
+    router = limits::shed(router, &state.config.limits);
+    router = compression::apply(router, &state.config.compression);
+
+    // Outside auth so preflights are answered, and so errors carry CORS headers too
+    if let Some(cors) = cors::layer(&state.config) {
+        router = router.layer(cors);
+    }
+    router = security_headers::apply(router, &state.config.security_headers);
+
+    if metrics_config.enabled {
+        crate::metrics::install();
+        router = router.layer(middleware::from_fn(crate::metrics::metrics_middleware));
+    }
+
+    router
+        // Assign request IDs outside panic recovery so 500s carry them too
+        .layer(middleware::from_fn(request_id::request_id_middleware))
+        // Open the per-request span outermost so everything below logs inside it
+        .layer(middleware::from_fn_with_state(state, http_trace::trace_middleware))
+}
+
+// Apply the rate limit, timeouts and body size limit of a route group
+fn route_group(router: Router, state: &AppState, group: &'static str) -> Router {
+    limits::limit(rate_limit::limit(router, state, group), &state.config.limits, group)
+}
+
+// A route group behind auth. Idempotency sits inside the group's limits, so
+// buffering a keyed request body is bound by the group's body timeout and size.
+fn secured_group(router: Router, state: &AppState, group: &'static str) -> Router {
+    let router = router.layer(middleware::from_fn_with_state(
+        state.clone(),
+        idempotency::idempotency_middleware,
+    ));
+    route_group(router, state, group)
 }
 
 // Helper function to create secured routes
-fn secured_routes() -> Router {
-    Router::new()
-        .nest("/clients", clients::routes::api_routes())
+fn secured_routes(state: AppState) -> Router {
+    let router = Router::new()
+        // Groups sit inside auth so callers are rate limited and idempotency
+        // keys are scoped by principal
+        .nest("/clients", secured_group(clients::routes::api_routes(state.clone()), &state, "clients"))
+        .nest("/admin", secured_group(admin::routes::api_routes(state.clone()), &state, "admin"))
+        .nest("/audit", secured_group(audit::routes::api_routes(state.clone()), &state, "audit"))
+        .nest("/webhooks", secured_group(webhooks::routes::api_routes(state.clone()), &state, "webhooks"))
         // Additional secured routes can be added here
         // For example:
-        // .nest("/admin", admin::routes::api_routes())
         // .nest("/users", users::routes::api_routes())
-        .layer(middleware::from_fn(auth::auth_middleware))
+        .layer(middleware::from_fn_with_state(state.clone(), auth::auth_middleware));
+    // Requests without a token never reach the group limits, so limit them by IP
+    rate_limit::limit_anonymous(router, &state, "public")
 }
 
 // Define API routes
-fn api_routes() -> Router {
+fn api_routes(state: AppState) -> Router {
     Router::new()
         // Public routes don't need authentication
         .nest("/health", health::routes::api_routes())
-        // Secured routes that require authentication
-        .merge(secured_routes())
+        // Authenticates during the handshake, since browsers can't set headers on it
+        .nest("/ws", route_group(ws::routes::api_routes(state.clone()), &state, "ws"))
         // OpenAPI documentation
-        .merge(openapi::routes())
+        .merge(openapi::routes(&state.config.security_headers))
+        // Secured routes that require authentication
+        .merge(secured_routes(state))
 }
 
 #[tokio::main]
@@ -95,29 +170,18 @@ async fn main() {
         return;
     }
     
-    // Initialize JSON tracing
-    let env_filter = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new("axum_starter=debug,tower_http=debug"));
-    
-    // Configure JSON formatter
-    let json_layer = fmt::layer()
-        .json()
-        .with_timer(fmt::time::UtcTime::rfc_3339())
-        .with_current_span(true)
-        .with_span_list(true);
-    
-    // Set up tracing
-    tracing_subscriber::registry()
-        .with(env_filter)
-        .with(json_layer)
-        .init();
-        
-    // Load configuration
+    // Load configuration before logging, since it decides the log format
     let config = config::load_config().unwrap_or_else(|err| {
-        tracing::error!("Failed to load configuration: {}", err);
+        eprintln!("Failed to load configuration: {}", err);
         std::process::exit(1);
     });
-    
+
+    // Set up tracing; the guards flush file output when main returns
+    let logging = logging::init(&config).unwrap_or_else(|err| {
+        eprintln!("Failed to initialize logging: {}", err);
+        std::process::exit(1);
+    });
+
     // Log successful configuration load
     tracing::info!(
         run_mode = %config.run_mode,
@@ -126,7 +190,35 @@ async fn main() {
     );
 
     // Build our application
-    let app = app();
+    let _log_guards = logging.guards;
+    let tracer_provider = logging.tracer_provider;
+
+    // Optionally serve metrics on a separate admin port
+    if config.metrics.enabled
+        && let Some(metrics_addr) = &config.metrics.listen_addr
+    {
+        let listener = tokio::net::TcpListener::bind(metrics_addr)
+            .await
+            .unwrap_or_else(|err| {
+                tracing::error!(address = %metrics_addr, "Failed to bind metrics listener: {}", err);
+                std::process::exit(1);
+            });
+        let metrics_app = crate::metrics::routes(&config.metrics.path);
+        tracing::debug!(address = %metrics_addr, "Metrics listening");
+        tokio::spawn(async move { axum::serve(listener, metrics_app).await });
+    }
+
+    let state = AppState::new(config).with_log_levels(logging.levels);
+
+    // Hard-delete soft-deleted clients once their retention period is over
+    clients::purge::spawn(state.clone());
+
+    // Send queued client events to webhook endpoints, retrying failures
+    if state.config.webhooks.enabled {
+        webhooks::delivery::spawn(state.clone());
+    }
+
+    let app = app(state);
 
     // Run the server
     let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
@@ -137,7 +229,52 @@ async fn main() {
     // Log startup complete
     tracing::info!("Startup complete - server ready to accept connections");
     
-    axum::serve(listener, app).await.unwrap();
+    // Record peer addresses so the trace layer can log client IPs
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
+
+    // Flush any spans still waiting to be exported
+    if let Some(provider) = tracer_provider {
+        tokio::task::spawn_blocking(move || provider.shutdown())
+            .await
+            .ok();
+    }
+}
+
+// Resolve when the process is asked to stop, by Ctrl+C or by SIGTERM from
+// docker stop, Kubernetes and systemd
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl+C: {}", err);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(err) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", err);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    tracing::info!("Shutdown signal received");
 }
 
 // Run a health check by making a request to the health endpoint
@@ -147,7 +284,13 @@ async fn run_health_check() {
     
     println!("Checking service health at {}...", url);
     
-    match client.get(url).send().await {
+    match client
+        .get(url)
+        .propagate_request_id()
+        .propagate_trace_context()
+        .send()
+        .await
+    {
         Ok(response) => {
             if response.status().is_success() {
                 println!("Service is running");
@@ -174,7 +317,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_original_health_endpoint() {
-        let app = app();
+        let app = app(AppState::default());
         let response = app
             .oneshot(Request::builder().uri("/health").body(Body::empty()).unwrap())
             .await
@@ -185,7 +328,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_original_clients_endpoint() {
-        let app = app();
+        let app = app(AppState::default());
 
         let response = app
             .oneshot(Request::builder().uri("/clients").body(Body::empty()).unwrap())
@@ -197,7 +340,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_api_health_endpoint() {
-        let app = app();
+        let app = app(AppState::default());
 
         let response = app
             .oneshot(Request::builder().uri("/api/health").body(Body::empty()).unwrap())
@@ -209,7 +352,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_api_clients_endpoint() {
-        let app = app();
+        let app = app(AppState::default());
 
         // Need to include auth token for secured routes
         let response = app
@@ -229,7 +372,7 @@ mod tests {
     // Test for not found (404) response
     #[tokio::test]
     async fn test_not_found() {
-        let app = app();
+        let app = app(AppState::default());
 
         // Request to a non-existent endpoint
         let response = app
@@ -249,10 +392,35 @@ mod tests {
         assert_eq!(body["error"]["message"], "Route not found");
     }
     
+    // Test that error responses from the full app carry the request ID
+    #[tokio::test]
+    async fn test_not_found_includes_request_id() {
+        let app = app(AppState::default());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/nonexistent")
+                    .header("x-request-id", "test-request-id")
+                    .body(Body::empty())
+                    .unwrap()
+            )
+            .await
+            .unwrap();
+
+        // The ID is echoed in the headers
+        assert_eq!(response.headers()["x-request-id"], "test-request-id");
+
+        // And included in the error body
+        let body_bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body_bytes).unwrap();
+        assert_eq!(body["error"]["request_id"], "test-request-id");
+    }
+
     // Test secured route with no authentication
     #[tokio::test]
     async fn test_secured_route_no_auth() {
-        let app = app();
+        let app = app(AppState::default());
 
         // Request to secured endpoint without token
         let response = app
@@ -267,7 +435,7 @@ mod tests {
     // Test secured route with invalid token
     #[tokio::test]
     async fn test_secured_route_invalid_token() {
-        let app = app();
+        let app = app(AppState::default());
 
         // Request to secured endpoint with invalid token
         let response = app
@@ -288,7 +456,7 @@ mod tests {
     // Test secured route with valid token
     #[tokio::test]
     async fn test_secured_route_valid_token() {
-        let app = app();
+        let app = app(AppState::default());
 
         // Request to secured endpoint with valid token
         let response = app
@@ -309,8 +477,8 @@ mod tests {
         let body_bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
         let body: Value = serde_json::from_slice(&body_bytes).unwrap();
 
-        // Check it contains the expected message
-        assert_eq!(body["message"], "Clients endpoint");
+        // Check it lists the stored clients
+        assert_eq!(body["data"][0]["name"], "Example Client");
     }
 
     // This is a test of the request 404 handler, not the panic handler
@@ -337,10 +505,181 @@ mod tests {
         assert_eq!(body["error"]["message"], "Route not found");
     }
     
+    // Test that the admin routes are secured
+    #[tokio::test]
+    async fn test_admin_log_level_requires_auth() {
+        let app = app(AppState::default());
+
+        let response = app
+            .oneshot(Request::builder().uri("/api/admin/log-level").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    // Test the admin log level endpoint when logging was not initialized by main
+    #[tokio::test]
+    async fn test_admin_log_level_unavailable() {
+        let app = app(AppState::default());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/admin/log-level")
+                    .header("Authorization", auth::DEV_TOKEN)
+                    .body(Body::empty())
+                    .unwrap()
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    // Test that client changes land in the audit log with the actor and request ID
+    #[tokio::test]
+    async fn test_audit_log_records_client_changes() {
+        let app = app(AppState::default());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/clients")
+                    .header("Authorization", auth::DEV_TOKEN)
+                    .header("Content-Type", "application/json")
+                    .header("x-request-id", "audit-test-id")
+                    .body(Body::from(r#"{"name":"Audited"}"#))
+                    .unwrap()
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/audit?action=create&entity_type=client")
+                    .header("Authorization", auth::DEV_TOKEN)
+                    .body(Body::empty())
+                    .unwrap()
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let event = &body["data"][0];
+        assert_eq!(event["actor"], auth::DEV_SUBJECT);
+        assert_eq!(event["request_id"], "audit-test-id");
+        assert_eq!(event["action"], "create");
+    }
+
+    // Test that retrying a create with the same Idempotency-Key does not create a second client
+    #[tokio::test]
+    async fn test_idempotent_client_create() {
+        let state = AppState::default();
+        let app = app(state.clone());
+        let create = || {
+            Request::builder()
+                .method("POST")
+                .uri("/api/clients")
+                .header("Authorization", auth::DEV_TOKEN)
+                .header("Content-Type", "application/json")
+                .header("Idempotency-Key", "create-once")
+                .body(Body::from(r#"{"name":"Once"}"#))
+                .unwrap()
+        };
+
+        let first = app.clone().oneshot(create()).await.unwrap();
+        assert_eq!(first.status(), StatusCode::CREATED);
+        let location = first.headers()["location"].clone();
+
+        let retry = app.oneshot(create()).await.unwrap();
+        assert_eq!(retry.status(), StatusCode::CREATED);
+        assert_eq!(retry.headers()["location"], location);
+        assert_eq!(retry.headers()["idempotent-replayed"], "true");
+
+        let page = state
+            .clients
+            .list(&clients::repository::ListQuery {
+                limit: 10,
+                name_prefix: Some("Once".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(page.items.len(), 1);
+    }
+
+    // Test that the metrics endpoint is mounted on the main router by default
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        let app = app(AppState::default());
+
+        // Make a request so there is something to report
+        app.clone()
+            .oneshot(Request::builder().uri("/api/health").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let response = app
+            .oneshot(Request::builder().uri("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body_bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body_bytes.to_vec()).unwrap();
+        assert!(body.contains(r#"route="/api/health""#));
+    }
+
+    async fn panicking_handler() -> &'static str {
+        panic!("handler exploded")
+    }
+
+    // Test that panics become 500s and are counted
+    #[tokio::test]
+    async fn test_panic_handler() {
+        crate::metrics::install();
+        let app = Router::new()
+            .route("/panic", axum::routing::get(panicking_handler))
+            .layer(middleware::from_fn(panic_handler));
+
+        let response = app
+            .oneshot(Request::builder().uri("/panic").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let body_bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body_bytes).unwrap();
+        assert_eq!(body["error"]["message"], "Internal server error");
+
+        assert!(crate::metrics::render().contains("panics_total"));
+    }
+
+    // Test that a separate metrics port removes the endpoint from the main router
+    #[tokio::test]
+    async fn test_metrics_on_separate_port() {
+        let mut config = config::AppConfig::default();
+        config.metrics.listen_addr = Some("127.0.0.1:0".to_string());
+        let app = app(AppState::new(config));
+
+        let response = app
+            .oneshot(Request::builder().uri("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
     // Test for OpenAPI docs endpoint
     #[tokio::test]
     async fn test_openapi_docs() {
-        let app = app();
+        let app = app(AppState::default());
 
         // Request to the OpenAPI UI endpoint
         let response = app
@@ -358,7 +697,7 @@ mod tests {
     // Test for OpenAPI JSON endpoint
     #[tokio::test]
     async fn test_openapi_json() {
-        let app = app();
+        let app = app(AppState::default());
 
         // Request to the OpenAPI JSON endpoint
         let response = app
@@ -369,4 +708,117 @@ mod tests {
         // Should get 200 OK
         assert_eq!(response.status(), StatusCode::OK);
     }
+
+    // Preflights reach the CORS layer before authentication
+    #[tokio::test]
+    async fn test_cors_preflight_on_secured_route() {
+        use axum::http::header;
+
+        let app = app(AppState::default());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("OPTIONS")
+                    .uri("/api/clients")
+                    .header(header::ORIGIN, "http://localhost:5173")
+                    .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                    .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization,content-type")
+                    .body(Body::empty())
+                    .unwrap()
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "http://localhost:5173"
+        );
+    }
+
+    // Only the Swagger UI gets the relaxed Content-Security-Policy
+    #[tokio::test]
+    async fn test_security_headers() {
+        let state = AppState::default();
+        let policies = &state.config.security_headers;
+        let (api_policy, docs_policy) = (
+            policies.content_security_policy.clone(),
+            policies.docs_content_security_policy.clone(),
+        );
+        let app = app(state);
+
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri("/api/docs/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-security-policy"], docs_policy.as_str());
+        assert_eq!(response.headers()["x-content-type-options"], "nosniff");
+
+        // Errors from auth and the 404 fallback get the headers too
+        for uri in ["/api/health", "/api/clients", "/missing"] {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.headers()["content-security-policy"], api_policy.as_str());
+            assert_eq!(response.headers()["x-frame-options"], "DENY");
+        }
+    }
+
+    // Each route group gets its own body size limit
+    #[tokio::test]
+    async fn test_body_size_limit_by_group() {
+        let app = app(AppState::default());
+        let post = |uri: &str| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("authorization", auth::DEV_TOKEN)
+                .header("content-type", "application/json")
+                .body(Body::from(vec![b' '; 3 * 1024 * 1024]))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(post("/api/webhooks")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // Clients allow larger bodies for bulk imports
+        let response = app.oneshot(post("/api/clients")).await.unwrap();
+        assert_ne!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(response.status().is_client_error());
+    }
+
+    // Buffering a body for idempotency is still bound by the group's limits
+    #[tokio::test(start_paused = true)]
+    async fn test_idempotent_request_body_limits() {
+        use axum::body::Bytes;
+        use futures_util::StreamExt;
+
+        let app = app(AppState::default());
+        let post = |body: Body| {
+            Request::builder()
+                .method("POST")
+                .uri("/api/webhooks")
+                .header("authorization", auth::DEV_TOKEN)
+                .header("content-type", "application/json")
+                .header("idempotency-key", "slow-body")
+                .body(body)
+                .unwrap()
+        };
+
+        // One byte a second outlasts the 10 second body timeout
+        let slow = futures_util::stream::iter(0..20).then(|_| async {
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            Ok::<_, std::convert::Infallible>(Bytes::from_static(b" "))
+        });
+        let response = app.clone().oneshot(post(Body::from_stream(slow))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+
+        let response = app.oneshot(post(Body::from(vec![b' '; 3 * 1024 * 1024]))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,273 @@
+use std::{
+    sync::OnceLock,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::Request,
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+use crate::http_trace::matched_route;
+
+pub const HTTP_REQUESTS_TOTAL: &str = "http_requests_total";
+pub const HTTP_REQUEST_DURATION_SECONDS: &str = "http_request_duration_seconds";
+pub const HTTP_REQUESTS_IN_FLIGHT: &str = "http_requests_in_flight";
+pub const AUTH_FAILURES_TOTAL: &str = "auth_failures_total";
+pub const AUTH_LOCKOUTS_TOTAL: &str = "auth_lockouts_total";
+pub const PANICS_TOTAL: &str = "panics_total";
+pub const WEBHOOK_DELIVERIES_TOTAL: &str = "webhook_deliveries_total";
+pub const RATE_LIMITED_TOTAL: &str = "rate_limited_requests_total";
+pub const REQUEST_LIMITS_EXCEEDED_TOTAL: &str = "request_limits_exceeded_total";
+pub const SHED_REQUESTS_TOTAL: &str = "shed_requests_total";
+
+// Latency buckets in seconds, from 5ms to 10s
+const DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+struct Recorder {
+    handle: PrometheusHandle,
+    started: Instant,
+    started_at: SystemTime,
+}
+
+static RECORDER: OnceLock<Recorder> = OnceLock::new();
+
+fn recorder() -> &'static Recorder {
+    RECORDER.get_or_init(|| {
+        let recorder = PrometheusBuilder::new()
+            .set_buckets_for_metric(
+                Matcher::Full(HTTP_REQUEST_DURATION_SECONDS.to_string()),
+                DURATION_BUCKETS,
+            )
+            .expect("valid histogram buckets")
+            .build_recorder();
+        let handle = recorder.handle();
+
+        if metrics::set_global_recorder(recorder).is_err() {
+            tracing::warn!("A metrics recorder was already installed");
+        }
+
+        Recorder {
+            handle,
+            started: Instant::now(),
+            started_at: SystemTime::now(),
+        }
+    })
+}
+
+/// Install the global Prometheus recorder. Safe to call more than once.
+pub fn install() -> PrometheusHandle {
+    recorder().handle.clone()
+}
+
+/// Render all metrics in the Prometheus text format
+pub fn render() -> String {
+    record_process_metrics();
+    recorder().handle.render()
+}
+
+/// Routes serving the metrics endpoint at `path`
+pub fn routes(path: &str) -> Router {
+    Router::new().route(path, get(get_metrics))
+}
+
+/// Prometheus scrape endpoint
+pub async fn get_metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        render(),
+    )
+}
+
+// Decrements the in-flight gauge even if the request future is dropped
+struct InFlightGuard {
+    method: String,
+    route: String,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        gauge!(HTTP_REQUESTS_IN_FLIGHT, "method" => self.method.clone(), "route" => self.route.clone())
+            .decrement(1.0);
+    }
+}
+
+// Middleware recording request count, latency and in-flight requests per route
+pub async fn metrics_middleware(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = matched_route(&request);
+
+    gauge!(HTTP_REQUESTS_IN_FLIGHT, "method" => method.clone(), "route" => route.clone())
+        .increment(1.0);
+    let guard = InFlightGuard {
+        method: method.clone(),
+        route: route.clone(),
+    };
+
+    let response = next.run(request).await;
+    drop(guard);
+
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status_class", status_class(response.status().as_u16())),
+    ];
+    counter!(HTTP_REQUESTS_TOTAL, &labels).increment(1);
+    histogram!(HTTP_REQUEST_DURATION_SECONDS, &labels).record(start.elapsed().as_secs_f64());
+
+    response
+}
+
+fn status_class(status: u16) -> String {
+    format!("{}xx", status / 100)
+}
+
+/// Count an authentication failure, e.g. `missing_token` or `invalid_token`
+pub fn record_auth_failure(reason: &'static str) {
+    counter!(AUTH_FAILURES_TOTAL, "reason" => reason).increment(1);
+}
+
+/// Count a lockout after repeated authentication failures, by `ip` or `credential`
+pub fn record_auth_lockout(scope: &'static str) {
+    counter!(AUTH_LOCKOUTS_TOTAL, "scope" => scope).increment(1);
+}
+
+/// Count a panic caught by the panic handler
+pub fn record_panic() {
+    counter!(PANICS_TOTAL).increment(1);
+}
+
+/// Count a webhook delivery attempt by outcome: `succeeded`, `failed` or `dead_lettered`
+pub fn record_webhook_delivery(outcome: &'static str) {
+    counter!(WEBHOOK_DELIVERIES_TOTAL, "outcome" => outcome).increment(1);
+}
+
+/// Count a request rejected by the rate limiter, by route group
+pub fn record_rate_limited(group: &'static str) {
+    counter!(RATE_LIMITED_TOTAL, "group" => group).increment(1);
+}
+
+/// Count a request that ran out of time or sent too large a body, by route
+/// group and limit (`timeout`, `body_timeout` or `body_size`)
+pub fn record_limit_exceeded(group: &'static str, limit: &'static str) {
+    counter!(REQUEST_LIMITS_EXCEEDED_TOTAL, "group" => group, "limit" => limit).increment(1);
+}
+
+/// Count a request shed because the server was at its concurrency cap
+pub fn record_shed() {
+    counter!(SHED_REQUESTS_TOTAL).increment(1);
+}
+
+// Process metrics are sampled at scrape time
+fn record_process_metrics() {
+    let recorder = recorder();
+
+    gauge!("process_uptime_seconds").set(recorder.started.elapsed().as_secs_f64());
+    if let Ok(since_epoch) = recorder.started_at.duration_since(UNIX_EPOCH) {
+        gauge!("process_start_time_seconds").set(since_epoch.as_secs_f64());
+    }
+
+    if let Some(bytes) = proc_status_bytes("VmRSS:") {
+        gauge!("process_resident_memory_bytes").set(bytes as f64);
+    }
+    if let Some(bytes) = proc_status_bytes("VmSize:") {
+        gauge!("process_virtual_memory_bytes").set(bytes as f64);
+    }
+    if let Ok(entries) = std::fs::read_dir("/proc/self/fd") {
+        gauge!("process_open_fds").set(entries.count() as f64);
+    }
+}
+
+// Read a `kB` value from /proc/self/status; only available on Linux
+fn proc_status_bytes(key: &str) -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with(key))?;
+    let kilobytes: u64 = line[key.len()..].trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::{Body, to_bytes},
+        http::StatusCode,
+        middleware,
+    };
+    use tower::ServiceExt;
+
+    async fn ok_handler() -> &'static str {
+        "ok"
+    }
+
+    async fn error_handler() -> StatusCode {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+
+    #[tokio::test]
+    async fn test_request_metrics() {
+        install();
+        let app = Router::new()
+            .route("/metrics-test/:id", get(ok_handler))
+            .route("/metrics-test/:id/error", get(error_handler))
+            .layer(middleware::from_fn(metrics_middleware))
+            .merge(routes("/metrics"));
+
+        for uri in ["/metrics-test/1", "/metrics-test/2", "/metrics-test/3/error"] {
+            app.clone()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+        }
+
+        let response = app
+            .oneshot(Request::builder().uri("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+
+        assert!(body.contains(
+            r#"http_requests_total{method="GET",route="/metrics-test/:id",status_class="2xx"} 2"#
+        ));
+        assert!(body.contains(
+            r#"http_requests_total{method="GET",route="/metrics-test/:id/error",status_class="5xx"} 1"#
+        ));
+        assert!(body.contains(
+            r#"http_request_duration_seconds_bucket{method="GET",route="/metrics-test/:id",status_class="2xx",le="+Inf"} 2"#
+        ));
+        assert!(body.contains(
+            r#"http_requests_in_flight{method="GET",route="/metrics-test/:id"} 0"#
+        ));
+        assert!(body.contains("process_uptime_seconds"));
+    }
+
+    #[test]
+    fn test_status_class() {
+        assert_eq!(status_class(200), "2xx");
+        assert_eq!(status_class(404), "4xx");
+        assert_eq!(status_class(503), "5xx");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_process_metrics() {
+        install();
+        let body = render();
+
+        assert!(body.contains("process_resident_memory_bytes"));
+        assert!(body.contains("process_open_fds"));
+        assert!(body.contains("process_start_time_seconds"));
+    }
+}
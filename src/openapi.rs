@@ -2,24 +2,99 @@ use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 use axum::Router;
 
+use crate::{config::SecurityHeadersConfig, security_headers};
+
 /// API documentation
 #[derive(OpenApi)]
 #[openapi(
     paths(
         crate::health::handlers::get_health,
-        crate::clients::handlers::get_secured_clients,
-        crate::clients::handlers::get_clients
+        crate::clients::handlers::get_clients,
+        crate::clients::handlers::list_clients,
+        crate::clients::handlers::search_clients,
+        crate::clients::handlers::create_client,
+        crate::clients::handlers::get_client,
+        crate::clients::handlers::update_client,
+        crate::clients::handlers::patch_client,
+        crate::clients::handlers::delete_client,
+        crate::clients::handlers::restore_client,
+        crate::clients::handlers::get_client_history,
+        crate::clients::handlers::import_clients,
+        crate::clients::handlers::export_clients,
+        crate::clients::stream::stream_events,
+        crate::ws::handlers::ws_handler,
+        crate::clients::custom_fields::list_custom_fields,
+        crate::clients::custom_fields::get_custom_field,
+        crate::clients::custom_fields::put_custom_field,
+        crate::clients::custom_fields::delete_custom_field,
+        crate::clients::contacts::list_contacts,
+        crate::clients::contacts::create_contact,
+        crate::clients::contacts::get_contact,
+        crate::clients::contacts::update_contact,
+        crate::clients::contacts::delete_contact,
+        crate::clients::addresses::list_addresses,
+        crate::clients::addresses::create_address,
+        crate::clients::addresses::get_address,
+        crate::clients::addresses::update_address,
+        crate::clients::addresses::delete_address,
+        crate::audit::handlers::get_audit_log,
+        crate::webhooks::handlers::list_webhooks,
+        crate::webhooks::handlers::create_webhook,
+        crate::webhooks::handlers::get_webhook,
+        crate::webhooks::handlers::delete_webhook,
+        crate::webhooks::handlers::list_deliveries,
+        crate::webhooks::handlers::get_delivery,
+        crate::webhooks::handlers::redeliver,
+        crate::admin::handlers::get_log_level,
+        crate::admin::handlers::put_log_level
     ),
     components(
         schemas(
             crate::health::handlers::HealthResponse,
-            crate::clients::handlers::Client,
-            crate::clients::handlers::ClientResponse
+            crate::clients::models::Client,
+            crate::clients::handlers::ClientListResponse,
+            crate::clients::handlers::ClientRequest,
+            crate::clients::bulk::BulkFormat,
+            crate::clients::bulk::ImportMode,
+            crate::clients::bulk::ImportReport,
+            crate::clients::bulk::RowResult,
+            crate::clients::bulk::RowStatus,
+            crate::clients::handlers::SearchResponse,
+            crate::clients::search::SearchHit,
+            crate::clients::handlers::ClientSummary,
+            crate::clients::models::CustomFieldDefinition,
+            crate::clients::models::CustomFieldType,
+            crate::clients::custom_fields::CustomFieldRequest,
+            crate::clients::custom_fields::CustomFieldListResponse,
+            crate::clients::models::Contact,
+            crate::clients::contacts::ContactRequest,
+            crate::clients::contacts::ContactListResponse,
+            crate::clients::models::Address,
+            crate::clients::models::AddressKind,
+            crate::clients::addresses::AddressRequest,
+            crate::clients::addresses::AddressListResponse,
+            crate::audit::models::AuditAction,
+            crate::audit::models::AuditEvent,
+            crate::audit::models::Change,
+            crate::audit::handlers::AuditListResponse,
+            crate::events::ClientEventType,
+            crate::events::ClientEvent,
+            crate::webhooks::models::WebhookEndpoint,
+            crate::webhooks::models::WebhookDelivery,
+            crate::webhooks::models::DeliveryStatus,
+            crate::webhooks::handlers::WebhookRequest,
+            crate::webhooks::handlers::WebhookListResponse,
+            crate::webhooks::handlers::DeliveryListResponse,
+            crate::admin::handlers::LogLevelResponse,
+            crate::admin::handlers::UpdateLogLevelRequest
         )
     ),
     tags(
         (name = "health", description = "Health check endpoints"),
-        (name = "clients", description = "Client management endpoints")
+        (name = "clients", description = "Client management endpoints"),
+        (name = "audit", description = "Audit trail of changes"),
+        (name = "webhooks", description = "Webhook subscriptions to client events"),
+        (name = "admin", description = "Operational endpoints")
     ),
     info(
         title = "Axum Starter API",
@@ -30,11 +105,14 @@ use axum::Router;
 pub struct ApiDoc;
 
 /// Create routes for OpenAPI documentation
-pub fn routes() -> Router {
+pub fn routes(security_headers: &SecurityHeadersConfig) -> Router {
     // Note that when mounted at /api, the full paths will be:
     // - Swagger UI: /api/docs (which redirects to /api/docs/)
     // - OpenAPI JSON: /api/openapi.json
-    Router::new()
+    let router = Router::new()
         // This creates all the necessary routes for Swagger UI
-        .merge(SwaggerUi::new("/docs").url("/openapi.json", ApiDoc::openapi()))
+        .merge(SwaggerUi::new("/docs").url("/openapi.json", ApiDoc::openapi()));
+
+    // The Swagger UI can't load under the API's default policy
+    security_headers::with_docs_policy(router, security_headers)
 }
\ No newline at end of file
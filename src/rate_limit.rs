@@ -0,0 +1,464 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::Duration,
+};
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue},
+    middleware::{self, Next},
+    response::Response,
+    Router,
+};
+use sha2::{Digest, Sha256};
+use tokio::time::Instant;
+
+use crate::{
+    auth::{self, Principal},
+    client_ip,
+    config::{AppConfig, RateLimitRule},
+    errors::AppError,
+    state::AppState,
+};
+
+// Rate limit headers from the IETF httpapi draft
+pub static RATELIMIT_LIMIT_HEADER: HeaderName = HeaderName::from_static("ratelimit-limit");
+pub static RATELIMIT_REMAINING_HEADER: HeaderName = HeaderName::from_static("ratelimit-remaining");
+pub static RATELIMIT_RESET_HEADER: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+// How many acquisitions between sweeps of idle buckets
+const SWEEP_INTERVAL: u64 = 1024;
+
+/// Token bucket: holds up to `burst` tokens and refills at a steady rate
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    per_second: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket
+    pub fn new(per_second: f64, burst: u32) -> Self {
+        let capacity = f64::from(burst.max(1));
+        Self {
+            capacity,
+            per_second: per_second.max(0.0),
+            tokens: capacity,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Take a token if one is available
+    pub fn try_acquire(&mut self) -> bool {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Size of the bucket
+    pub fn capacity(&self) -> u32 {
+        self.capacity as u32
+    }
+
+    /// Whole tokens left as of the last refill
+    pub fn remaining(&self) -> u32 {
+        self.tokens.floor() as u32
+    }
+
+    /// How long until a token is available
+    pub fn retry_after(&self) -> Duration {
+        self.time_until(1.0)
+    }
+
+    /// How long until the bucket is full again
+    pub fn reset_after(&self) -> Duration {
+        self.time_until(self.capacity)
+    }
+
+    fn time_until(&self, tokens: f64) -> Duration {
+        let missing = tokens - self.tokens;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else if self.per_second == 0.0 {
+            Duration::MAX
+        } else {
+            Duration::from_secs_f64(missing / self.per_second)
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let refill = now.duration_since(self.updated).as_secs_f64() * self.per_second;
+        self.tokens = (self.tokens + refill).min(self.capacity);
+        self.updated = now;
+    }
+}
+
+/// Outcome of taking a request from a caller's bucket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// How long until the next request would be allowed
+    pub retry_after: Duration,
+    /// How long until the bucket is full again
+    pub reset_after: Duration,
+}
+
+/// Storage for rate limit buckets
+pub trait RateLimitStore: Send + Sync {
+    /// Take a request from the bucket for `key`, which starts out full
+    fn acquire(&self, key: &str, rule: &RateLimitRule) -> Decision;
+}
+
+#[derive(Default)]
+struct Buckets {
+    by_key: HashMap<String, TokenBucket>,
+    acquisitions: u64,
+}
+
+/// Buckets kept in memory; each instance limits callers on its own
+#[derive(Default)]
+pub struct InMemoryRateLimitStore {
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimitStore for InMemoryRateLimitStore {
+    fn acquire(&self, key: &str, rule: &RateLimitRule) -> Decision {
+        let mut buckets = self.buckets.lock().expect("rate limit store lock poisoned");
+
+        buckets.acquisitions += 1;
+        if buckets.acquisitions.is_multiple_of(SWEEP_INTERVAL) {
+            // A full bucket is the same as no bucket, so forget idle callers
+            buckets.by_key.retain(|_, bucket| {
+                bucket.refill();
+                !bucket.reset_after().is_zero()
+            });
+        }
+
+        let bucket = buckets.by_key.entry(key.to_string()).or_insert_with(|| {
+            TokenBucket::new(f64::from(rule.requests_per_minute) / 60.0, rule.burst)
+        });
+        let allowed = bucket.try_acquire();
+        Decision {
+            allowed,
+            limit: bucket.capacity(),
+            remaining: bucket.remaining(),
+            retry_after: bucket.retry_after(),
+            reset_after: bucket.reset_after(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Group {
+    state: AppState,
+    name: &'static str,
+    // Only count requests that carry no credentials
+    anonymous_only: bool,
+}
+
+/// Rate limit the routes of `router` as the route group `group`.
+///
+/// Callers get a bucket per group, so being limited on one group doesn't
+/// affect the others. Apply it inside `auth_middleware` so callers are
+/// limited by principal rather than by IP.
+pub fn limit(router: Router, state: &AppState, group: &'static str) -> Router {
+    layer(router, state, group, false)
+}
+
+/// Rate limit requests without an `Authorization` header as the route group
+/// `group`, by IP.
+///
+/// Apply it outside `auth_middleware`: requests without a token are turned
+/// away before the group limits inside it, and they aren't counted as failed
+/// attempts either.
+pub fn limit_anonymous(router: Router, state: &AppState, group: &'static str) -> Router {
+    layer(router, state, group, true)
+}
+
+fn layer(router: Router, state: &AppState, group: &'static str, anonymous_only: bool) -> Router {
+    if !state.config.rate_limit.enabled {
+        return router;
+    }
+    router.layer(middleware::from_fn_with_state(
+        Group {
+            state: state.clone(),
+            name: group,
+            anonymous_only,
+        },
+        rate_limit_middleware,
+    ))
+}
+
+async fn rate_limit_middleware(
+    State(group): State<Group>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if group.anonymous_only && request.headers().contains_key(header::AUTHORIZATION) {
+        return Ok(next.run(request).await);
+    }
+
+    let config = &group.state.config;
+    let key = format!("{}:{}", group.name, caller(&request, config));
+    let decision = group
+        .state
+        .rate_limits
+        .acquire(&key, config.rate_limit.rule(group.name));
+
+    if !decision.allowed {
+        crate::metrics::record_rate_limited(group.name);
+        let mut err = AppError::too_many_requests("Rate limit exceeded, retry later")
+            .with_header(header::RETRY_AFTER, seconds(decision.retry_after));
+        for (name, value) in &headers(&decision) {
+            err = err.with_header(name.clone(), value.clone());
+        }
+        return Err(err);
+    }
+
+    let mut response = next.run(request).await;
+    response.headers_mut().extend(headers(&decision));
+    Ok(response)
+}
+
+// Who a request counts against: the principal, then the API key, then the client IP
+fn caller(request: &Request, config: &AppConfig) -> String {
+    if let Some(principal) = request.extensions().get::<Principal>() {
+        return format!("principal:{}", principal.subject);
+    }
+
+    // Only a key that authenticates is trusted, or every made-up key would get
+    // a fresh bucket
+    if let Some(name) = &config.rate_limit.api_key_header
+        && let Some(api_key) = request.headers().get(name.as_str())
+        && let Ok(value) = api_key.to_str()
+        && auth::verify(Some(&format!("Bearer {}", value))).is_some()
+    {
+        // Keep a digest rather than the key itself
+        let digest: String = Sha256::digest(api_key.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        return format!("api_key:{}", digest);
+    }
+
+    match client_ip::client_ip(request, config.http.trust_forwarded_for) {
+        Some(ip) => format!("ip:{}", ip),
+        None => "ip:unknown".to_string(),
+    }
+}
+
+fn headers(decision: &Decision) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(RATELIMIT_LIMIT_HEADER.clone(), HeaderValue::from(decision.limit));
+    headers.insert(RATELIMIT_REMAINING_HEADER.clone(), HeaderValue::from(decision.remaining));
+    headers.insert(RATELIMIT_RESET_HEADER.clone(), seconds(decision.reset_after));
+    headers
+}
+
+// Whole seconds, rounded up so callers don't retry too early
+fn seconds(duration: Duration) -> HeaderValue {
+    let seconds = duration.as_secs().saturating_add(u64::from(duration.subsec_nanos() > 0));
+    HeaderValue::from(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    use axum::{
+        body::Body,
+        extract::ConnectInfo,
+        http::StatusCode,
+        routing::get,
+    };
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use crate::auth;
+
+    fn rule(requests_per_minute: u32, burst: u32) -> RateLimitRule {
+        RateLimitRule {
+            requests_per_minute,
+            burst,
+        }
+    }
+
+    fn app(config: AppConfig) -> Router {
+        let state = AppState::new(config);
+        let router = Router::new().route("/things", get(|| async { "ok" }));
+        limit(router, &state, "things").layer(axum::middleware::from_fn(
+            |mut request: Request, next: Next| async move {
+                // Stand in for auth_middleware on requests that carry the dev token
+                if request.headers().get(header::AUTHORIZATION).is_some() {
+                    request.extensions_mut().insert(Principal {
+                        subject: auth::DEV_SUBJECT.to_string(),
+                    });
+                }
+                next.run(request).await
+            },
+        ))
+    }
+
+    fn request(peer: &str, token: bool) -> Request {
+        let mut builder = Request::builder().uri("/things");
+        if token {
+            builder = builder.header(header::AUTHORIZATION, auth::DEV_TOKEN);
+        }
+        let mut request = builder.body(Body::empty()).unwrap();
+        let peer: SocketAddr = peer.parse().unwrap();
+        request.extensions_mut().insert(ConnectInfo(peer));
+        request
+    }
+
+    fn config(requests_per_minute: u32, burst: u32) -> AppConfig {
+        let mut config = AppConfig::default();
+        config.rate_limit.default = rule(requests_per_minute, burst);
+        config
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_bucket_refills() {
+        let mut bucket = TokenBucket::new(2.0, 3);
+
+        assert!((0..3).all(|_| bucket.try_acquire()));
+        assert!(!bucket.try_acquire());
+        assert_eq!(bucket.retry_after(), Duration::from_millis(500));
+        assert_eq!(bucket.reset_after(), Duration::from_millis(1500));
+
+        tokio::time::advance(Duration::from_millis(500)).await;
+        assert!(bucket.try_acquire());
+        assert!(!bucket.try_acquire());
+
+        // Idle time never saves up more than the burst
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert!((0..3).all(|_| bucket.try_acquire()));
+        assert!(!bucket.try_acquire());
+
+        let mut empty = TokenBucket::new(0.0, 1);
+        assert!(empty.try_acquire());
+        assert_eq!(empty.retry_after(), Duration::MAX);
+        assert_eq!(seconds(empty.retry_after()), HeaderValue::from(u64::MAX));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_store_tracks_keys_separately() {
+        let store = InMemoryRateLimitStore::default();
+        let rule = rule(60, 2);
+
+        let first = store.acquire("a", &rule);
+        assert!(first.allowed);
+        assert_eq!((first.limit, first.remaining), (2, 1));
+        assert!(store.acquire("a", &rule).allowed);
+
+        let limited = store.acquire("a", &rule);
+        assert!(!limited.allowed);
+        assert_eq!(limited.retry_after, Duration::from_secs(1));
+        assert_eq!(limited.reset_after, Duration::from_secs(2));
+
+        assert!(store.acquire("b", &rule).allowed);
+    }
+
+    #[tokio::test]
+    async fn test_limited_requests_get_429() {
+        let app = app(config(60, 2));
+
+        for remaining in ["1", "0"] {
+            let response = app.clone().oneshot(request("10.0.0.1:1000", false)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["ratelimit-limit"], "2");
+            assert_eq!(response.headers()["ratelimit-remaining"], remaining);
+        }
+
+        let response = app.clone().oneshot(request("10.0.0.1:1001", false)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "1");
+        assert_eq!(response.headers()["ratelimit-remaining"], "0");
+        assert_eq!(response.headers()["ratelimit-reset"], "2");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["status"], 429);
+
+        // Other IPs and authenticated callers have their own buckets
+        let response = app.clone().oneshot(request("10.0.0.2:1000", false)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.oneshot(request("10.0.0.1:1000", true)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_api_key_and_group_rules() {
+        let mut config = config(60, 1);
+        config.rate_limit.api_key_header = Some("x-api-key".to_string());
+        config.rate_limit.groups.insert("things".to_string(), rule(60, 3));
+        let app = app(config);
+
+        let with_key = |key: &'static str| {
+            let mut request = request("10.0.0.1:1000", false);
+            request.headers_mut().insert("x-api-key", HeaderValue::from_static(key));
+            request
+        };
+
+        for _ in 0..3 {
+            let response = app.clone().oneshot(with_key("dev_token")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["ratelimit-limit"], "3");
+        }
+        let response = app.clone().oneshot(with_key("dev_token")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Same IP, but unverified keys fall back to the IP, however many are tried
+        for (key, status) in [
+            ("key-one", StatusCode::OK),
+            ("key-two", StatusCode::OK),
+            ("key-three", StatusCode::OK),
+            ("key-four", StatusCode::TOO_MANY_REQUESTS),
+        ] {
+            let response = app.clone().oneshot(with_key(key)).await.unwrap();
+            assert_eq!(response.status(), status);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_requests_are_limited_by_ip() {
+        let mut config = config(60, 1);
+        config.rate_limit.groups.insert("public".to_string(), rule(60, 1));
+        let state = AppState::new(config);
+        let router = Router::new()
+            .route("/things", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), auth::auth_middleware));
+        let app = limit_anonymous(router, &state, "public");
+
+        let response = app.clone().oneshot(request("10.0.0.1:1000", false)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app.clone().oneshot(request("10.0.0.1:1000", false)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Requests with a token are left to auth and the limits inside it
+        let response = app.oneshot(request("10.0.0.1:1000", true)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_disabled() {
+        let mut config = config(60, 1);
+        config.rate_limit.enabled = false;
+        let app = app(config);
+
+        for _ in 0..3 {
+            let response = app.clone().oneshot(request("10.0.0.1:1000", false)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(response.headers().get("ratelimit-limit").is_none());
+        }
+    }
+}
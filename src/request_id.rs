@@ -0,0 +1,230 @@
+use std::future::Future;
+
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use uuid::Uuid;
+
+// Header used to accept, echo and forward request IDs
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// Longest incoming request ID we are willing to reuse
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// The ID correlating logs, errors and responses for a single request
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: RequestId;
+}
+
+impl RequestId {
+    // Reuse the incoming header if it is sane, otherwise generate a UUIDv7
+    fn from_request(request: &Request) -> Self {
+        let incoming = request
+            .headers()
+            .get(&REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| is_valid(value));
+
+        match incoming {
+            Some(id) => Self(id.to_string()),
+            None => Self(Uuid::now_v7().to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Returns the request ID of the request currently being handled, if any
+pub fn current() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(|id| id.0.clone()).ok()
+}
+
+/// Runs a future with the current request ID (if any) still in scope.
+///
+/// Task-locals do not cross `tokio::spawn`, so wrap spawned futures with this.
+pub fn scoped<F>(future: F) -> impl Future<Output = F::Output>
+where
+    F: Future,
+{
+    let id = CURRENT_REQUEST_ID.try_with(Clone::clone).ok();
+    async move {
+        match id {
+            Some(id) => CURRENT_REQUEST_ID.scope(id, future).await,
+            None => future.await,
+        }
+    }
+}
+
+// Middleware that assigns a request ID, records it on the request span and echoes it back
+pub async fn request_id_middleware(mut request: Request, next: Next) -> Response {
+    let request_id = RequestId::from_request(&request);
+    request.extensions_mut().insert(request_id.clone());
+
+    // The span is opened by `http_trace::trace_middleware`
+    tracing::Span::current().record("request_id", request_id.as_str());
+
+    let mut response = CURRENT_REQUEST_ID
+        .scope(request_id.clone(), next.run(request))
+        .await;
+
+    if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+        response
+            .headers_mut()
+            .insert(REQUEST_ID_HEADER.clone(), value);
+    }
+
+    response
+}
+
+/// Forwards the current request ID on outbound `reqwest` calls
+pub trait PropagateRequestId {
+    fn propagate_request_id(self) -> Self;
+}
+
+impl PropagateRequestId for reqwest::RequestBuilder {
+    fn propagate_request_id(self) -> Self {
+        match current() {
+            Some(id) => self.header(REQUEST_ID_HEADER.as_str(), id),
+            None => self,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::AppError;
+    use axum::{
+        body::{Body, to_bytes},
+        http::StatusCode,
+        middleware,
+        routing::get,
+        Router,
+    };
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    async fn echo_handler() -> String {
+        current().unwrap_or_default()
+    }
+
+    async fn error_handler() -> Result<&'static str, AppError> {
+        Err(AppError::bad_request("Invalid request"))
+    }
+
+    fn test_app() -> Router {
+        Router::new()
+            .route("/echo", get(echo_handler))
+            .route("/error", get(error_handler))
+            .layer(middleware::from_fn(request_id_middleware))
+    }
+
+    #[tokio::test]
+    async fn test_generates_request_id() {
+        let response = test_app()
+            .oneshot(Request::builder().uri("/echo").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let header = response.headers()[&REQUEST_ID_HEADER].to_str().unwrap().to_string();
+        let uuid = Uuid::parse_str(&header).unwrap();
+        assert_eq!(uuid.get_version_num(), 7);
+
+        // The handler sees the same ID that is echoed back
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, header.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_reuses_incoming_request_id() {
+        let response = test_app()
+            .oneshot(
+                Request::builder()
+                    .uri("/echo")
+                    .header(&REQUEST_ID_HEADER, "abc-123")
+                    .body(Body::empty())
+                    .unwrap()
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()[&REQUEST_ID_HEADER], "abc-123");
+    }
+
+    #[tokio::test]
+    async fn test_replaces_invalid_request_id() {
+        let too_long = "a".repeat(MAX_REQUEST_ID_LEN + 1);
+        let response = test_app()
+            .oneshot(
+                Request::builder()
+                    .uri("/echo")
+                    .header(&REQUEST_ID_HEADER, too_long.as_str())
+                    .body(Body::empty())
+                    .unwrap()
+            )
+            .await
+            .unwrap();
+
+        let header = response.headers()[&REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(Uuid::parse_str(header).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_error_body_includes_request_id() {
+        let response = test_app()
+            .oneshot(
+                Request::builder()
+                    .uri("/error")
+                    .header(&REQUEST_ID_HEADER, "error-id")
+                    .body(Body::empty())
+                    .unwrap()
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body_bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body_bytes).unwrap();
+        assert_eq!(body["error"]["request_id"], "error-id");
+    }
+
+    #[tokio::test]
+    async fn test_outbound_requests_forward_request_id() {
+        let client = reqwest::Client::new();
+
+        let request = CURRENT_REQUEST_ID
+            .scope(RequestId("outbound-id".to_string()), async {
+                client
+                    .get("http://localhost/")
+                    .propagate_request_id()
+                    .build()
+                    .unwrap()
+            })
+            .await;
+        assert_eq!(request.headers()[REQUEST_ID_HEADER.as_str()], "outbound-id");
+
+        // Without a request in scope nothing is added
+        let request = client
+            .get("http://localhost/")
+            .propagate_request_id()
+            .build()
+            .unwrap();
+        assert!(request.headers().get(REQUEST_ID_HEADER.as_str()).is_none());
+    }
+}
@@ -0,0 +1,142 @@
+use axum::{
+    http::{header, HeaderName, HeaderValue},
+    Router,
+};
+use tower_http::set_header::SetResponseHeaderLayer;
+
+use crate::config::SecurityHeadersConfig;
+
+/// Add the configured hardening headers to every response from `router`.
+///
+/// Headers a handler or inner layer already set are kept, which is how the
+/// docs get their own `Content-Security-Policy`.
+pub fn apply(router: Router, config: &SecurityHeadersConfig) -> Router {
+    if !config.enabled {
+        return router;
+    }
+    layered(router, headers(config))
+}
+
+/// Give the routes in `router` the docs `Content-Security-Policy` instead of the default
+pub fn with_docs_policy(router: Router, config: &SecurityHeadersConfig) -> Router {
+    if !config.enabled {
+        return router;
+    }
+    layered(
+        router,
+        vec![(header::CONTENT_SECURITY_POLICY, config.docs_content_security_policy.clone())],
+    )
+}
+
+fn headers(config: &SecurityHeadersConfig) -> Vec<(HeaderName, String)> {
+    let mut hsts = String::new();
+    if config.hsts_max_age_seconds > 0 {
+        hsts = format!("max-age={}", config.hsts_max_age_seconds);
+        if config.hsts_include_subdomains {
+            hsts.push_str("; includeSubDomains");
+        }
+        if config.hsts_preload {
+            hsts.push_str("; preload");
+        }
+    }
+
+    vec![
+        (header::STRICT_TRANSPORT_SECURITY, hsts),
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+        (header::X_FRAME_OPTIONS, config.frame_options.clone()),
+        (header::REFERRER_POLICY, config.referrer_policy.clone()),
+        (HeaderName::from_static("permissions-policy"), config.permissions_policy.clone()),
+        (header::CONTENT_SECURITY_POLICY, config.content_security_policy.clone()),
+    ]
+}
+
+// Skips empty values, and invalid ones with a warning
+fn layered(router: Router, headers: Vec<(HeaderName, String)>) -> Router {
+    headers
+        .into_iter()
+        .filter(|(_, value)| !value.trim().is_empty())
+        .fold(router, |router, (name, value)| match HeaderValue::from_str(value.trim()) {
+            Ok(value) => router.layer(SetResponseHeaderLayer::if_not_present(name, value)),
+            Err(_) => {
+                tracing::warn!(header = %name, value = %value, "Ignoring invalid security header value");
+                router
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{HeaderMap, Request},
+        routing::get,
+    };
+    use tower::ServiceExt;
+
+    async fn get_headers(app: Router, uri: &str) -> HeaderMap {
+        let response = app
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        response.headers().clone()
+    }
+
+    fn app(config: &SecurityHeadersConfig) -> Router {
+        let docs = with_docs_policy(Router::new().route("/docs", get(|| async { "docs" })), config);
+        let router = Router::new()
+            .route("/things", get(|| async { "ok" }))
+            .route(
+                "/framed",
+                get(|| async { ([(header::X_FRAME_OPTIONS, "SAMEORIGIN")], "ok") }),
+            )
+            .merge(docs);
+        apply(router, config)
+    }
+
+    #[tokio::test]
+    async fn test_default_headers() {
+        let config = SecurityHeadersConfig::default();
+        let headers = get_headers(app(&config), "/things").await;
+
+        assert_eq!(headers["strict-transport-security"], "max-age=31536000; includeSubDomains");
+        assert_eq!(headers["x-content-type-options"], "nosniff");
+        assert_eq!(headers["x-frame-options"], "DENY");
+        assert_eq!(headers["referrer-policy"], "no-referrer");
+        assert!(headers["permissions-policy"].to_str().unwrap().contains("camera=()"));
+        assert_eq!(headers["content-security-policy"], config.content_security_policy.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_docs_policy_and_handler_headers_win() {
+        let config = SecurityHeadersConfig::default();
+
+        let headers = get_headers(app(&config), "/docs").await;
+        assert_eq!(headers["content-security-policy"], config.docs_content_security_policy.as_str());
+        assert_eq!(headers["x-frame-options"], "DENY");
+
+        let headers = get_headers(app(&config), "/framed").await;
+        assert_eq!(headers["x-frame-options"], "SAMEORIGIN");
+    }
+
+    #[tokio::test]
+    async fn test_empty_values_and_disabled() {
+        let config = SecurityHeadersConfig {
+            hsts_max_age_seconds: 0,
+            permissions_policy: String::new(),
+            ..SecurityHeadersConfig::default()
+        };
+        let headers = get_headers(app(&config), "/things").await;
+        assert!(headers.get("strict-transport-security").is_none());
+        assert!(headers.get("permissions-policy").is_none());
+        assert_eq!(headers["x-content-type-options"], "nosniff");
+
+        let config = SecurityHeadersConfig {
+            enabled: false,
+            ..SecurityHeadersConfig::default()
+        };
+        let headers = get_headers(app(&config), "/docs").await;
+        assert!(headers.get("x-content-type-options").is_none());
+        assert!(headers.get("content-security-policy").is_none());
+    }
+}
@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use crate::{
+    audit::store::{AuditStore, InMemoryAuditStore},
+    clients::{
+        custom_fields::{CustomFieldRepository, InMemoryCustomFieldRepository},
+        repository::{ClientRepository, InMemoryClientRepository},
+    },
+    config::AppConfig,
+    events::EventBus,
+    idempotency::{IdempotencyStore, InMemoryIdempotencyStore},
+    lockout::{AuthFailureStore, InMemoryAuthFailureStore},
+    logging::LogLevelController,
+    rate_limit::{InMemoryRateLimitStore, RateLimitStore},
+    webhooks::store::{InMemoryWebhookStore, WebhookStore},
+};
+
+/// Shared application state handed to middleware and handlers
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<AppConfig>,
+    /// Only present when the global subscriber was installed by `logging::init`
+    pub log_levels: Option<LogLevelController>,
+    pub clients: Arc<dyn ClientRepository>,
+    pub custom_fields: Arc<dyn CustomFieldRepository>,
+    pub audit: Arc<dyn AuditStore>,
+    pub idempotency: Arc<dyn IdempotencyStore>,
+    pub rate_limits: Arc<dyn RateLimitStore>,
+    pub auth_failures: Arc<dyn AuthFailureStore>,
+    pub webhooks: Arc<dyn WebhookStore>,
+    pub events: Arc<EventBus>,
+}
+
+impl AppState {
+    pub fn new(config: AppConfig) -> Self {
+        Self {
+            events: Arc::new(EventBus::new(&config.events)),
+            config: Arc::new(config),
+            log_levels: None,
+            clients: Arc::new(InMemoryClientRepository::with_example_data()),
+            custom_fields: Arc::new(InMemoryCustomFieldRepository::default()),
+            audit: Arc::new(InMemoryAuditStore::default()),
+            idempotency: Arc::new(InMemoryIdempotencyStore::default()),
+            rate_limits: Arc::new(InMemoryRateLimitStore::default()),
+            auth_failures: Arc::new(InMemoryAuthFailureStore::default()),
+            webhooks: Arc::new(InMemoryWebhookStore::default()),
+        }
+    }
+
+    pub fn with_log_levels(mut self, log_levels: LogLevelController) -> Self {
+        self.log_levels = Some(log_levels);
+        self
+    }
+
+    pub fn with_clients(mut self, clients: Arc<dyn ClientRepository>) -> Self {
+        self.clients = clients;
+        self
+    }
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new(AppConfig::default())
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use axum::http::HeaderMap;
+use opentelemetry::{
+    global,
+    propagation::Extractor,
+    trace::TracerProvider as _,
+    KeyValue,
+};
+use opentelemetry_otlp::{Protocol, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    trace::{Sampler, SdkTracerProvider},
+    Resource,
+};
+use tracing::{Span, Subscriber};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{registry::LookupSpan, Layer};
+
+use crate::config::{OtelConfig, OtlpProtocol};
+
+/// Set up OpenTelemetry trace export if it is enabled in the `[otel]` config.
+///
+/// Installs the W3C `traceparent` propagator and returns the tracer provider,
+/// which should be shut down on exit to flush pending spans.
+pub fn init(config: &OtelConfig) -> anyhow::Result<Option<SdkTracerProvider>> {
+    if !config.enabled {
+        return Ok(None);
+    }
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let provider = tracer_provider(config)?;
+    global::set_tracer_provider(provider.clone());
+
+    Ok(Some(provider))
+}
+
+/// Build a tracer provider exporting spans over OTLP/HTTP
+pub fn tracer_provider(config: &OtelConfig) -> anyhow::Result<SdkTracerProvider> {
+    let protocol = match config.protocol {
+        OtlpProtocol::Protobuf => Protocol::HttpBinary,
+        OtlpProtocol::Json => Protocol::HttpJson,
+    };
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(&config.endpoint)
+        .with_protocol(protocol)
+        .build()?;
+
+    let resource = Resource::builder()
+        .with_service_name(config.service_name.clone())
+        .with_attributes(parse_resource_attributes(&config.resource_attributes)?)
+        .build();
+
+    // Respect the caller's sampling decision, otherwise sample by ratio
+    let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+        config.sampling_ratio,
+    )));
+
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(sampler)
+        .with_resource(resource)
+        .build())
+}
+
+/// The tracing layer that turns spans into OpenTelemetry spans
+pub fn layer<S>(provider: &SdkTracerProvider) -> impl Layer<S> + Send + Sync
+where
+    S: Subscriber + for<'a> LookupSpan<'a> + Send + Sync,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+}
+
+// Parse `key=value,key=value` as used by `OTEL_RESOURCE_ATTRIBUTES`
+fn parse_resource_attributes(attributes: &str) -> anyhow::Result<Vec<KeyValue>> {
+    attributes
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                Ok(KeyValue::new(key.trim().to_string(), value.trim().to_string()))
+            }
+            _ => Err(anyhow::anyhow!("Invalid resource attribute: {}", pair)),
+        })
+        .collect()
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Continue the trace from an incoming W3C `traceparent` header, if any
+pub fn set_parent_from_headers(span: &Span, headers: &HeaderMap) {
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(headers))
+    });
+
+    // Fails only when OpenTelemetry is disabled, in which case there is nothing to do
+    let _ = span.set_parent(parent);
+}
+
+/// Forwards the current trace context on outbound `reqwest` calls
+pub trait PropagateTraceContext {
+    fn propagate_trace_context(self) -> Self;
+}
+
+impl PropagateTraceContext for reqwest::RequestBuilder {
+    fn propagate_trace_context(self) -> Self {
+        let context = Span::current().context();
+        let mut headers = HashMap::new();
+        global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&context, &mut headers)
+        });
+
+        headers
+            .into_iter()
+            .fold(self, |builder, (name, value)| builder.header(name, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http_trace::trace_middleware, state::AppState};
+    use axum::{
+        body::{Body, Bytes},
+        extract::{Request, State},
+        http::StatusCode,
+        middleware,
+        routing::{get, post},
+        Router,
+    };
+    use serde_json::Value;
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+    use tracing_subscriber::prelude::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const PARENT_SPAN_ID: &str = "00f067aa0ba902b7";
+
+    type Received = Arc<Mutex<Vec<Value>>>;
+
+    // In-process stand-in for an OTLP collector that records JSON export requests
+    async fn start_collector() -> (String, Received) {
+        async fn collect(State(received): State<Received>, body: Bytes) -> StatusCode {
+            received.lock().unwrap().push(serde_json::from_slice(&body).unwrap());
+            StatusCode::OK
+        }
+
+        let received = Received::default();
+        let app = Router::new()
+            .route("/v1/traces", post(collect))
+            .with_state(received.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        (format!("http://{}/v1/traces", addr), received)
+    }
+
+    fn exported_spans(received: &Received) -> Vec<Value> {
+        received
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|request| request["resourceSpans"].as_array().cloned().unwrap_or_default())
+            .flat_map(|resource| resource["scopeSpans"].as_array().cloned().unwrap_or_default())
+            .flat_map(|scope| scope["spans"].as_array().cloned().unwrap_or_default())
+            .collect()
+    }
+
+    async fn traced_handler() -> &'static str {
+        "traced"
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_exports_request_spans_to_collector() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let (endpoint, received) = start_collector().await;
+
+        let config = OtelConfig {
+            enabled: true,
+            endpoint,
+            protocol: OtlpProtocol::Json,
+            resource_attributes: "deployment.environment=test".to_string(),
+            ..OtelConfig::default()
+        };
+        let provider = tracer_provider(&config).unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer(&provider));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let app = Router::new()
+            .route("/traced", get(traced_handler))
+            .layer(middleware::from_fn_with_state(AppState::default(), trace_middleware));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/traced")
+                    .header("traceparent", format!("00-{}-{}-01", TRACE_ID, PARENT_SPAN_ID))
+                    .body(Body::empty())
+                    .unwrap()
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Flushing blocks on the export, which the collector answers on another worker
+        let flushing = provider.clone();
+        tokio::task::spawn_blocking(move || flushing.force_flush())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let spans = exported_spans(&received);
+        let span = spans
+            .iter()
+            .find(|span| span["name"] == "http_request")
+            .expect("http_request span exported");
+        assert_eq!(span["traceId"], TRACE_ID);
+        assert_eq!(span["parentSpanId"], PARENT_SPAN_ID);
+
+        let attributes =
+            received.lock().unwrap()[0]["resourceSpans"][0]["resource"]["attributes"].to_string();
+        assert!(attributes.contains("service.name"));
+        assert!(attributes.contains("deployment.environment"));
+
+        let shutting_down = provider.clone();
+        tokio::task::spawn_blocking(move || shutting_down.shutdown())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_outbound_requests_forward_trace_context() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = SdkTracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry().with(layer(&provider));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            format!("00-{}-{}-01", TRACE_ID, PARENT_SPAN_ID).parse().unwrap(),
+        );
+
+        let span = tracing::info_span!("outbound");
+        set_parent_from_headers(&span, &headers);
+        let _entered = span.enter();
+
+        let request = reqwest::Client::new()
+            .get("http://localhost/")
+            .propagate_trace_context()
+            .build()
+            .unwrap();
+
+        let traceparent = request.headers()["traceparent"].to_str().unwrap();
+        assert!(traceparent.starts_with(&format!("00-{}-", TRACE_ID)));
+        assert!(!traceparent.contains(PARENT_SPAN_ID));
+    }
+
+    #[test]
+    fn test_parse_resource_attributes() {
+        let attributes = parse_resource_attributes("team=platform, region = eu-west-1").unwrap();
+        assert_eq!(
+            attributes,
+            vec![
+                KeyValue::new("team", "platform"),
+                KeyValue::new("region", "eu-west-1"),
+            ]
+        );
+
+        assert!(parse_resource_attributes("").unwrap().is_empty());
+        assert!(parse_resource_attributes("missing-value").is_err());
+    }
+}
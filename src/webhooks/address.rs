@@ -0,0 +1,180 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+};
+
+use hyper_0_14::client::connect::dns::Name;
+use reqwest::{
+    dns::{Addrs, Resolve, Resolving},
+    Url,
+};
+
+/// Whether `ip` is reachable on the public internet. Loopback, private,
+/// link-local and other special-purpose addresses aren't.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_v4(mapped),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        // "This network"
+        || first == 0
+        // Carrier-grade NAT, 100.64.0.0/10
+        || (first == 100 && (64..128).contains(&second))
+        // Reserved, 240.0.0.0/4
+        || first >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local, fc00::/7
+        || (first & 0xfe00) == 0xfc00
+        // Link-local, fe80::/10
+        || (first & 0xffc0) == 0xfe80)
+}
+
+/// Which hosts webhooks may be sent to: any with only public addresses, plus
+/// the configured exceptions
+#[derive(Debug, Clone, Default)]
+pub struct AddressPolicy {
+    allowed_hosts: Arc<[String]>,
+}
+
+impl AddressPolicy {
+    pub fn new(allowed_hosts: &[String]) -> Self {
+        Self {
+            allowed_hosts: allowed_hosts.iter().map(|host| host.trim().to_ascii_lowercase()).collect(),
+        }
+    }
+
+    fn allows_any_address(&self, host: &str) -> bool {
+        let host = host.trim_start_matches('[').trim_end_matches(']').to_ascii_lowercase();
+        self.allowed_hosts.contains(&host)
+    }
+
+    /// Check a URL whose host is an IP address; names are left to `check` or
+    /// the resolver
+    pub fn check_literal(&self, url: &Url) -> Result<(), String> {
+        let host = url.host_str().ok_or_else(|| "url must have a host".to_string())?;
+        let Some(ip) = ip_literal(host) else {
+            return Ok(());
+        };
+        if is_public(ip) || self.allows_any_address(&ip.to_string()) {
+            Ok(())
+        } else {
+            Err(format!("{} is not a public address", ip))
+        }
+    }
+
+    /// Check that every address the URL's host resolves to is public
+    pub async fn check(&self, url: &Url) -> Result<(), String> {
+        self.check_literal(url)?;
+        let host = url.host_str().unwrap_or_default();
+        if ip_literal(host).is_some() || self.allows_any_address(host) {
+            return Ok(());
+        }
+
+        let port = url.port_or_known_default().unwrap_or(443);
+        let addrs: Vec<IpAddr> = tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|_| format!("{} could not be resolved", host))?
+            .map(|addr| addr.ip())
+            .collect();
+        match addrs.iter().find(|ip| !is_public(**ip)) {
+            Some(ip) => Err(format!("{} resolves to {}, which is not a public address", host, ip)),
+            None if addrs.is_empty() => Err(format!("{} could not be resolved", host)),
+            None => Ok(()),
+        }
+    }
+}
+
+// The address in a URL host such as `10.0.0.1` or `[::1]`
+fn ip_literal(host: &str) -> Option<IpAddr> {
+    host.trim_start_matches('[').trim_end_matches(']').parse().ok()
+}
+
+/// Resolver for webhook deliveries that refuses names resolving to non-public
+/// addresses.
+///
+/// Checking at connection time means a name can't pass registration and then
+/// be pointed at an internal address.
+impl Resolve for AddressPolicy {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy = self.clone();
+        Box::pin(async move {
+            let host = name.as_str();
+            let addrs: Vec<_> = tokio::net::lookup_host((host, 0)).await?.collect();
+            if !policy.allows_any_address(host)
+                && let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip()))
+            {
+                return Err(format!("{} resolves to {}, which is not a public address", host, addr.ip()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_addresses() {
+        for ip in ["93.184.215.14", "203.0.113.10", "2606:2800:21f:cb07:6820:80da:af6b:8b2c"] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check() {
+        let policy = AddressPolicy::default();
+        let check = |url: &str| {
+            let policy = policy.clone();
+            let url = Url::parse(url).unwrap();
+            async move { policy.check(&url).await }
+        };
+
+        assert!(check("https://203.0.113.10/hook").await.is_ok());
+        assert!(check("http://169.254.169.254/latest/meta-data").await.is_err());
+        assert!(check("http://[::1]:8080/hook").await.is_err());
+        assert!(check("http://localhost:8080/hook").await.is_err());
+
+        let policy = AddressPolicy::new(&["127.0.0.1".to_string(), "LOCALHOST".to_string()]);
+        assert!(policy.check(&Url::parse("http://127.0.0.1:8080/hook").unwrap()).await.is_ok());
+        assert!(policy.check(&Url::parse("http://localhost:8080/hook").unwrap()).await.is_ok());
+        assert!(policy.check(&Url::parse("http://10.0.0.1/hook").unwrap()).await.is_err());
+    }
+}
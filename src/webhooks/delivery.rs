@@ -0,0 +1,415 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use futures_util::future::join_all;
+use hmac::{Hmac, Mac};
+use reqwest::redirect::Policy;
+use sha2::Sha256;
+use tokio::task::JoinHandle;
+
+use super::{
+    address::AddressPolicy,
+    models::{DeliveryStatus, WebhookDelivery, WebhookEndpoint},
+};
+use crate::{config::WebhooksConfig, metrics, state::AppState};
+
+/// `t=<unix seconds>,v1=<hex HMAC-SHA256 of "<t>.<body>">`
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+/// Event type, e.g. `client.created`
+pub const EVENT_HEADER: &str = "x-webhook-event";
+/// Event id; the same for retries and redeliveries of one event
+pub const EVENT_ID_HEADER: &str = "x-webhook-id";
+pub const DELIVERY_ID_HEADER: &str = "x-webhook-delivery";
+
+// Deliveries attempted per poll of the worker
+const BATCH_SIZE: usize = 100;
+
+/// Signature header value for a body sent at `timestamp`
+pub fn signature(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    let digest: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("t={},v1={}", timestamp, digest)
+}
+
+/// Delay before the attempt after `failures` failed ones
+pub fn backoff(config: &WebhooksConfig, failures: u32) -> Duration {
+    let factor = 2u64.saturating_pow(failures.saturating_sub(1));
+    Duration::from_secs(
+        config
+            .retry_base_seconds
+            .saturating_mul(factor)
+            .min(config.retry_max_seconds),
+    )
+}
+
+/// HTTP client for deliveries.
+///
+/// Redirects aren't followed and names are only resolved to public addresses,
+/// so an endpoint can't be used to reach internal services.
+pub fn http_client(config: &WebhooksConfig) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_seconds.max(1)))
+        .redirect(Policy::none())
+        .dns_resolver(Arc::new(AddressPolicy::new(&config.allowed_private_hosts)))
+        .build()
+        .expect("webhook HTTP client")
+}
+
+/// Start the background job that sends due deliveries
+pub fn spawn(state: AppState) -> JoinHandle<()> {
+    let http = http_client(&state.config.webhooks);
+    let period = Duration::from_millis(state.config.webhooks.poll_interval_ms.max(10));
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            if let Err(err) = deliver_due(&state, &http).await {
+                tracing::error!(error = %err, "Failed to deliver webhooks");
+            }
+        }
+    })
+}
+
+/// Attempt every delivery that is due, returning how many were attempted
+pub async fn deliver_due(state: &AppState, http: &reqwest::Client) -> anyhow::Result<usize> {
+    let due = state.webhooks.due_deliveries(Utc::now(), BATCH_SIZE)?;
+    let attempted = due.len();
+
+    let attempts = due.into_iter().map(|delivery| async move {
+        let Some(endpoint) = state.webhooks.get_endpoint(&delivery.endpoint_id)? else {
+            return Ok(());
+        };
+        let delivery = attempt(http, &state.config.webhooks, &endpoint, delivery).await;
+        state.webhooks.save_delivery(delivery)
+    });
+    for result in join_all(attempts).await {
+        result?;
+    }
+
+    Ok(attempted)
+}
+
+// Send one delivery and record the outcome on it
+async fn attempt(
+    http: &reqwest::Client,
+    config: &WebhooksConfig,
+    endpoint: &WebhookEndpoint,
+    mut delivery: WebhookDelivery,
+) -> WebhookDelivery {
+    let body = delivery.payload.to_string();
+    let now = Utc::now();
+
+    // Addresses in the URL itself never reach the resolver
+    let literal = reqwest::Url::parse(&endpoint.url)
+        .map_err(|err| err.to_string())
+        .and_then(|url| AddressPolicy::new(&config.allowed_private_hosts).check_literal(&url));
+    if let Err(reason) = literal {
+        return failed(config, endpoint, delivery, now, None, format!("Endpoint not allowed: {}", reason));
+    }
+
+    let result = http
+        .post(&endpoint.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, signature(&endpoint.secret, now.timestamp(), body.as_bytes()))
+        .header(EVENT_HEADER, delivery.event_type.as_str())
+        .header(EVENT_ID_HEADER, &delivery.event_id)
+        .header(DELIVERY_ID_HEADER, &delivery.id)
+        .body(body)
+        .send()
+        .await;
+
+    match result {
+        Ok(response) if response.status().is_success() => {
+            record_attempt(&mut delivery, now, Some(response.status().as_u16()), None);
+            delivery.status = DeliveryStatus::Succeeded;
+            delivery.next_attempt_at = None;
+            metrics::record_webhook_delivery("succeeded");
+            delivery
+        }
+        // Only the status is kept; the body could be anything the endpoint returns
+        Ok(response) => {
+            let status = response.status().as_u16();
+            failed(config, endpoint, delivery, now, Some(status), format!("Endpoint responded with {}", status))
+        }
+        Err(err) => failed(config, endpoint, delivery, now, None, err.to_string()),
+    }
+}
+
+fn record_attempt(delivery: &mut WebhookDelivery, now: DateTime<Utc>, status_code: Option<u16>, error: Option<String>) {
+    delivery.attempts += 1;
+    delivery.last_attempt_at = Some(now);
+    delivery.last_status_code = status_code;
+    delivery.last_error = error;
+}
+
+// Record a failed attempt, scheduling a retry or dead-lettering the delivery
+fn failed(
+    config: &WebhooksConfig,
+    endpoint: &WebhookEndpoint,
+    mut delivery: WebhookDelivery,
+    now: DateTime<Utc>,
+    status_code: Option<u16>,
+    error: String,
+) -> WebhookDelivery {
+    record_attempt(&mut delivery, now, status_code, Some(error));
+
+    if delivery.attempts >= config.max_attempts {
+        delivery.status = DeliveryStatus::DeadLettered;
+        delivery.next_attempt_at = None;
+        metrics::record_webhook_delivery("dead_lettered");
+        tracing::warn!(
+            delivery_id = %delivery.id,
+            endpoint_id = %endpoint.id,
+            attempts = delivery.attempts,
+            error = delivery.last_error.as_deref().unwrap_or_default(),
+            "Webhook delivery dead-lettered"
+        );
+    } else {
+        let delay = chrono::Duration::from_std(backoff(config, delivery.attempts)).unwrap_or_default();
+        delivery.next_attempt_at = Some(now + delay);
+        metrics::record_webhook_delivery("failed");
+    }
+
+    delivery
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        collections::BTreeSet,
+        sync::{
+            atomic::{AtomicU16, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    use axum::{
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+
+    use crate::{
+        config::AppConfig,
+        events::{ClientEvent, ClientEventType},
+        webhooks::enqueue,
+    };
+
+    // A stand-in for a subscriber: records requests and answers with `status`
+    #[derive(Clone, Default)]
+    struct Receiver {
+        requests: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+        status: Arc<AtomicU16>,
+    }
+
+    async fn receive(State(receiver): State<Receiver>, headers: HeaderMap, body: Bytes) -> StatusCode {
+        receiver.requests.lock().unwrap().push((headers, body));
+        StatusCode::from_u16(receiver.status.load(Ordering::SeqCst)).unwrap()
+    }
+
+    async fn start_receiver(status: StatusCode) -> (Receiver, String) {
+        let receiver = Receiver::default();
+        receiver.status.store(status.as_u16(), Ordering::SeqCst);
+
+        let app = Router::new().route("/hook", post(receive)).with_state(receiver.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        (receiver, url)
+    }
+
+    fn state(max_attempts: u32) -> AppState {
+        let mut config = AppConfig::default();
+        config.webhooks.max_attempts = max_attempts;
+        config.webhooks.retry_base_seconds = 0;
+        // The test receivers listen on loopback
+        config.webhooks.allowed_private_hosts = vec!["127.0.0.1".to_string()];
+        AppState::new(config)
+    }
+
+    fn register(state: &AppState, url: &str, events: &[ClientEventType]) -> WebhookEndpoint {
+        let endpoint = WebhookEndpoint {
+            id: uuid::Uuid::now_v7().to_string(),
+            url: url.to_string(),
+            secret: "0123456789abcdef".to_string(),
+            events: events.iter().copied().collect::<BTreeSet<_>>(),
+            description: None,
+            created_at: Utc::now(),
+        };
+        state.webhooks.insert_endpoint(endpoint.clone()).unwrap();
+        endpoint
+    }
+
+    fn publish(state: &AppState, event_type: ClientEventType) -> ClientEvent {
+        let client = state.clients.get("1", false).unwrap();
+        let event = ClientEvent::new(event_type, &client);
+        enqueue(state, &event).unwrap();
+        event
+    }
+
+    #[test]
+    fn test_signature() {
+        let header = signature("secret", 1700000000, b"{}");
+        assert!(header.starts_with("t=1700000000,v1="));
+        assert_eq!(header.len(), "t=1700000000,v1=".len() + 64);
+
+        assert_ne!(header, signature("other", 1700000000, b"{}"));
+        assert_ne!(header, signature("secret", 1700000001, b"{}"));
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_maximum() {
+        let config = WebhooksConfig {
+            retry_base_seconds: 10,
+            retry_max_seconds: 60,
+            ..WebhooksConfig::default()
+        };
+
+        let delays: Vec<u64> = (1..=5).map(|failures| backoff(&config, failures).as_secs()).collect();
+        assert_eq!(delays, vec![10, 20, 40, 60, 60]);
+        assert_eq!(backoff(&config, u32::MAX).as_secs(), 60);
+    }
+
+    #[tokio::test]
+    async fn test_delivers_signed_events_to_subscribers() {
+        let state = state(3);
+        let (receiver, url) = start_receiver(StatusCode::NO_CONTENT).await;
+        let endpoint = register(&state, &url, &[ClientEventType::Created]);
+        register(&state, &url, &[ClientEventType::Deleted]);
+
+        let event = publish(&state, ClientEventType::Created);
+        let http = http_client(&state.config.webhooks);
+        assert_eq!(deliver_due(&state, &http).await.unwrap(), 1);
+        assert_eq!(deliver_due(&state, &http).await.unwrap(), 0);
+
+        let requests = receiver.requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 1);
+        let (headers, body) = &requests[0];
+        assert_eq!(headers[EVENT_HEADER], "client.created");
+        assert_eq!(headers[EVENT_ID_HEADER], event.id.as_str());
+
+        // The receiver can recompute the signature from the timestamp and raw body
+        let signature_header = headers[SIGNATURE_HEADER].to_str().unwrap();
+        let timestamp: i64 = signature_header
+            .strip_prefix("t=")
+            .and_then(|rest| rest.split(',').next())
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(signature_header, signature(&endpoint.secret, timestamp, body));
+
+        let payload: ClientEvent = serde_json::from_slice(body).unwrap();
+        assert_eq!(payload, event);
+
+        let deliveries = state.webhooks.list_deliveries(&endpoint.id, None, 10).unwrap();
+        assert_eq!(deliveries[0].status, DeliveryStatus::Succeeded);
+        assert_eq!(deliveries[0].attempts, 1);
+        assert_eq!(deliveries[0].last_status_code, Some(204));
+    }
+
+    #[tokio::test]
+    async fn test_failing_deliveries_are_retried_then_dead_lettered() {
+        let state = state(2);
+        let (receiver, url) = start_receiver(StatusCode::INTERNAL_SERVER_ERROR).await;
+        let endpoint = register(&state, &url, &[ClientEventType::Updated]);
+        publish(&state, ClientEventType::Updated);
+        let http = http_client(&state.config.webhooks);
+
+        deliver_due(&state, &http).await.unwrap();
+        let delivery = state.webhooks.list_deliveries(&endpoint.id, None, 10).unwrap().remove(0);
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.last_status_code, Some(500));
+        assert_eq!(delivery.last_error.as_deref(), Some("Endpoint responded with 500"));
+        assert!(delivery.next_attempt_at.is_some());
+
+        deliver_due(&state, &http).await.unwrap();
+        let delivery = state.webhooks.get_delivery(&delivery.id).unwrap().unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::DeadLettered);
+        assert_eq!(delivery.attempts, 2);
+        assert_eq!(delivery.next_attempt_at, None);
+
+        // Dead-lettered deliveries are not retried any more
+        assert_eq!(deliver_due(&state, &http).await.unwrap(), 0);
+        assert_eq!(receiver.requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_endpoint_records_the_error() {
+        let state = state(1);
+        // Nothing listens on a port that was just released
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        drop(listener);
+
+        let endpoint = register(&state, &url, &[ClientEventType::Created]);
+        publish(&state, ClientEventType::Created);
+        deliver_due(&state, &http_client(&state.config.webhooks)).await.unwrap();
+
+        let delivery = state.webhooks.list_deliveries(&endpoint.id, None, 10).unwrap().remove(0);
+        assert_eq!(delivery.status, DeliveryStatus::DeadLettered);
+        assert_eq!(delivery.last_status_code, None);
+        assert!(delivery.last_error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_redirects_are_not_followed() {
+        let state = state(1);
+        let (target, target_url) = start_receiver(StatusCode::NO_CONTENT).await;
+        let app = Router::new().route(
+            "/hook",
+            post(move || {
+                let target_url = target_url.clone();
+                async move { axum::response::Redirect::temporary(&target_url) }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let endpoint = register(&state, &url, &[ClientEventType::Created]);
+        publish(&state, ClientEventType::Created);
+        deliver_due(&state, &http_client(&state.config.webhooks)).await.unwrap();
+
+        let delivery = state.webhooks.list_deliveries(&endpoint.id, None, 10).unwrap().remove(0);
+        assert_eq!(delivery.status, DeliveryStatus::DeadLettered);
+        assert_eq!(delivery.last_status_code, Some(307));
+        assert!(target.requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_non_public_addresses_are_refused() {
+        let (receiver, url) = start_receiver(StatusCode::NO_CONTENT).await;
+        let port = url.trim_start_matches("http://127.0.0.1:").trim_end_matches("/hook");
+
+        let mut config = AppConfig::default();
+        config.webhooks.max_attempts = 1;
+        let state = AppState::new(config);
+        // Stored directly, as if registered before the address changed
+        let literal = register(&state, &url, &[ClientEventType::Created]);
+        let name = register(&state, &format!("http://localhost:{}/hook", port), &[ClientEventType::Created]);
+        publish(&state, ClientEventType::Created);
+        deliver_due(&state, &http_client(&state.config.webhooks)).await.unwrap();
+
+        for endpoint in [literal, name] {
+            let delivery = state.webhooks.list_deliveries(&endpoint.id, None, 10).unwrap().remove(0);
+            assert_eq!(delivery.status, DeliveryStatus::DeadLettered, "{}", endpoint.url);
+            assert_eq!(delivery.last_status_code, None);
+        }
+        assert!(receiver.requests.lock().unwrap().is_empty());
+    }
+}
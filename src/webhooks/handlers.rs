@@ -0,0 +1,355 @@
+use std::collections::BTreeSet;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use super::{
+    address::AddressPolicy,
+    models::{DeliveryStatus, WebhookDelivery, WebhookEndpoint},
+};
+use crate::{
+    audit::{self, models::AuditAction},
+    auth::Principal,
+    clients::validation,
+    errors::AppError,
+    events::ClientEventType,
+    state::AppState,
+};
+
+// Entity type of webhook endpoints in the audit log
+const WEBHOOK_ENTITY: &str = "webhook";
+
+const MIN_SECRET_LEN: usize = 16;
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+/// Body for registering a webhook endpoint
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct WebhookRequest {
+    /// `http` or `https` URL that receives a `POST` per event
+    pub url: String,
+    /// Shared secret for verifying `X-Webhook-Signature`, at least 16 characters
+    pub secret: String,
+    /// Event types to deliver, e.g. `client.created`
+    pub events: BTreeSet<ClientEventType>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl WebhookRequest {
+    async fn into_endpoint(self, policy: &AddressPolicy) -> Result<WebhookEndpoint, AppError> {
+        let url = validation::required("url", &self.url)?;
+        let parsed = reqwest::Url::parse(&url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or_else(|| validation::invalid("url", "url must be an absolute http or https URL"))?;
+        // Endpoints must not reach internal services through this server
+        policy
+            .check(&parsed)
+            .await
+            .map_err(|reason| validation::invalid("url", format!("url must be publicly reachable: {}", reason)))?;
+        if self.secret.chars().count() < MIN_SECRET_LEN {
+            return Err(validation::invalid(
+                "secret",
+                format!("secret must be at least {} characters", MIN_SECRET_LEN),
+            ));
+        }
+        if self.events.is_empty() {
+            return Err(validation::invalid("events", "events must not be empty"));
+        }
+
+        Ok(WebhookEndpoint {
+            id: Uuid::now_v7().to_string(),
+            url,
+            secret: self.secret,
+            events: self.events,
+            description: validation::optional("description", self.description.as_deref())?,
+            created_at: Utc::now(),
+        })
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct WebhookListResponse {
+    pub data: Vec<WebhookEndpoint>,
+}
+
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct DeliveryListResponse {
+    pub data: Vec<WebhookDelivery>,
+}
+
+/// Query parameters for the delivery log
+#[derive(Serialize, Deserialize, Debug, Default, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeliveryQueryParams {
+    #[param(inline)]
+    pub status: Option<DeliveryStatus>,
+    /// Number of deliveries, between 1 and 500 (default 50)
+    pub limit: Option<usize>,
+}
+
+fn store_error(err: anyhow::Error) -> AppError {
+    AppError::internal_error(format!("Failed to access webhooks: {}", err))
+}
+
+fn endpoint(state: &AppState, id: &str) -> Result<WebhookEndpoint, AppError> {
+    state
+        .webhooks
+        .get_endpoint(id)
+        .map_err(store_error)?
+        .ok_or_else(|| AppError::not_found(format!("Webhook {} not found", id)))
+}
+
+fn delivery(state: &AppState, endpoint_id: &str, id: &str) -> Result<WebhookDelivery, AppError> {
+    endpoint(state, endpoint_id)?;
+    state
+        .webhooks
+        .get_delivery(id)
+        .map_err(store_error)?
+        .filter(|delivery| delivery.endpoint_id == endpoint_id)
+        .ok_or_else(|| AppError::not_found(format!("Delivery {} not found", id)))
+}
+
+fn record(
+    state: &AppState,
+    principal: &Principal,
+    action: AuditAction,
+    before: Option<&WebhookEndpoint>,
+    after: Option<&WebhookEndpoint>,
+) {
+    let id = after.or(before).map(|endpoint| endpoint.id.as_str()).unwrap_or_default();
+    audit::record_committed(
+        state.audit.as_ref(),
+        principal,
+        action,
+        WEBHOOK_ENTITY,
+        id,
+        before,
+        after,
+    );
+}
+
+/// List webhook endpoints
+#[utoipa::path(
+    get,
+    path = "/api/webhooks",
+    tag = "webhooks",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Registered endpoints", body = WebhookListResponse),
+        (status = 401, description = "Unauthorized - Missing or invalid token")
+    )
+)]
+pub async fn list_webhooks(State(state): State<AppState>) -> Result<Json<WebhookListResponse>, AppError> {
+    let data = state.webhooks.list_endpoints().map_err(store_error)?;
+    Ok(Json(WebhookListResponse { data }))
+}
+
+/// Register a webhook endpoint
+#[utoipa::path(
+    post,
+    path = "/api/webhooks",
+    tag = "webhooks",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = WebhookRequest,
+    responses(
+        (status = 201, description = "Endpoint registered", body = WebhookEndpoint,
+            headers(("Location" = String))),
+        (status = 400, description = "Invalid endpoint"),
+        (status = 401, description = "Unauthorized - Missing or invalid token")
+    )
+)]
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    principal: Principal,
+    Json(request): Json<WebhookRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let policy = AddressPolicy::new(&state.config.webhooks.allowed_private_hosts);
+    let endpoint = request.into_endpoint(&policy).await?;
+    state.webhooks.insert_endpoint(endpoint.clone()).map_err(store_error)?;
+    record(&state, &principal, AuditAction::Create, None, Some(&endpoint));
+
+    let mut headers = HeaderMap::new();
+    if let Ok(location) = HeaderValue::from_str(&format!("/api/webhooks/{}", endpoint.id)) {
+        headers.insert(header::LOCATION, location);
+    }
+
+    Ok((StatusCode::CREATED, headers, Json(endpoint)))
+}
+
+/// Get a webhook endpoint
+#[utoipa::path(
+    get,
+    path = "/api/webhooks/{id}",
+    tag = "webhooks",
+    params(("id" = String, Path, description = "Webhook id")),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "The endpoint", body = WebhookEndpoint),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Webhook not found")
+    )
+)]
+pub async fn get_webhook(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<WebhookEndpoint>, AppError> {
+    Ok(Json(endpoint(&state, &id)?))
+}
+
+/// Remove a webhook endpoint
+///
+/// Pending deliveries to the endpoint are dropped along with its delivery log.
+#[utoipa::path(
+    delete,
+    path = "/api/webhooks/{id}",
+    tag = "webhooks",
+    params(("id" = String, Path, description = "Webhook id")),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 204, description = "Endpoint removed"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Webhook not found")
+    )
+)]
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let removed = state
+        .webhooks
+        .delete_endpoint(&id)
+        .map_err(store_error)?
+        .ok_or_else(|| AppError::not_found(format!("Webhook {} not found", id)))?;
+    record(&state, &principal, AuditAction::Delete, Some(&removed), None);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List deliveries to a webhook endpoint, newest first
+#[utoipa::path(
+    get,
+    path = "/api/webhooks/{id}/deliveries",
+    tag = "webhooks",
+    params(
+        ("id" = String, Path, description = "Webhook id"),
+        DeliveryQueryParams
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Deliveries to the endpoint", body = DeliveryListResponse),
+        (status = 400, description = "Invalid query parameters"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Webhook not found")
+    )
+)]
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<DeliveryQueryParams>,
+) -> Result<Json<DeliveryListResponse>, AppError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::bad_request(format!(
+            "limit must be between 1 and {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+
+    endpoint(&state, &id)?;
+    let data = state
+        .webhooks
+        .list_deliveries(&id, params.status, limit)
+        .map_err(store_error)?;
+    Ok(Json(DeliveryListResponse { data }))
+}
+
+/// Get a delivery
+#[utoipa::path(
+    get,
+    path = "/api/webhooks/{id}/deliveries/{delivery_id}",
+    tag = "webhooks",
+    params(
+        ("id" = String, Path, description = "Webhook id"),
+        ("delivery_id" = String, Path, description = "Delivery id")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "The delivery", body = WebhookDelivery),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Webhook or delivery not found")
+    )
+)]
+pub async fn get_delivery(
+    State(state): State<AppState>,
+    Path((id, delivery_id)): Path<(String, String)>,
+) -> Result<Json<WebhookDelivery>, AppError> {
+    Ok(Json(delivery(&state, &id, &delivery_id)?))
+}
+
+/// Send a delivery again
+///
+/// Queues a new delivery of the same event and payload, with a fresh set of
+/// attempts. Works for dead-lettered deliveries as well as successful ones.
+#[utoipa::path(
+    post,
+    path = "/api/webhooks/{id}/deliveries/{delivery_id}/redeliver",
+    tag = "webhooks",
+    params(
+        ("id" = String, Path, description = "Webhook id"),
+        ("delivery_id" = String, Path, description = "Delivery id")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 202, description = "Redelivery queued", body = WebhookDelivery),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 404, description = "Webhook or delivery not found")
+    )
+)]
+pub async fn redeliver(
+    State(state): State<AppState>,
+    Path((id, delivery_id)): Path<(String, String)>,
+) -> Result<(StatusCode, Json<WebhookDelivery>), AppError> {
+    let original = delivery(&state, &id, &delivery_id)?;
+    let now = Utc::now();
+
+    let redelivery = WebhookDelivery {
+        id: Uuid::now_v7().to_string(),
+        status: DeliveryStatus::Pending,
+        attempts: 0,
+        next_attempt_at: Some(now),
+        last_attempt_at: None,
+        last_status_code: None,
+        last_error: None,
+        created_at: now,
+        redelivery_of: Some(original.id.clone()),
+        ..original
+    };
+    state.webhooks.save_delivery(redelivery.clone()).map_err(store_error)?;
+
+    Ok((StatusCode::ACCEPTED, Json(redelivery)))
+}
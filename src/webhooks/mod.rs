@@ -0,0 +1,45 @@
+pub mod address;
+pub mod delivery;
+pub mod handlers;
+pub mod models;
+pub mod routes;
+pub mod store;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use self::models::{DeliveryStatus, WebhookDelivery};
+use crate::{events::ClientEvent, state::AppState};
+
+/// Queue a delivery of the event to every endpoint subscribed to its type
+pub fn enqueue(state: &AppState, event: &ClientEvent) -> anyhow::Result<()> {
+    if !state.config.webhooks.enabled {
+        return Ok(());
+    }
+
+    let payload = serde_json::to_value(event)?;
+    let now = Utc::now();
+
+    for endpoint in state.webhooks.list_endpoints()? {
+        if !endpoint.events.contains(&event.event_type) {
+            continue;
+        }
+        state.webhooks.save_delivery(WebhookDelivery {
+            id: Uuid::now_v7().to_string(),
+            endpoint_id: endpoint.id,
+            event_id: event.id.clone(),
+            event_type: event.event_type,
+            payload: payload.clone(),
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: Some(now),
+            last_attempt_at: None,
+            last_status_code: None,
+            last_error: None,
+            created_at: now,
+            redelivery_of: None,
+        })?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::events::ClientEventType;
+
+/// A URL that receives client events
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct WebhookEndpoint {
+    pub id: String,
+    pub url: String,
+    /// Key for the `X-Webhook-Signature` HMAC; never returned
+    #[serde(skip_serializing)]
+    pub secret: String,
+    /// Event types delivered to this endpoint
+    pub events: BTreeSet<ClientEventType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Where a delivery stands
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Waiting for its first attempt or a retry
+    Pending,
+    Succeeded,
+    /// Gave up after the configured number of attempts
+    DeadLettered,
+}
+
+/// One event sent to one endpoint, with the outcome of its latest attempt
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub endpoint_id: String,
+    pub event_id: String,
+    pub event_type: ClientEventType,
+    /// The request body, exactly as signed and sent
+    #[schema(value_type = Object)]
+    pub payload: Value,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    /// When the next attempt is due, while the delivery is pending
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    /// HTTP status of the latest response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_status_code: Option<u16>,
+    /// Why the latest attempt failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// The delivery this one was manually redelivered from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redelivery_of: Option<String>,
+}
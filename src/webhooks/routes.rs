@@ -0,0 +1,135 @@
+use axum::{
+    routing::{get, post},
+    Router,
+};
+
+use super::handlers;
+use crate::state::AppState;
+
+pub fn api_routes(state: AppState) -> Router {
+    // This router doesn't include authentication -
+    // Authentication is added in main.rs
+    Router::new()
+        .route("/", get(handlers::list_webhooks).post(handlers::create_webhook))
+        .route("/:id", get(handlers::get_webhook).delete(handlers::delete_webhook))
+        .route("/:id/deliveries", get(handlers::list_deliveries))
+        .route("/:id/deliveries/:delivery_id", get(handlers::get_delivery))
+        .route(
+            "/:id/deliveries/:delivery_id/redeliver",
+            post(handlers::redeliver),
+        )
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{auth, clients};
+    use axum::{
+        body::{Body, to_bytes},
+        http::{header, Request, StatusCode},
+        middleware,
+    };
+    use serde_json::{json, Value};
+    use tower::util::ServiceExt;
+
+    // Webhook and client APIs behind the auth middleware, as mounted in main.rs
+    fn app(state: AppState) -> Router {
+        Router::new()
+            .nest("/webhooks", api_routes(state.clone()))
+            .nest("/clients", clients::routes::api_routes(state.clone()))
+            .layer(middleware::from_fn_with_state(state, auth::auth_middleware))
+    }
+
+    async fn send(app: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, auth::DEV_TOKEN)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.map(|body| Body::from(body.to_string())).unwrap_or_default())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_webhook_routes() {
+        let app = app(AppState::default());
+
+        let (status, body) = send(
+            &app,
+            "POST",
+            "/webhooks",
+            Some(json!({ "url": "ftp://example.com", "secret": "0123456789abcdef", "events": ["client.created"] })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["details"]["field"], "url");
+
+        let (status, body) = send(
+            &app,
+            "POST",
+            "/webhooks",
+            Some(json!({ "url": "https://203.0.113.10/hook", "secret": "short", "events": ["client.created"] })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["details"]["field"], "secret");
+
+        // Internal addresses can't be registered
+        for url in ["http://169.254.169.254/latest/meta-data", "http://127.0.0.1:8080/hook", "http://localhost/hook"] {
+            let (status, body) = send(
+                &app,
+                "POST",
+                "/webhooks",
+                Some(json!({ "url": url, "secret": "0123456789abcdef", "events": ["client.created"] })),
+            )
+            .await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", url);
+            assert_eq!(body["error"]["details"]["field"], "url");
+        }
+
+        let (status, endpoint) = send(
+            &app,
+            "POST",
+            "/webhooks",
+            Some(json!({
+                "url": "https://203.0.113.10/hook",
+                "secret": "0123456789abcdef",
+                "events": ["client.created", "client.deleted"]
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(endpoint.get("secret").is_none());
+        let uri = format!("/webhooks/{}", endpoint["id"].as_str().unwrap());
+
+        // Creating a client queues a delivery; updates aren't subscribed to
+        send(&app, "POST", "/clients", Some(json!({ "name": "Acme" }))).await;
+        let (status, body) = send(&app, "GET", &format!("{}/deliveries?status=pending", uri), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let deliveries = body["data"].as_array().unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0]["event_type"], "client.created");
+        assert_eq!(deliveries[0]["payload"]["data"]["name"], "Acme");
+
+        let delivery_uri = format!("{}/deliveries/{}", uri, deliveries[0]["id"].as_str().unwrap());
+        let (status, redelivery) = send(&app, "POST", &format!("{}/redeliver", delivery_uri), None).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(redelivery["redelivery_of"], deliveries[0]["id"]);
+        assert_eq!(redelivery["event_id"], deliveries[0]["event_id"]);
+        assert_eq!(redelivery["attempts"], 0);
+
+        let (status, _) = send(&app, "GET", "/webhooks/missing/deliveries", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = send(&app, "DELETE", &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&app, "GET", &delivery_uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
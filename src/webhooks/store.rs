@@ -0,0 +1,140 @@
+use std::{
+    collections::BTreeMap,
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use chrono::{DateTime, Utc};
+
+use super::models::{DeliveryStatus, WebhookDelivery, WebhookEndpoint};
+
+/// Storage for webhook endpoints and their delivery log
+pub trait WebhookStore: Send + Sync {
+    /// All endpoints, oldest first
+    fn list_endpoints(&self) -> anyhow::Result<Vec<WebhookEndpoint>>;
+
+    fn get_endpoint(&self, id: &str) -> anyhow::Result<Option<WebhookEndpoint>>;
+
+    fn insert_endpoint(&self, endpoint: WebhookEndpoint) -> anyhow::Result<()>;
+
+    /// Remove an endpoint and its deliveries, returning it if it existed
+    fn delete_endpoint(&self, id: &str) -> anyhow::Result<Option<WebhookEndpoint>>;
+
+    /// Add a delivery, or replace the one with the same id. Deliveries to an
+    /// endpoint that no longer exists are dropped.
+    fn save_delivery(&self, delivery: WebhookDelivery) -> anyhow::Result<()>;
+
+    fn get_delivery(&self, id: &str) -> anyhow::Result<Option<WebhookDelivery>>;
+
+    /// Deliveries to an endpoint, newest first
+    fn list_deliveries(
+        &self,
+        endpoint_id: &str,
+        status: Option<DeliveryStatus>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<WebhookDelivery>>;
+
+    /// Pending deliveries whose next attempt is due at `now`, oldest first
+    fn due_deliveries(&self, now: DateTime<Utc>, limit: usize) -> anyhow::Result<Vec<WebhookDelivery>>;
+}
+
+/// A [`WebhookStore`] that keeps endpoints and deliveries in memory
+#[derive(Default)]
+pub struct InMemoryWebhookStore {
+    endpoints: RwLock<BTreeMap<String, WebhookEndpoint>>,
+    // Keyed by UUIDv7 id, so iteration is in creation order
+    deliveries: RwLock<BTreeMap<String, WebhookDelivery>>,
+}
+
+impl InMemoryWebhookStore {
+    fn endpoints(&self) -> anyhow::Result<RwLockReadGuard<'_, BTreeMap<String, WebhookEndpoint>>> {
+        self.endpoints
+            .read()
+            .map_err(|_| anyhow::anyhow!("webhook endpoint lock poisoned"))
+    }
+
+    fn endpoints_mut(&self) -> anyhow::Result<RwLockWriteGuard<'_, BTreeMap<String, WebhookEndpoint>>> {
+        self.endpoints
+            .write()
+            .map_err(|_| anyhow::anyhow!("webhook endpoint lock poisoned"))
+    }
+
+    fn deliveries(&self) -> anyhow::Result<RwLockReadGuard<'_, BTreeMap<String, WebhookDelivery>>> {
+        self.deliveries
+            .read()
+            .map_err(|_| anyhow::anyhow!("webhook delivery lock poisoned"))
+    }
+
+    fn deliveries_mut(&self) -> anyhow::Result<RwLockWriteGuard<'_, BTreeMap<String, WebhookDelivery>>> {
+        self.deliveries
+            .write()
+            .map_err(|_| anyhow::anyhow!("webhook delivery lock poisoned"))
+    }
+}
+
+impl WebhookStore for InMemoryWebhookStore {
+    fn list_endpoints(&self) -> anyhow::Result<Vec<WebhookEndpoint>> {
+        Ok(self.endpoints()?.values().cloned().collect())
+    }
+
+    fn get_endpoint(&self, id: &str) -> anyhow::Result<Option<WebhookEndpoint>> {
+        Ok(self.endpoints()?.get(id).cloned())
+    }
+
+    fn insert_endpoint(&self, endpoint: WebhookEndpoint) -> anyhow::Result<()> {
+        self.endpoints_mut()?.insert(endpoint.id.clone(), endpoint);
+        Ok(())
+    }
+
+    fn delete_endpoint(&self, id: &str) -> anyhow::Result<Option<WebhookEndpoint>> {
+        // Lock order: endpoints, then deliveries
+        let mut endpoints = self.endpoints_mut()?;
+        let removed = endpoints.remove(id);
+        if removed.is_some() {
+            self.deliveries_mut()?.retain(|_, delivery| delivery.endpoint_id != id);
+        }
+        Ok(removed)
+    }
+
+    fn save_delivery(&self, delivery: WebhookDelivery) -> anyhow::Result<()> {
+        // Hold the endpoint lock so a concurrent delete can't leave an orphan behind
+        let endpoints = self.endpoints()?;
+        if endpoints.contains_key(&delivery.endpoint_id) {
+            self.deliveries_mut()?.insert(delivery.id.clone(), delivery);
+        }
+        Ok(())
+    }
+
+    fn get_delivery(&self, id: &str) -> anyhow::Result<Option<WebhookDelivery>> {
+        Ok(self.deliveries()?.get(id).cloned())
+    }
+
+    fn list_deliveries(
+        &self,
+        endpoint_id: &str,
+        status: Option<DeliveryStatus>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<WebhookDelivery>> {
+        Ok(self
+            .deliveries()?
+            .values()
+            .rev()
+            .filter(|delivery| delivery.endpoint_id == endpoint_id)
+            .filter(|delivery| status.is_none_or(|status| delivery.status == status))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    fn due_deliveries(&self, now: DateTime<Utc>, limit: usize) -> anyhow::Result<Vec<WebhookDelivery>> {
+        Ok(self
+            .deliveries()?
+            .values()
+            .filter(|delivery| {
+                delivery.status == DeliveryStatus::Pending
+                    && delivery.next_attempt_at.is_some_and(|due| due <= now)
+            })
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+}
@@ -0,0 +1,75 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, Query, State, ws::WebSocketUpgrade},
+    http::{HeaderMap, header},
+    response::Response,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use super::session;
+use crate::{auth, client_ip, errors::AppError, lockout::Attempt, state::AppState};
+
+/// Query parameters for the WebSocket handshake
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WsParams {
+    /// Bearer token, for clients that can't set an `Authorization` header
+    pub access_token: Option<String>,
+}
+
+/// Open a WebSocket session
+///
+/// Authenticates with the `Authorization` header or the `access_token` query
+/// parameter, then speaks a JSON protocol: `subscribe`, `unsubscribe`, `ping`
+/// and `command` messages from the client, and `subscriptions`, `pong`,
+/// `result`, `event`, `resync` and `error` messages from the server.
+#[utoipa::path(
+    get,
+    path = "/api/ws",
+    tag = "clients",
+    params(WsParams),
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 101, description = "Switched to the WebSocket protocol"),
+        (status = 401, description = "Unauthorized - Missing or invalid token"),
+        (status = 429, description = "Locked out after repeated authentication failures"),
+        (status = 426, description = "Not a WebSocket handshake")
+    )
+)]
+pub async fn ws_handler(
+    State(state): State<AppState>,
+    Query(params): Query<WsParams>,
+    headers: HeaderMap,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    upgrade: Option<WebSocketUpgrade>,
+) -> Result<Response, AppError> {
+    let query_token = params.access_token.map(|token| format!("Bearer {}", token));
+    let auth_header = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .or(query_token.as_deref());
+
+    // Guesses over WebSocket count towards the same lockouts as auth_middleware
+    let ip = client_ip::from_parts(&headers, connect_info.as_ref(), state.config.http.trust_forwarded_for);
+    let attempt = Attempt::new(&state, ip, auth_header);
+    attempt.check()?;
+    let Some(principal) = auth::authenticate(auth_header) else {
+        if auth_header.is_some() {
+            attempt.failed()?;
+        }
+        return Err(AppError::unauthorized("Authentication required"));
+    };
+    attempt.succeeded();
+
+    let upgrade =
+        upgrade.ok_or_else(|| AppError::upgrade_required("Expected a WebSocket handshake"))?;
+    let max_message_bytes = state.config.websocket.max_message_bytes;
+
+    Ok(upgrade
+        .max_message_size(max_message_bytes)
+        .on_upgrade(move |socket| session::run(socket, state, principal)))
+}
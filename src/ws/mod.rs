@@ -0,0 +1,4 @@
+pub mod handlers;
+pub mod protocol;
+pub mod routes;
+pub mod session;
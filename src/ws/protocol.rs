@@ -0,0 +1,135 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::events::{ClientEvent, ClientEventType};
+
+/// A message from the client. `id` is optional and echoed in the reply, so
+/// replies can be matched to requests.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Start receiving events of these types; all types when empty
+    Subscribe {
+        #[serde(default)]
+        id: Option<String>,
+        #[serde(default)]
+        events: BTreeSet<ClientEventType>,
+    },
+    /// Stop receiving events of these types; all types when empty
+    Unsubscribe {
+        #[serde(default)]
+        id: Option<String>,
+        #[serde(default)]
+        events: BTreeSet<ClientEventType>,
+    },
+    Ping {
+        #[serde(default)]
+        id: Option<String>,
+    },
+    /// Run a named command, e.g. `get_client` with `{"id": "1"}`
+    Command {
+        #[serde(default)]
+        id: Option<String>,
+        command: String,
+        #[serde(default)]
+        args: Value,
+    },
+}
+
+/// A message from the server
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// Event types the connection is subscribed to after a `subscribe` or `unsubscribe`
+    Subscriptions {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        events: BTreeSet<ClientEventType>,
+    },
+    Pong {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+    },
+    /// Output of a command
+    Result {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        data: Value,
+    },
+    Event {
+        /// Same numbering as the sequence that ends `id` on the SSE stream
+        sequence: u64,
+        event: ClientEvent,
+    },
+    /// Events were missed; reload whatever is derived from them
+    Resync { reason: &'static str },
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        code: &'static str,
+        message: String,
+    },
+}
+
+impl ServerMessage {
+    pub fn error(id: Option<String>, code: &'static str, message: impl Into<String>) -> Self {
+        Self::Error {
+            id,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_client_messages() {
+        let message: ClientMessage = serde_json::from_value(
+            json!({ "type": "subscribe", "id": "1", "events": ["client.created"] }),
+        )
+        .unwrap();
+        assert_eq!(
+            message,
+            ClientMessage::Subscribe {
+                id: Some("1".to_string()),
+                events: BTreeSet::from([ClientEventType::Created]),
+            }
+        );
+
+        let message: ClientMessage =
+            serde_json::from_value(json!({ "type": "unsubscribe" })).unwrap();
+        assert_eq!(
+            message,
+            ClientMessage::Unsubscribe {
+                id: None,
+                events: BTreeSet::new(),
+            }
+        );
+
+        assert!(serde_json::from_value::<ClientMessage>(json!({ "type": "shout" })).is_err());
+        assert!(serde_json::from_value::<ClientMessage>(json!({ "type": "command" })).is_err());
+    }
+
+    #[test]
+    fn test_serialize_server_messages() {
+        assert_eq!(
+            serde_json::to_value(ServerMessage::Pong { id: None }).unwrap(),
+            json!({ "type": "pong" })
+        );
+        assert_eq!(
+            serde_json::to_value(ServerMessage::error(
+                Some("7".to_string()),
+                "rate_limited",
+                "Slow down"
+            ))
+            .unwrap(),
+            json!({ "type": "error", "id": "7", "code": "rate_limited", "message": "Slow down" })
+        );
+    }
+}
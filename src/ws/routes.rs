@@ -0,0 +1,198 @@
+use axum::{Router, routing::get};
+
+use super::handlers;
+use crate::state::AppState;
+
+pub fn api_routes(state: AppState) -> Router {
+    // Authenticates in the handler rather than with auth_middleware, since
+    // browsers can't set headers on a WebSocket handshake
+    Router::new()
+        .route("/", get(handlers::ws_handler))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::{Value, json};
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::{
+        MaybeTlsStream, WebSocketStream, connect_async,
+        tungstenite::{Error, Message, client::IntoClientRequest},
+    };
+
+    use crate::{
+        auth,
+        config::AppConfig,
+        events::{self, ClientEvent, ClientEventType},
+    };
+
+    type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    async fn serve(state: AppState) -> String {
+        let app = Router::new().nest("/api/ws", api_routes(state));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/api/ws", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        url
+    }
+
+    async fn connect(url: &str) -> Socket {
+        let mut request = url.into_client_request().unwrap();
+        request.headers_mut().insert(
+            axum::http::header::AUTHORIZATION,
+            auth::DEV_TOKEN.parse().unwrap(),
+        );
+        connect_async(request).await.unwrap().0
+    }
+
+    async fn send(socket: &mut Socket, message: Value) {
+        socket
+            .send(Message::Text(message.to_string()))
+            .await
+            .unwrap();
+    }
+
+    // Next JSON message from the server, skipping heartbeats
+    async fn receive(socket: &mut Socket) -> Value {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+                .await
+                .expect("message from server")
+                .unwrap()
+                .unwrap();
+            if let Message::Text(text) = message {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handshake_requires_token() {
+        let state = AppState::default();
+        let url = serve(state).await;
+
+        match connect_async(url.as_str()).await {
+            Err(Error::Http(response)) => assert_eq!(response.status(), 401),
+            other => panic!(
+                "expected 401, got {:?}",
+                other.map(|(_, response)| response.status())
+            ),
+        }
+
+        // Browsers can pass the token in the query string instead
+        let (_, response) = connect_async(format!("{}?access_token=dev_token", url))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 101);
+    }
+
+    #[tokio::test]
+    async fn test_ping_subscribe_and_commands() {
+        let state = AppState::default();
+        let url = serve(state.clone()).await;
+        let mut socket = connect(&url).await;
+
+        send(&mut socket, json!({ "type": "ping", "id": "p1" })).await;
+        assert_eq!(
+            receive(&mut socket).await,
+            json!({ "type": "pong", "id": "p1" })
+        );
+
+        send(
+            &mut socket,
+            json!({ "type": "subscribe", "events": ["client.deleted"] }),
+        )
+        .await;
+        assert_eq!(
+            receive(&mut socket).await,
+            json!({ "type": "subscriptions", "events": ["client.deleted"] })
+        );
+
+        let client = state.clients.get("1", false).unwrap();
+        events::publish(&state, ClientEvent::new(ClientEventType::Updated, &client));
+        events::publish(&state, ClientEvent::new(ClientEventType::Deleted, &client));
+        let event = receive(&mut socket).await;
+        assert_eq!(event["type"], "event");
+        assert_eq!(event["sequence"], 2);
+        assert_eq!(event["event"]["type"], "client.deleted");
+
+        send(
+            &mut socket,
+            json!({ "type": "command", "id": "c1", "command": "get_client", "args": { "id": "1" } }),
+        )
+        .await;
+        let result = receive(&mut socket).await;
+        assert_eq!(result["type"], "result");
+        assert_eq!(result["id"], "c1");
+        assert_eq!(result["data"]["name"], "Example Client");
+
+        send(
+            &mut socket,
+            json!({ "type": "command", "command": "get_client", "args": { "id": "missing" } }),
+        )
+        .await;
+        assert_eq!(receive(&mut socket).await["code"], "not_found");
+
+        send(
+            &mut socket,
+            json!({ "type": "command", "command": "drop_tables" }),
+        )
+        .await;
+        assert_eq!(receive(&mut socket).await["code"], "unknown_command");
+
+        send(&mut socket, json!({ "type": "shout" })).await;
+        assert_eq!(receive(&mut socket).await["code"], "invalid_message");
+
+        send(
+            &mut socket,
+            json!({ "type": "command", "command": "list_clients", "args": { "limit": 5 } }),
+        )
+        .await;
+        assert_eq!(receive(&mut socket).await["data"]["data"][0]["id"], "1");
+    }
+
+    #[tokio::test]
+    async fn test_messages_are_rate_limited() {
+        let mut config = AppConfig::default();
+        config.websocket.messages_per_second = 0;
+        config.websocket.message_burst = 2;
+        let url = serve(AppState::new(config)).await;
+        let mut socket = connect(&url).await;
+
+        for _ in 0..3 {
+            send(&mut socket, json!({ "type": "ping" })).await;
+        }
+        assert_eq!(receive(&mut socket).await["type"], "pong");
+        assert_eq!(receive(&mut socket).await["type"], "pong");
+        let error = receive(&mut socket).await;
+        assert_eq!(error["type"], "error");
+        assert_eq!(error["code"], "rate_limited");
+    }
+
+    #[tokio::test]
+    async fn test_idle_connections_are_closed() {
+        let mut config = AppConfig::default();
+        config.websocket.heartbeat_seconds = 1;
+        config.websocket.idle_timeout_seconds = 1;
+        let url = serve(AppState::new(config)).await;
+        let mut socket = connect(&url).await;
+
+        // Reading answers server pings, but this client never sends anything itself
+        // before the timeout runs out, apart from those pongs
+        let close = loop {
+            match tokio::time::timeout(Duration::from_secs(5), socket.next())
+                .await
+                .unwrap()
+            {
+                Some(Ok(Message::Close(frame))) => break frame,
+                Some(Ok(_)) => continue,
+                other => panic!("expected a close frame, got {:?}", other),
+            }
+        };
+        assert_eq!(u16::from(close.unwrap().code), 1001);
+    }
+}
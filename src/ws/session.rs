@@ -0,0 +1,317 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::{
+    sync::{
+        broadcast::error::RecvError,
+        mpsc::{self, error::TrySendError},
+    },
+    time::Instant,
+};
+
+use super::protocol::{ClientMessage, ServerMessage};
+use crate::{
+    auth::Principal,
+    clients::repository::ListQuery,
+    errors::AppError,
+    events::{ClientEventType, Sequenced},
+    rate_limit::TokenBucket,
+    state::AppState,
+};
+
+// Close codes from RFC 6455
+const CLOSE_GOING_AWAY: u16 = 1001;
+const CLOSE_TRY_AGAIN_LATER: u16 = 1013;
+
+const ALL_EVENT_TYPES: [ClientEventType; 3] = [
+    ClientEventType::Created,
+    ClientEventType::Updated,
+    ClientEventType::Deleted,
+];
+const DEFAULT_LIST_LIMIT: usize = 20;
+const MAX_LIST_LIMIT: usize = 100;
+// How long a closing connection gets to flush its queue
+const CLOSE_GRACE: Duration = Duration::from_secs(5);
+
+// Why the server ended a session
+enum Close {
+    /// The peer went away or the connection failed; nothing left to send
+    Gone,
+    /// Close with this code and reason
+    With(u16, &'static str),
+}
+
+struct Session {
+    state: AppState,
+    principal: Principal,
+    outbox: mpsc::Sender<Message>,
+    subscriptions: BTreeSet<ClientEventType>,
+    limiter: TokenBucket,
+}
+
+/// Serve one WebSocket connection until either side closes it.
+///
+/// Outgoing messages go through a bounded queue drained by a writer task.
+/// Replies wait for space in the queue, which stops reading from a client
+/// that doesn't read its replies. Events never wait: a subscriber whose queue
+/// is full is disconnected.
+pub async fn run(socket: WebSocket, state: AppState, principal: Principal) {
+    let config = &state.config.websocket;
+    let heartbeat = Duration::from_secs(config.heartbeat_seconds.max(1));
+    let idle_timeout = Duration::from_secs(config.idle_timeout_seconds.max(1));
+
+    let (mut sink, mut stream) = socket.split();
+    let (outbox, mut queue) = mpsc::channel::<Message>(config.send_queue_size.max(1));
+    let writer = tokio::spawn(async move {
+        while let Some(message) = queue.recv().await {
+            let closing = matches!(message, Message::Close(_));
+            if sink.send(message).await.is_err() || closing {
+                break;
+            }
+        }
+    });
+
+    let mut events = state.events.subscribe(None).receiver;
+    let mut session = Session {
+        limiter: TokenBucket::new(f64::from(config.messages_per_second), config.message_burst),
+        state: state.clone(),
+        principal,
+        outbox: outbox.clone(),
+        subscriptions: BTreeSet::new(),
+    };
+    tracing::debug!(subject = %session.principal.subject, "WebSocket session opened");
+
+    let mut heartbeats = tokio::time::interval_at(Instant::now() + heartbeat, heartbeat);
+    let mut last_seen = Instant::now();
+
+    let close = loop {
+        tokio::select! {
+            message = stream.next() => {
+                let Some(Ok(message)) = message else {
+                    break Close::Gone;
+                };
+                last_seen = Instant::now();
+                let result = match message {
+                    Message::Text(text) => session.receive(&text).await,
+                    Message::Binary(_) => {
+                        session
+                            .reply(ServerMessage::error(None, "unsupported", "Only text messages are supported"))
+                            .await
+                    }
+                    // Pings are answered by axum; pongs only count as activity
+                    Message::Ping(_) | Message::Pong(_) => Ok(()),
+                    Message::Close(_) => Err(Close::Gone),
+                };
+                if let Err(close) = result {
+                    break close;
+                }
+            }
+            event = events.recv() => {
+                let result = match event {
+                    Ok(event) => session.forward(event),
+                    Err(RecvError::Lagged(_)) if !session.subscriptions.is_empty() => {
+                        session.push(ServerMessage::Resync { reason: "subscriber_lagged" })
+                    }
+                    Err(RecvError::Lagged(_)) => Ok(()),
+                    Err(RecvError::Closed) => Err(Close::Gone),
+                };
+                if let Err(close) = result {
+                    break close;
+                }
+            }
+            _ = heartbeats.tick() => {
+                if last_seen.elapsed() >= idle_timeout {
+                    break Close::With(CLOSE_GOING_AWAY, "Idle timeout");
+                }
+                if let Err(close) = session.try_send(Message::Ping(Vec::new())) {
+                    break close;
+                }
+            }
+        }
+    };
+
+    tracing::debug!(subject = %session.principal.subject, "WebSocket session closed");
+    drop(session);
+
+    let abort = writer.abort_handle();
+    if let Close::With(code, reason) = close {
+        let frame = CloseFrame {
+            code,
+            reason: reason.into(),
+        };
+        // A full queue means the peer isn't reading, so don't wait to say goodbye
+        if outbox.try_send(Message::Close(Some(frame))).is_err() {
+            abort.abort();
+        }
+    }
+    drop(outbox);
+    if tokio::time::timeout(CLOSE_GRACE, writer).await.is_err() {
+        abort.abort();
+    }
+}
+
+impl Session {
+    // Handle one text message from the client
+    async fn receive(&mut self, text: &str) -> Result<(), Close> {
+        if !self.limiter.try_acquire() {
+            // Don't wait on a client that is flooding us; with a full queue it misses the error
+            return match self.push(ServerMessage::error(
+                None,
+                "rate_limited",
+                "Too many messages",
+            )) {
+                Err(Close::With(..)) => Ok(()),
+                result => result,
+            };
+        }
+
+        let message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(err) => {
+                return self
+                    .reply(ServerMessage::error(
+                        None,
+                        "invalid_message",
+                        err.to_string(),
+                    ))
+                    .await;
+            }
+        };
+
+        let reply = match message {
+            ClientMessage::Subscribe { id, events } => {
+                if events.is_empty() {
+                    self.subscriptions.extend(ALL_EVENT_TYPES);
+                } else {
+                    self.subscriptions.extend(events);
+                }
+                ServerMessage::Subscriptions {
+                    id,
+                    events: self.subscriptions.clone(),
+                }
+            }
+            ClientMessage::Unsubscribe { id, events } => {
+                if events.is_empty() {
+                    self.subscriptions.clear();
+                } else {
+                    self.subscriptions
+                        .retain(|event_type| !events.contains(event_type));
+                }
+                ServerMessage::Subscriptions {
+                    id,
+                    events: self.subscriptions.clone(),
+                }
+            }
+            ClientMessage::Ping { id } => ServerMessage::Pong { id },
+            ClientMessage::Command { id, command, args } => match self.command(&command, args) {
+                Some(Ok(data)) => ServerMessage::Result { id, data },
+                Some(Err(err)) => ServerMessage::error(id, error_code(&err), err.message),
+                None => ServerMessage::error(
+                    id,
+                    "unknown_command",
+                    format!("Unknown command {}", command),
+                ),
+            },
+        };
+
+        self.reply(reply).await
+    }
+
+    // Run a command, or `None` if there is no such command
+    fn command(&self, command: &str, args: Value) -> Option<Result<Value, AppError>> {
+        match command {
+            "get_client" => Some(self.get_client(args)),
+            "list_clients" => Some(self.list_clients(args)),
+            _ => None,
+        }
+    }
+
+    fn get_client(&self, args: Value) -> Result<Value, AppError> {
+        let args: GetClientArgs = parse_args(args)?;
+        let client = self.state.clients.get(&args.id, false)?;
+        Ok(json!(client))
+    }
+
+    fn list_clients(&self, args: Value) -> Result<Value, AppError> {
+        let args: ListClientsArgs = parse_args(args)?;
+        let limit = args.limit.unwrap_or(DEFAULT_LIST_LIMIT);
+        if !(1..=MAX_LIST_LIMIT).contains(&limit) {
+            return Err(AppError::bad_request(format!(
+                "limit must be between 1 and {}",
+                MAX_LIST_LIMIT
+            )));
+        }
+        let page = self.state.clients.list(&ListQuery {
+            limit,
+            cursor: args.cursor,
+            name_prefix: args.name_prefix,
+            ..ListQuery::default()
+        })?;
+        Ok(json!({ "data": page.items, "next_cursor": page.next_cursor }))
+    }
+
+    // Send an event if the connection subscribed to its type
+    fn forward(&self, event: Sequenced) -> Result<(), Close> {
+        if !self.subscriptions.contains(&event.event.event_type) {
+            return Ok(());
+        }
+        self.push(ServerMessage::Event {
+            sequence: event.sequence,
+            event: event.event,
+        })
+    }
+
+    // Queue a reply, waiting for space
+    async fn reply(&self, message: ServerMessage) -> Result<(), Close> {
+        self.outbox
+            .send(encode(&message))
+            .await
+            .map_err(|_| Close::Gone)
+    }
+
+    // Queue a message without waiting; a full queue means the client can't keep up
+    fn push(&self, message: ServerMessage) -> Result<(), Close> {
+        self.try_send(encode(&message))
+    }
+
+    fn try_send(&self, message: Message) -> Result<(), Close> {
+        self.outbox.try_send(message).map_err(|err| match err {
+            TrySendError::Full(_) => Close::With(CLOSE_TRY_AGAIN_LATER, "Too far behind"),
+            TrySendError::Closed(_) => Close::Gone,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct GetClientArgs {
+    id: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ListClientsArgs {
+    limit: Option<usize>,
+    cursor: Option<String>,
+    name_prefix: Option<String>,
+}
+
+fn parse_args<T: for<'de> Deserialize<'de>>(args: Value) -> Result<T, AppError> {
+    let args = if args.is_null() { json!({}) } else { args };
+    serde_json::from_value(args)
+        .map_err(|err| AppError::bad_request(format!("Invalid args: {}", err)))
+}
+
+fn error_code(err: &AppError) -> &'static str {
+    match err.status.as_u16() {
+        400 => "invalid_request",
+        404 => "not_found",
+        _ => "internal_error",
+    }
+}
+
+fn encode(message: &ServerMessage) -> Message {
+    Message::Text(serde_json::to_string(message).unwrap_or_default())
+}